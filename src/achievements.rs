@@ -0,0 +1,77 @@
+use crate::history::History;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Number of nights in a row needed for the streak badge
+const STREAK_NIGHTS: u64 = 7;
+
+/// Number of nights needed for the tracking badge
+const TRACKED_NIGHTS: usize = 30;
+
+/// Number of nights in a row without skipping needed for the no-snooze badge
+const UNSKIPPED_NIGHTS: usize = 30;
+
+/// Badge a user can unlock by keeping good sleep habits
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Badge {
+    Streak,
+    Tracked,
+    NoSnooze,
+}
+
+impl Badge {
+    pub const ALL: [Badge; 3] = [Badge::Streak, Badge::Tracked, Badge::NoSnooze];
+
+    /// Get the badge's name
+    pub fn name(self) -> &'static str {
+        match self {
+            Badge::Streak => "7-night streak",
+            Badge::Tracked => "30 nights tracked",
+            Badge::NoSnooze => "Never snoozed for a month",
+        }
+    }
+
+    /// Get a description of how to unlock the badge
+    pub fn description(self) -> &'static str {
+        match self {
+            Badge::Streak => "Go to bed on time 7 nights in a row",
+            Badge::Tracked => "Have 30 nights tracked",
+            Badge::NoSnooze => "Go 30 nights in a row without skipping reminders",
+        }
+    }
+
+    /// Whether a user with some sleep history has earned the badge
+    fn earned(self, history: &History) -> bool {
+        match self {
+            Badge::Streak => history.streak() >= STREAK_NIGHTS,
+            Badge::Tracked => history.all().len() >= TRACKED_NIGHTS,
+            Badge::NoSnooze => {
+                let nights = history.all();
+                nights.len() >= UNSKIPPED_NIGHTS
+                    && nights
+                        .iter()
+                        .rev()
+                        .take(UNSKIPPED_NIGHTS)
+                        .all(|night| !night.skipped)
+            }
+        }
+    }
+
+    /// Get the badges a user with some sleep history has earned, but not
+    /// unlocked yet
+    pub fn newly_earned(history: &History, unlocked: &[Badge]) -> Vec<Badge> {
+        Badge::ALL
+            .iter()
+            .copied()
+            .filter(|badge| !unlocked.contains(badge) && badge.earned(history))
+            .collect()
+    }
+}
+
+impl fmt::Display for Badge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "**{}** — {}", self.name(), self.description())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::history::History;
+
+use std::collections::HashMap;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Background job a user's actor runs for them
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Job {
+    /// Bedtime alert schedule and the nag loop it starts
+    Bedtime,
+
+    /// One-off reminder schedule
+    Reminders,
+
+    /// Wind-down check-ins
+    WindDown,
+}
+
+/// Starts a job, getting a handle to it
+pub type Start = Box<dyn FnOnce() -> JoinHandle<()> + Send>;
+
+/// Message to a user's actor
+pub enum Msg {
+    /// Replace one of the user's jobs with a new one, or stop it if `None`
+    SetJob(Job, Option<Start>),
+
+    /// The user went online or offline
+    PresenceChanged(bool),
+
+    /// The user acknowledged their sleep reminders, and may stay up
+    Ack,
+
+    /// Stop all of the user's jobs, and the actor itself
+    Shutdown,
+}
+
+/// Flags about a user that their actor is the only one to change, except the
+/// nag loop starting a night and ending it at the end of the sleep window
+#[derive(Clone)]
+pub struct Flags {
+    /// Whether the user is detected to be awake
+    pub awake: Arc<AtomicBool>,
+
+    /// Whether the user is allowed to be awake past their bedtime
+    pub allowed_awake: Arc<AtomicBool>,
+
+    /// The user's sleep history, for remembering when they fell asleep
+    pub history: History,
+}
+
+/// Handle to a task that owns a user's background jobs and flags. Changes go
+/// through it one message at a time, so they can't race each other. The task
+/// stops all the jobs and exits when the handle is dropped.
+pub struct UserActor {
+    tx: UnboundedSender<Msg>,
+}
+
+impl UserActor {
+    /// Spawn an actor for a user
+    pub fn spawn(flags: Flags) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run(flags, rx));
+        UserActor { tx }
+    }
+
+    /// Whether the actor is still handling messages
+    pub fn is_running(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    /// Send a message to the actor
+    pub fn send(&self, msg: Msg) {
+        // Sending only fails once the actor is gone, when its jobs are too
+        let _ = self.tx.send(msg);
+    }
+}
+
+/// Handle the messages sent to a user's actor until it shuts down
+async fn run(flags: Flags, mut rx: UnboundedReceiver<Msg>) {
+    let mut jobs: HashMap<Job, JoinHandle<()>> = HashMap::new();
+    while let Some(msg) = rx.recv().await {
+        match msg {
+            Msg::SetJob(job, start) => {
+                if let Some(old) = jobs.remove(&job) {
+                    old.abort();
+                }
+                if let Some(start) = start {
+                    jobs.insert(job, start());
+                }
+            }
+            Msg::PresenceChanged(true) => flags.awake.store(true, atomic::Ordering::Relaxed),
+            Msg::PresenceChanged(false) => {
+                flags.awake.store(false, atomic::Ordering::Relaxed);
+                // Remember when they fell asleep if it was past their bedtime
+                if !flags.allowed_awake.load(atomic::Ordering::Relaxed) {
+                    flags.history.fall_asleep();
+                }
+            }
+            Msg::Ack => flags.allowed_awake.store(true, atomic::Ordering::Relaxed),
+            Msg::Shutdown => break,
+        }
+    }
+    for job in jobs.values() {
+        job.abort();
+    }
+}
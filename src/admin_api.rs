@@ -0,0 +1,114 @@
+use crate::bot::Bot;
+
+use std::env;
+
+use chrono::Utc;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::json;
+
+/// Whether a request carries the operator's token, set with the `ADMIN_TOKEN`
+/// environment variable. Without the variable, nobody is let in.
+fn authorized(req: &Request<Body>) -> bool {
+    let token = match env::var("ADMIN_TOKEN") {
+        Ok(token) if !token.is_empty() => token,
+        _ => return false,
+    };
+    let given = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    given.is_some_and(|given| {
+        ring::constant_time::verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_ok()
+    })
+}
+
+/// Respond with JSON
+fn json_response(status: StatusCode, value: &serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .expect("Invalid response")
+}
+
+/// Summarize every user's schedule, without their history or notes
+async fn users(bot: &Bot) -> serde_json::Value {
+    let now = Utc::now();
+    let state = bot.read().await;
+    let users: Vec<_> = state
+        .users
+        .iter()
+        .map(|(id, user_info)| {
+            let schedule = user_info.active_schedule();
+            json!({
+                "id": id.to_string(),
+                "configured": user_info.is_configured(),
+                "time_zone": user_info.time_zone().map(|tz| tz.name()),
+                "bedtime": schedule.map(|(_, bedtime)| bedtime.to_string()),
+                "reminders_on": schedule.is_some(),
+                "paused": state.is_paused(*id),
+                "scheduled": user_info.is_scheduled(),
+                "nagging": user_info.is_nagging(),
+                "last_fired": user_info.last_fired(),
+                "next_fire": user_info.next_fire(now),
+            })
+        })
+        .collect();
+    json!(users)
+}
+
+/// List sleep reminders going out right now and coming up next
+async fn scheduler(bot: &Bot) -> serde_json::Value {
+    let now = Utc::now();
+    let state = bot.read().await;
+    let nagging: Vec<_> = state
+        .users
+        .iter()
+        .filter(|(_, user_info)| user_info.is_nagging())
+        .map(|(id, user_info)| json!({ "id": id.to_string(), "since": user_info.last_fired() }))
+        .collect();
+    let mut upcoming: Vec<_> = state
+        .users
+        .iter()
+        .filter_map(|(id, user_info)| Some((user_info.next_fire(now)?, id)))
+        .collect();
+    upcoming.sort();
+    let upcoming: Vec<_> = upcoming
+        .into_iter()
+        .map(|(at, id)| json!({ "id": id.to_string(), "at": at }))
+        .collect();
+    json!({
+        "now": now,
+        "paused": state.paused,
+        "nagging": nagging,
+        "upcoming": upcoming,
+    })
+}
+
+/// Queue the state to be written to disk right away
+async fn save(bot: &Bot) -> serde_json::Value {
+    bot.read().await.save();
+    println!("State saved from the admin API");
+    json!({ "saved": true })
+}
+
+/// Route a request under `/admin`, for operators' dashboards and debugging.
+/// Every request needs the operator's token.
+pub async fn handle(bot: &Bot, req: &Request<Body>) -> Response<Body> {
+    if !authorized(req) {
+        return json_response(
+            StatusCode::UNAUTHORIZED,
+            &json!({ "error": "Missing or wrong admin token" }),
+        );
+    }
+    let resp = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/admin/users") => users(bot).await,
+        (&Method::GET, "/admin/scheduler") => scheduler(bot).await,
+        (&Method::GET, "/admin/metrics-snapshot") => bot.app.metrics.snapshot(),
+        (&Method::POST, "/admin/save") => save(bot).await,
+        _ => return json_response(StatusCode::NOT_FOUND, &json!({ "error": "Not found" })),
+    };
+    json_response(StatusCode::OK, &resp)
+}
@@ -0,0 +1,77 @@
+use crate::bot::Bot;
+
+use std::env;
+use std::panic;
+use std::sync::Mutex;
+
+use serenity::model::id::{ChannelId, UserId};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Maximum length of an alert, to stay within Discord's message length limit
+const MAX_ALERT_LEN: usize = 1900;
+
+lazy_static! {
+    /// Queue of alerts waiting to be sent to the bot's operator
+    static ref QUEUE: (UnboundedSender<String>, Mutex<Option<UnboundedReceiver<String>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, Mutex::new(Some(rx)))
+    };
+}
+
+/// Tell the bot's operator that something went wrong. This is also printed,
+/// and can be called from anywhere, since sending happens in the background.
+pub fn report(message: String) {
+    println!("Alert: {}", message);
+    // Sending only fails if the alert task is gone, and it's printed anyway
+    let _ = QUEUE.0.send(message);
+}
+
+/// Find where to send alerts: the channel in the `OPS_CHANNEL` environment
+/// variable if it's set, or else a DM to the bot's owner
+async fn destination(bot: &Bot) -> serenity::Result<ChannelId> {
+    if let Some(channel) = env::var("OPS_CHANNEL")
+        .ok()
+        .and_then(|channel| channel.parse().ok())
+    {
+        return Ok(ChannelId(channel));
+    }
+    let owner: UserId = bot.http.get_current_application_info().await?.owner.id;
+    Ok(owner.create_dm_channel(&bot.http).await?.id)
+}
+
+/// Report panics as alerts, and send alerts to the bot's operator as they come
+/// in
+pub fn spawn(bot: Bot) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        report(format!("Panic: {}", info));
+        default_hook(info);
+    }));
+
+    let mut rx = QUEUE
+        .1
+        .lock()
+        .expect("Alert queue lock poisoned")
+        .take()
+        .expect("Alert task already spawned");
+
+    tokio::spawn(async move {
+        while let Some(mut message) = rx.recv().await {
+            if message.len() > MAX_ALERT_LEN {
+                let mut end = MAX_ALERT_LEN;
+                while !message.is_char_boundary(end) {
+                    end -= 1;
+                }
+                message.truncate(end);
+                message.push('…');
+            }
+            let res = match destination(&bot).await {
+                Ok(channel) => channel.say(&bot.http, format!("⚠️ {}", message)).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = res {
+                println!("Error sending alert: {}", err);
+            }
+        }
+    });
+}
@@ -0,0 +1,67 @@
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::scheduler::Scheduler;
+use crate::state::State;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use serenity::model::id::UserId;
+use serenity::prelude::*;
+
+/// Everything the bot shares between the gateway handler, commands and
+/// background tasks. It's built in `main` before the client, and commands and
+/// hooks find it in the client's data.
+pub struct AppState {
+    /// The bot's saved state
+    pub state: RwLock<State>,
+
+    /// User who owns the bot's application, and can use admin commands
+    pub owner: UserId,
+
+    /// Scheduler that users' jobs wait on
+    pub scheduler: Scheduler,
+
+    /// Settings from the environment
+    pub config: Config,
+
+    /// Metrics the bot keeps about itself
+    pub metrics: Metrics,
+}
+
+impl TypeMapKey for AppState {
+    type Value = Arc<AppState>;
+}
+
+impl AppState {
+    /// Wrap loaded state for sharing, starting the scheduler and reading the
+    /// configuration
+    pub fn new(state: State, owner: UserId) -> Arc<Self> {
+        Arc::new(AppState {
+            state: RwLock::new(state),
+            owner,
+            scheduler: Scheduler::spawn(),
+            config: Config::from_env(),
+            metrics: Metrics::default(),
+        })
+    }
+}
+
+/// Error from looking for the app state in a context before it was added
+#[derive(Debug)]
+pub struct MissingAppState;
+
+impl fmt::Display for MissingAppState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "I'm still starting up. Please try again in a bit.")
+    }
+}
+
+impl Error for MissingAppState {}
+
+impl From<MissingAppState> for serenity::Error {
+    fn from(_: MissingAppState) -> Self {
+        serenity::Error::Other("App state missing from the client's data")
+    }
+}
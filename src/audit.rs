@@ -0,0 +1,114 @@
+use std::env;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serenity::model::id::UserId;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Something a user did or the bot did to them, kept in the audit trail
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// A user changed their settings with a command or the dashboard
+    SettingsChanged { user: UserId, via: String },
+
+    /// A sleep reminder was delivered to a user
+    NagSent { user: UserId },
+
+    /// A user said they're up, stopping their sleep reminders
+    NagAcknowledged { user: UserId },
+
+    /// A user was detected going to sleep while being reminded
+    SleepDetected { user: UserId },
+}
+
+/// Audit event with when it happened, as written to the sinks
+#[derive(Serialize)]
+struct Record<'a> {
+    at: DateTime<Utc>,
+
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+lazy_static! {
+    /// Queue of audit events waiting to be written by the audit task
+    static ref QUEUE: (UnboundedSender<AuditEvent>, Mutex<Option<UnboundedReceiver<AuditEvent>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, Mutex::new(Some(rx)))
+    };
+}
+
+/// Add an event to the audit trail. This can be called from anywhere, since
+/// writing happens in the background.
+pub fn record(event: AuditEvent) {
+    // Sending only fails if the audit task is gone, when the bot is shutting
+    // down anyway
+    let _ = QUEUE.0.send(event);
+}
+
+/// Append a line to the audit log file
+async fn append(path: &str, line: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(format!("{}\n", line).as_bytes()).await
+}
+
+/// Write audit events as JSON lines as they come in: appended to the file in
+/// the `AUDIT_LOG` environment variable, and sent to the sink in `AUDIT_SINK`,
+/// which is either `stdout` or a webhook URL to post each event to. Events are
+/// dropped if neither is set.
+pub fn spawn() {
+    let mut rx = QUEUE
+        .1
+        .lock()
+        .expect("Audit queue lock poisoned")
+        .take()
+        .expect("Audit task already spawned");
+    let log = env::var("AUDIT_LOG").ok();
+    let sink = env::var("AUDIT_SINK").ok();
+    let client = reqwest::Client::new();
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let record = Record {
+                at: Utc::now(),
+                event: &event,
+            };
+            let line = match serde_json::to_string(&record) {
+                Ok(line) => line,
+                Err(err) => {
+                    println!("Error serializing audit event {:?}: {}", event, err);
+                    continue;
+                }
+            };
+            if let Some(path) = &log {
+                if let Err(err) = append(path, &line).await {
+                    println!("Error writing audit log: {}", err);
+                }
+            }
+            match sink.as_deref() {
+                Some("stdout") => println!("Audit: {}", line),
+                Some(url) => {
+                    let res = client
+                        .post(url)
+                        .header(reqwest::header::CONTENT_TYPE, "application/json")
+                        .body(line)
+                        .send()
+                        .await
+                        .and_then(reqwest::Response::error_for_status);
+                    if let Err(err) = res {
+                        println!("Error sending audit event to webhook: {}", err);
+                    }
+                }
+                None => {}
+            }
+        }
+    });
+}
@@ -0,0 +1,49 @@
+use crate::app::{AppState, MissingAppState};
+use crate::state::State;
+
+use std::sync::Arc;
+
+use serenity::{cache::Cache, http::Http, prelude::*};
+use tokio::sync::{RwLockReadGuard, RwLockWriteGuard};
+
+/// Handles to the bot's shared resources, for commands and for tasks that run
+/// outside of event handlers, like scheduled sleep reminders
+#[derive(Clone)]
+pub struct Bot {
+    /// HTTP client for talking to Discord
+    pub http: Arc<Http>,
+
+    /// Cache of what the bot knows about guilds and users
+    pub cache: Arc<Cache>,
+
+    /// Shared app state, which holds the bot's state
+    pub app: Arc<AppState>,
+}
+
+impl Bot {
+    /// Get the bot's handles from an event context and the app state
+    pub fn new(ctx: &Context, app: Arc<AppState>) -> Self {
+        Bot {
+            http: Arc::clone(&ctx.http),
+            cache: Arc::clone(&ctx.cache),
+            app,
+        }
+    }
+
+    /// Get the bot's handles from a command or hook context, whose data the
+    /// app state is added to when the client is built
+    pub async fn of(ctx: &Context) -> Result<Self, MissingAppState> {
+        let app = ctx.data.read().await.get::<AppState>().cloned();
+        Ok(Bot::new(ctx, app.ok_or(MissingAppState)?))
+    }
+
+    /// Lock the bot's state for reading
+    pub async fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.app.state.read().await
+    }
+
+    /// Lock the bot's state for writing
+    pub async fn write(&self) -> RwLockWriteGuard<'_, State> {
+        self.app.state.write().await
+    }
+}
@@ -0,0 +1,142 @@
+use crate::bot::Bot;
+use crate::time::HumanDuration;
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::interactions::message_component::ButtonStyle, model::prelude::*, prelude::*, Result,
+};
+
+/// How long a buddy has to respond to a request
+const TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Custom ID of the button accepting a buddy request
+const ACCEPT_ID: &str = "buddy_accept";
+
+/// Custom ID of the button declining a buddy request
+const DECLINE_ID: &str = "buddy_decline";
+
+/// Friend who gets told when a user stays up too late
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Buddy {
+    /// The friend's user ID
+    pub id: UserId,
+
+    /// How many minutes past bedtime the user has to still be awake for the
+    /// friend to be told
+    pub after_minutes: i64,
+
+    /// Whether the friend agreed to be told
+    pub accepted: bool,
+}
+
+/// Ask a buddy in a DM whether they agree to be told when a user stays up
+/// late, and record their answer
+async fn request_consent(ctx: &Context, user: UserId, buddy: Buddy) -> Result<()> {
+    let bot = Bot::of(ctx).await?;
+    let dm = buddy.id.create_dm_channel(ctx).await?;
+
+    let after = HumanDuration(chrono::Duration::minutes(buddy.after_minutes));
+    let mut prompt = dm
+        .send_message(&ctx.http, |m| {
+            m.content(format!(
+                "{} wants me to tell you if they're still up {} past their \
+                 bedtime. Is that okay?",
+                user.mention(),
+                after
+            ))
+            .components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id(ACCEPT_ID)
+                            .label("Sure")
+                            .style(ButtonStyle::Success)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(DECLINE_ID)
+                            .label("No thanks")
+                            .style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = prompt
+        .await_component_interaction(ctx)
+        .author_id(buddy.id)
+        .timeout(TIMEOUT)
+        .await;
+
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => return prompt.edit(ctx, |m| m.components(|c| c)).await,
+    };
+
+    let accepted = interaction.data.custom_id == ACCEPT_ID;
+
+    {
+        let mut state = bot.write().await;
+        if let Some(user_info) = state.users.get_mut(&user) {
+            user_info
+                .answer_buddy(bot.clone(), user, buddy.id, accepted)
+                .await;
+            state.save();
+        }
+    }
+
+    let resp = if accepted {
+        "Got it, I'll let you know if they stay up late"
+    } else {
+        "Okay, I won't tell you about them"
+    };
+
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(resp).components(|c| c))
+        })
+        .await?;
+
+    let resp = if accepted {
+        format!("{} agreed to be your buddy", buddy.id.mention())
+    } else {
+        format!("{} declined to be your buddy", buddy.id.mention())
+    };
+    user.create_dm_channel(ctx)
+        .await?
+        .say(&ctx.http, resp)
+        .await?;
+
+    Ok(())
+}
+
+/// Ask a buddy for consent in the background, so the caller doesn't wait for
+/// them to respond
+pub fn spawn_request_consent(ctx: &Context, user: UserId, buddy: Buddy) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Err(err) = request_consent(&ctx, user, buddy).await {
+            println!("Error requesting buddy consent: {}", err);
+        }
+    });
+}
+
+/// Tell a buddy that a user is still up late
+pub async fn notify(bot: &Bot, user: UserId, buddy: Buddy) {
+    println!("Telling buddy '{}' about user '{}'", buddy.id, user);
+    let resp = format!(
+        "{} is still up {} past their bedtime. Maybe tell them to get some \
+         sleep? 😴",
+        user.mention(),
+        HumanDuration(chrono::Duration::minutes(buddy.after_minutes))
+    );
+    let res = match buddy.id.create_dm_channel(&bot.http).await {
+        Ok(dm) => dm.say(&bot.http, resp).await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = res {
+        println!("Error notifying buddy: {}", err);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::bot::Bot;
+use crate::state::State;
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::model::{
+    id::{ChannelId, UserId},
+    mention::Mentionable,
+};
+
+/// Hour of the day, in UTC, when challenge standings are posted
+const STANDINGS_HOUR: u32 = 12;
+
+/// Competition between guild members for who goes to bed on time most often
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Challenge {
+    /// Channel to post standings in
+    pub channel: ChannelId,
+
+    /// When the challenge started
+    pub start: DateTime<Utc>,
+
+    /// When the challenge ends
+    pub end: DateTime<Utc>,
+
+    /// Members taking part in the challenge
+    pub participants: BTreeSet<UserId>,
+}
+
+/// How a participant is doing in a challenge
+pub struct Standing {
+    pub id: UserId,
+
+    /// Number of nights the participant went to bed on time
+    pub on_time: usize,
+
+    /// Number of nights counted for the participant, excluding skipped ones
+    pub nights: usize,
+}
+
+impl Standing {
+    /// Fraction of nights the participant went to bed on time
+    fn adherence(&self) -> f64 {
+        if self.nights == 0 {
+            0.0
+        } else {
+            self.on_time as f64 / self.nights as f64
+        }
+    }
+}
+
+impl Challenge {
+    /// Get the standings of all participants, best first
+    pub fn standings(&self, state: &State) -> Vec<Standing> {
+        let start = self.start.naive_utc().date();
+        let mut standings: Vec<Standing> = self
+            .participants
+            .iter()
+            .map(|&id| {
+                let nights: Vec<_> = match state.users.get(&id) {
+                    Some(user_info) => user_info
+                        .nights_since(start)
+                        .into_iter()
+                        .filter(|night| !night.skipped)
+                        .collect(),
+                    None => Vec::new(),
+                };
+                Standing {
+                    id,
+                    on_time: nights.iter().filter(|night| night.on_time()).count(),
+                    nights: nights.len(),
+                }
+            })
+            .collect();
+
+        standings.sort_by(|a, b| {
+            b.adherence()
+                .partial_cmp(&a.adherence())
+                .expect("Adherence is NaN")
+                .then(b.on_time.cmp(&a.on_time))
+        });
+        standings
+    }
+
+    /// Format the standings as a message
+    pub fn standings_message(&self, state: &State) -> String {
+        let standings = self.standings(state);
+        if standings.is_empty() {
+            return "Nobody has joined the challenge yet".to_string();
+        }
+        standings
+            .iter()
+            .enumerate()
+            .map(|(i, standing)| {
+                format!(
+                    "{}. {} — {}/{} nights on time",
+                    i + 1,
+                    standing.id.mention(),
+                    standing.on_time,
+                    standing.nights
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Get the next time standings should be posted after `now`
+fn next_standings_time(now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.date().and_hms(STANDINGS_HOUR, 0, 0);
+    if today > now {
+        today
+    } else {
+        today + Duration::days(1)
+    }
+}
+
+/// Post standings for all running challenges, and announce the winners of
+/// challenges that ended
+async fn post_standings(bot: &Bot) {
+    let mut posts = Vec::new();
+
+    {
+        let mut state = bot.write().await;
+        let now = Utc::now();
+
+        let mut ended = Vec::new();
+        for (&guild, config) in &state.guilds {
+            let challenge = match &config.challenge {
+                Some(challenge) => challenge,
+                None => continue,
+            };
+
+            let standings = challenge.standings_message(&state);
+            let post = if now >= challenge.end {
+                ended.push(guild);
+                let winner = match challenge.standings(&state).first() {
+                    Some(winner) if winner.nights > 0 => {
+                        format!("🏆 {} wins the bedtime challenge!", winner.id.mention())
+                    }
+                    _ => "The bedtime challenge is over, but nobody wins".to_string(),
+                };
+                format!("{}\n\n**Final standings**\n{}", winner, standings)
+            } else {
+                let day = (now - challenge.start).num_days() + 1;
+                let days = (challenge.end - challenge.start).num_days();
+                format!(
+                    "**Bedtime challenge standings, day {} of {}**\n{}",
+                    day, days, standings
+                )
+            };
+            posts.push((challenge.channel, post));
+        }
+
+        for guild in &ended {
+            if let Some(config) = state.guilds.get_mut(guild) {
+                config.challenge = None;
+            }
+        }
+        if !ended.is_empty() {
+            state.save();
+        }
+    }
+
+    for (channel, post) in posts {
+        let res = channel
+            .send_message(&bot.http, |m| {
+                m.content(post).allowed_mentions(|am| am.empty_parse())
+            })
+            .await;
+        if let Err(err) = res {
+            println!("Error posting challenge standings: {}", err);
+        }
+    }
+}
+
+/// Post challenge standings once a day, forever
+pub fn spawn_daily(bot: Bot) {
+    tokio::spawn(async move {
+        loop {
+            let now = Utc::now();
+            let wait = (next_standings_time(now) - now)
+                .to_std()
+                .expect("Standings time is in the past");
+            tokio::time::sleep(wait).await;
+            post_standings(&bot).await;
+        }
+    });
+}
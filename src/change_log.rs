@@ -0,0 +1,62 @@
+use crate::time::Time;
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of settings changes remembered per user
+const MAX_CHANGES: usize = 10;
+
+/// Value of a user setting
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Setting {
+    TimeZone(Option<Tz>),
+    Bedtime(Option<Time>),
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Setting::TimeZone(Some(tz)) => write!(f, "time zone {}", tz.name()),
+            Setting::TimeZone(None) => write!(f, "no time zone"),
+            Setting::Bedtime(Some(bedtime)) => write!(f, "bedtime {}", bedtime),
+            Setting::Bedtime(None) => write!(f, "no bedtime"),
+        }
+    }
+}
+
+/// A change to some of a user's settings, remembering what they were before
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// When the change was made
+    pub at: DateTime<Utc>,
+
+    /// Setting values from before the change
+    pub previous: Vec<Setting>,
+}
+
+/// Log of a user's most recent settings changes
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ChangeLog(VecDeque<Change>);
+
+impl ChangeLog {
+    /// Record a change, given the setting values from before it, forgetting
+    /// the oldest change if the log is full
+    pub fn record(&mut self, previous: Vec<Setting>) {
+        if self.0.len() == MAX_CHANGES {
+            self.0.pop_front();
+        }
+        self.0.push_back(Change {
+            at: Utc::now(),
+            previous,
+        });
+    }
+
+    /// Remove and return the most recent change
+    pub fn pop(&mut self) -> Option<Change> {
+        self.0.pop_back()
+    }
+}
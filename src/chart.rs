@@ -0,0 +1,84 @@
+use crate::history::Night;
+
+use std::error::Error;
+use std::io::Cursor;
+
+use chrono::NaiveDate;
+use image::{ImageOutputFormat, RgbImage};
+use plotters::prelude::*;
+
+/// Width of rendered charts, in pixels
+const WIDTH: u32 = 800;
+
+/// Height of rendered charts, in pixels
+const HEIGHT: u32 = 300;
+
+/// Number of nights the trend line averages over
+const TREND_NIGHTS: usize = 7;
+
+/// Color of bars for nights the user went to bed on time
+const ON_TIME: RGBColor = RGBColor(76, 175, 80);
+
+/// Color of bars for nights the user needed reminders
+const LATE: RGBColor = RGBColor(244, 67, 54);
+
+/// Color of bars for nights the user skipped
+const SKIPPED: RGBColor = RGBColor(189, 189, 189);
+
+/// Color of the trend line
+const TREND: RGBColor = RGBColor(33, 150, 243);
+
+/// Background color of charts
+const BACKGROUND: RGBColor = RGBColor(54, 57, 63);
+
+/// Render a PNG chart of bedtime adherence for the `days` days up to and
+/// including `today`. Each night is a bar colored by how it went, and a line
+/// shows the fraction of recent nights that were on time.
+pub fn render(
+    nights: &[Night],
+    days: i64,
+    today: NaiveDate,
+) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let start = today - chrono::Duration::days(days - 1);
+    let nights: Vec<&Night> = nights.iter().filter(|night| night.date >= start).collect();
+
+    let mut pixels = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&BACKGROUND)?;
+        let mut chart = ChartBuilder::on(&root)
+            .margin(10)
+            .build_cartesian_2d(0.0..days as f64, 0.0..1.0)?;
+
+        chart.draw_series(nights.iter().map(|night| {
+            let x = (night.date - start).num_days() as f64;
+            let (color, height) = if night.skipped {
+                (SKIPPED, 0.1)
+            } else if night.on_time() {
+                (ON_TIME, 1.0)
+            } else {
+                (LATE, 0.5)
+            };
+            Rectangle::new([(x + 0.1, 0.0), (x + 0.9, height)], color.filled())
+        }))?;
+
+        let counted: Vec<&&Night> = nights.iter().filter(|night| !night.skipped).collect();
+        let trend = counted.iter().enumerate().map(|(i, night)| {
+            let window = &counted[i.saturating_sub(TREND_NIGHTS - 1)..=i];
+            let on_time = window.iter().filter(|night| night.on_time()).count();
+            let x = (night.date - start).num_days() as f64 + 0.5;
+            (x, on_time as f64 / window.len() as f64)
+        });
+        chart.draw_series(std::iter::once(PathElement::new(
+            trend.collect::<Vec<_>>(),
+            TREND.stroke_width(3),
+        )))?;
+
+        root.present()?;
+    }
+
+    let image = RgbImage::from_raw(WIDTH, HEIGHT, pixels).ok_or("Chart buffer is too small")?;
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageOutputFormat::Png)?;
+    Ok(png.into_inner())
+}
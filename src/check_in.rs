@@ -0,0 +1,89 @@
+use crate::bot::Bot;
+
+use chrono::NaiveDate;
+use serenity::{
+    model::interactions::message_component::MessageComponentInteraction, model::prelude::*,
+    prelude::*, Result,
+};
+
+/// Prefix of the custom IDs of the check-in rating buttons. The full ID is
+/// `<prefix>:<night date>:<rating>`.
+const RATING_ID_PREFIX: &str = "check_in";
+
+/// Highest sleep quality rating
+pub const MAX_RATING: u8 = 5;
+
+/// DM a user a morning check-in asking them to rate how they slept the night
+/// of `date`
+pub async fn send(bot: &Bot, user: UserId, date: NaiveDate) -> Result<()> {
+    println!("Sending user '{}' a morning check-in", user);
+
+    let dm = user.create_dm_channel(&bot.http).await?;
+
+    dm.send_message(&bot.http, |m| {
+        m.content("Good morning! 🌅 How well did you sleep last night?")
+            .components(|c| {
+                c.create_action_row(|r| {
+                    for rating in 1..=MAX_RATING {
+                        r.create_button(|b| {
+                            b.custom_id(format!("{}:{}:{}", RATING_ID_PREFIX, date, rating))
+                                .label(rating)
+                        });
+                    }
+                    r
+                })
+            })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Parse the night date and rating out of a check-in button's custom ID
+fn parse_rating_id(id: &str) -> Option<(NaiveDate, u8)> {
+    let mut parts = id.split(':');
+    if parts.next()? != RATING_ID_PREFIX {
+        return None;
+    }
+    let date = parts.next()?.parse().ok()?;
+    let rating = parts.next()?.parse().ok()?;
+    Some((date, rating)).filter(|&(_, rating)| (1..=MAX_RATING).contains(&rating))
+}
+
+/// Record the rating chosen with a check-in button, ignoring interactions
+/// with other buttons
+pub async fn handle(ctx: &Context, interaction: &MessageComponentInteraction) -> Result<()> {
+    let bot = Bot::of(ctx).await?;
+    let (date, rating) = match parse_rating_id(&interaction.data.custom_id) {
+        Some(parsed) => parsed,
+        None => return Ok(()),
+    };
+
+    let rated = {
+        let mut state = bot.write().await;
+        let rated = state
+            .users
+            .get_mut(&interaction.user.id)
+            .is_some_and(|user_info| user_info.rate_night(date, rating));
+        state.save();
+        rated
+    };
+
+    let resp = if rated {
+        format!(
+            "Thanks! You rated last night's sleep **{}/{}**",
+            rating, MAX_RATING
+        )
+    } else {
+        "I don't remember that night anymore".to_string()
+    };
+
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(resp).components(|c| c))
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use crate::alert;
+
+use std::env;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::DATE;
+
+/// How often to check the clock
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Number of seconds the host clock can be off from Discord's before the
+/// operator is warned, if not set with the `CLOCK_SKEW_SECONDS` environment
+/// variable. Discord's clock is only given to the second, so this can't be
+/// too small.
+const DEFAULT_MAX_SKEW_SECONDS: i64 = 30;
+
+/// Discord endpoint that needs no authentication, for reading its clock
+const CLOCK_URL: &str = "https://discord.com/api/v10/gateway";
+
+/// Get how far the host clock can be off before the operator is warned
+fn max_skew() -> chrono::Duration {
+    let seconds = env::var("CLOCK_SKEW_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SKEW_SECONDS);
+    chrono::Duration::seconds(seconds)
+}
+
+/// Get how far ahead the host clock is of Discord's, from the `Date` header of
+/// a response, taking the middle of the request as when it was sent
+async fn skew(client: &reqwest::Client) -> Result<chrono::Duration, String> {
+    let sent = Utc::now();
+    let resp = client
+        .head(CLOCK_URL)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    let received = Utc::now();
+    let date = resp
+        .headers()
+        .get(DATE)
+        .and_then(|date| date.to_str().ok())
+        .ok_or("Discord sent no Date header")?;
+    let remote = DateTime::parse_from_rfc2822(date).map_err(|err| err.to_string())?;
+    let local = sent + (received - sent) / 2;
+    Ok(local - remote.with_timezone(&Utc))
+}
+
+/// Check the host clock against Discord's at startup and then every hour,
+/// warning the operator when it drifts too far, since every sleep reminder
+/// is scheduled by it
+pub fn spawn() {
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut interval = tokio::time::interval(INTERVAL);
+        // Only warn when the clock first drifts, not every hour it stays off
+        let mut skewed = false;
+        loop {
+            interval.tick().await;
+            let skew = match skew(&client).await {
+                Ok(skew) => skew,
+                Err(err) => {
+                    println!("Error checking the clock: {}", err);
+                    continue;
+                }
+            };
+            let was_skewed = skewed;
+            skewed = skew.num_seconds().abs() > max_skew().num_seconds();
+            if skewed && !was_skewed {
+                let direction = if skew > chrono::Duration::zero() {
+                    "ahead of"
+                } else {
+                    "behind"
+                };
+                alert::report(format!(
+                    "The host clock is {}s {} Discord's, so sleep reminders go out \
+                     at the wrong time. Check that the host syncs its clock.",
+                    skew.num_seconds().abs(),
+                    direction
+                ));
+            } else if !skewed && was_skewed {
+                alert::report("The host clock is back in sync with Discord's".to_string());
+            }
+        }
+    });
+}
@@ -1,8 +1,41 @@
-use crate::state::State;
+pub mod admin;
+pub mod challenge;
+pub mod group;
+pub mod guild;
+pub mod manage;
+pub mod profile;
+pub mod routine;
 
-use std::collections::HashSet;
-use std::sync::Arc;
+use crate::achievements::Badge;
+use crate::audit::{self, AuditEvent};
+use crate::bot::Bot;
+use crate::buddy::{self, Buddy};
+use crate::chart;
+use crate::confirm;
+use crate::export;
+use crate::holidays::Country;
+use crate::intensity::{self, Intensity};
+use crate::link;
+use crate::locale;
+use crate::location;
+use crate::manager;
+use crate::onboarding;
+use crate::presence::{self, AwakeThreshold, Client, IdlePolicy};
+use crate::profile::Profile;
+use crate::reminder;
+use crate::suggest::{self, Suggestion};
+use crate::target;
+use crate::templates;
+use crate::time::{self, BedtimeArg, HumanDuration, Time, UtcOffset};
+use crate::transfer;
+use crate::tz_picker;
+use crate::user_info::UserInfo;
+use crate::xp;
 
+use std::collections::{BTreeSet, HashSet};
+
+use chrono::{NaiveDate, Offset, Utc};
+use chrono_tz::Tz;
 use serenity::{
     framework::standard::{
         help_commands,
@@ -13,11 +46,121 @@ use serenity::{
     prelude::*,
 };
 
+/// Number of sleep reminders shown by the nag log command by default
+const NAG_LOG_DEFAULT_COUNT: usize = 10;
+
+/// Maximum number of sleep reminders shown by the nag log command
+const NAG_LOG_MAX_COUNT: usize = 25;
+
+/// Number of days shown by the chart command by default
+const CHART_DEFAULT_DAYS: i64 = 30;
+
+/// Maximum number of days shown by the chart command
+const CHART_MAX_DAYS: i64 = 366;
+
+/// Maximum length of a sleep diary note
+const NOTE_MAX_LEN: usize = 200;
+
+/// Maximum length of a wake phrase
+const WAKE_PHRASE_MAX_LEN: usize = 50;
+
+/// Maximum number of skipped nights imported settings can have
+const IMPORT_MAX_SKIPPED: usize = 366;
+
+/// Number of members shown on the leaderboard
+const LEADERBOARD_COUNT: usize = 10;
+
 #[group]
-#[commands(time_zone, bedtime, wake, info, on, off)]
+#[commands(
+    setup,
+    set,
+    time_zone,
+    location,
+    bedtime,
+    target,
+    travel,
+    tonight,
+    winddown,
+    skip_tonight,
+    country,
+    holiday_skip,
+    next,
+    intensity,
+    max_tone,
+    theme,
+    fuzz,
+    undo,
+    wake,
+    wakeword,
+    info,
+    now,
+    nag_log,
+    note,
+    export,
+    import,
+    chart,
+    suggest,
+    sleep_goal,
+    remind,
+    on,
+    off,
+    forget_me,
+    transfer,
+    link,
+    unlink,
+    presence,
+    idle,
+    awake_clients,
+    awake_threshold,
+    single_nag,
+    clean_up,
+    morning_summary,
+    away_reply,
+    lockout,
+    announce,
+    notify,
+    manager,
+    leaderboard,
+    badges,
+    opt_in_message
+)]
 pub struct General;
 
+/// Commands that change sleep reminder settings, after which the user is
+/// warned if their setup is still incomplete
+pub const SETTINGS_COMMANDS: &[&str] = &[
+    "set",
+    "time_zone",
+    "location",
+    "bedtime",
+    "target",
+    "travel",
+    "tonight",
+    "winddown",
+    "skip-tonight",
+    "country",
+    "holiday-skip",
+    "intensity",
+    "max-tone",
+    "theme",
+    "fuzz",
+    "undo",
+    "sleep-goal",
+    "on",
+    "presence",
+    "idle",
+    "awake-clients",
+    "awake-threshold",
+    "single-nag",
+    "clean-up",
+    "morning-summary",
+    "import",
+    "use",
+];
+
 #[help]
+#[individual_command_tip = "To see how to use a command and its shorter aliases, \
+                            pass its name to this command, like `help bedtime`."]
 async fn help(
     ctx: &Context,
     msg: &Message,
@@ -30,27 +173,101 @@ async fn help(
     Ok(())
 }
 
+/// Confirm a time zone change, in the user's message pack if they picked one
+fn time_zone_set(user_info: &UserInfo, id: UserId, tz: Tz) -> String {
+    templates::render(
+        user_info.theme(),
+        "time_zone_set",
+        "Your time zone has been set to {time_zone}",
+        &[("user", &id.mention()), ("time_zone", &tz.name())],
+    )
+}
+
+/// Greet a user who woke up, in their message pack if they picked one
+pub fn good_morning(user_info: &UserInfo, id: UserId) -> String {
+    templates::render(
+        user_info.theme(),
+        "good_morning",
+        "Good morning 🌅",
+        &[("user", &id.mention())],
+    )
+}
+
 #[command]
-#[description = "Set your time zone. List of options here: http://ix.io/1Rbm"]
-async fn time_zone(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let tz = args.parse()?;
+#[description = "Walk through setting up sleep reminders"]
+async fn setup(ctx: &Context, msg: &Message) -> CommandResult {
+    let locale = locale::guild_time_zone(ctx, msg.guild_id);
+    onboarding::setup(ctx, msg.channel_id, msg.author.id, msg.guild_id, locale).await?;
+    Ok(())
+}
 
-    let mut data = ctx.data.write().await;
+#[command]
+#[description = "Set your bedtime and time zone at once, in either order, and \
+                 turn on sleep reminders"]
+#[usage = "10:30 PM America/Chicago"]
+async fn set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
 
-    let state = data.get_mut::<State>().expect("No state in context");
+    // The time zone has no spaces, so it's either the first or last word
+    let (tz, tm) = match text.split_once(' ').zip(text.rsplit_once(' ')) {
+        Some(((first, rest), (init, last))) => match first.parse::<Tz>() {
+            Ok(tz) => (tz, rest.trim()),
+            Err(_) => (time::parse_time_zone(last)?, init.trim()),
+        },
+        None => return Err("Use something like `10:30 PM America/Chicago`".into()),
+    };
+    let tm: Time = tm.parse()?;
 
-    let http = &ctx.http;
+    let mut state = bot.write().await;
 
     state
         .users
         .entry(msg.author.id)
         .or_default()
-        .set_time_zone(Arc::clone(http), msg.author.id, tz)
+        .set_time_zone_and_bedtime(bot.clone(), msg.author.id, tz, tm)
         .await;
 
     state.save();
 
-    let resp = format!("Your time zone has been set to {}", tz.name());
+    let resp = format!(
+        "Your bedtime has been set to {} in {}. Sleep reminders are on.",
+        tm,
+        tz.name()
+    );
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases("tz", "timezone")]
+#[description = "Set your time zone. Run without arguments to pick from a menu, \
+                 or give one of the options here: http://ix.io/1Rbm"]
+async fn time_zone(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let tz = if args.is_empty() {
+        let suggested = locale::guild_time_zone(ctx, msg.guild_id);
+        match tz_picker::pick_time_zone(ctx, msg.channel_id, msg.author.id, suggested).await? {
+            Some(tz) => tz,
+            None => return Ok(()),
+        }
+    } else {
+        time::parse_time_zone(args.message())?
+    };
+
+    let mut state = bot.write().await;
+
+    let http = &ctx.http;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    user_info
+        .set_time_zone(bot.clone(), msg.author.id, tz)
+        .await;
+    let resp = time_zone_set(user_info, msg.author.id, tz);
+
+    state.save();
 
     msg.channel_id.say(http, resp).await?;
 
@@ -58,63 +275,390 @@ async fn time_zone(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 }
 
 #[command]
-#[description = "Set your bedtime"]
+#[description = "Set your time zone from the city you live in"]
+#[usage = "Berlin"]
+async fn location(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let tz = match location::lookup(args.message()) {
+        Some(tz) => tz,
+        None => {
+            return Err("I don't know that city. Try a bigger city nearby, or \
+                        set your time zone with `tz`."
+                .into())
+        }
+    };
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    user_info
+        .set_time_zone(bot.clone(), msg.author.id, tz)
+        .await;
+    let resp = time_zone_set(user_info, msg.author.id, tz);
+
+    state.save();
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases("bt")]
+#[description = "Set your bedtime, like `10:30 PM`. Use `in 2h` to go to bed \
+                 some time from now tonight only, or `+30m`/`-15m` to move your \
+                 bedtime later or earlier. Add a time zone, like \
+                 `10:30 PM Europe/Paris`, to set it too."]
 async fn bedtime(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let tm = args.parse()?;
+    let bot = Bot::of(ctx).await?;
+    let (tz, arg) = time::split_time_zone(args.message());
+    let arg = arg.parse()?;
+
+    let mut state = bot.write().await;
+
+    let http = &ctx.http;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+
+    if let Some(tz) = tz {
+        user_info
+            .set_time_zone(bot.clone(), msg.author.id, tz)
+            .await;
+    }
+
+    let mut resp = match arg {
+        BedtimeArg::At(tm) => {
+            user_info.set_bedtime(bot.clone(), msg.author.id, tm).await;
+            templates::render(
+                user_info.theme(),
+                "bedtime_set",
+                "Your bedtime has been set to {bedtime}",
+                &[("user", &msg.author.mention()), ("bedtime", &tm)],
+            )
+        }
+        BedtimeArg::In(d) => {
+            let tonight = Utc::now()
+                .checked_add_signed(d)
+                .ok_or("That bedtime is too far from now")?;
+            user_info
+                .set_tonight(bot.clone(), msg.author.id, tonight)
+                .await;
+            templates::render(
+                user_info.theme(),
+                "bedtime_tonight",
+                "Your bedtime tonight only has been set to {in} from now",
+                &[("user", &msg.author.mention()), ("in", &HumanDuration(d))],
+            )
+        }
+        BedtimeArg::Shift(d) => {
+            let shifted = user_info.shift_bedtime(bot.clone(), msg.author.id, d).await;
+            match shifted {
+                Some(tm) => templates::render(
+                    user_info.theme(),
+                    "bedtime_moved",
+                    "Your bedtime has been moved to {bedtime}",
+                    &[("user", &msg.author.mention()), ("bedtime", &tm)],
+                ),
+                None => "You don't have a bedtime to move yet".to_string(),
+            }
+        }
+    };
+
+    if let Some(tz) = tz {
+        resp = format!("{}\n{}", resp, time_zone_set(user_info, msg.author.id, tz));
+    }
+
+    state.save();
+
+    msg.channel_id.say(http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Move your bedtime toward a goal a little each night, so the \
+                 change sticks. Use `off` to stop."]
+#[usage = "10:30 PM over 3w"]
+async fn target(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
 
-    let mut data = ctx.data.write().await;
+    let resp = if text == "off" {
+        if user_info.clear_target() {
+            "I'll stop moving your bedtime".to_string()
+        } else {
+            "You don't have a bedtime target".to_string()
+        }
+    } else {
+        let (goal, over) = text
+            .split_once(" over ")
+            .ok_or("Say when to reach the goal, like `10:30 PM over 3w`")?;
+        let goal: Time = goal.trim().parse()?;
+        let over = time::parse_duration(over)?;
+        if over.num_days() > target::MAX_NIGHTS {
+            return Err(format!(
+                "Pick a goal you can reach within {} days",
+                target::MAX_NIGHTS
+            )
+            .into());
+        }
 
-    let state = data.get_mut::<State>().expect("No state in context");
+        match user_info
+            .set_target(bot.clone(), msg.author.id, goal, over)
+            .await
+        {
+            Some(target) => format!(
+                "I'll move your bedtime to {} a little each night, reaching it on {}",
+                target.goal,
+                target.end().format("%B %-d")
+            ),
+            None => "Set your time zone and bedtime first".to_string(),
+        }
+    };
+
+    state.save();
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Switch to another time zone on a date, keeping your bedtime. \
+                 With `gradual`, your bedtime moves toward the new time zone's \
+                 over the nights before. Use `off` to cancel."]
+#[usage = "to Asia/Tokyo on 2024-07-01 [gradual]"]
+async fn travel(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+
+    let resp = if text == "off" {
+        if user_info.clear_trip(bot.clone(), msg.author.id).await {
+            "Canceled your trip".to_string()
+        } else {
+            "You don't have a trip planned".to_string()
+        }
+    } else {
+        let (time_zone, rest) = text
+            .strip_prefix("to ")
+            .and_then(|rest| rest.split_once(" on "))
+            .ok_or("Say where and when you're going, like `to Asia/Tokyo on 2024-07-01`")?;
+        let time_zone = time::parse_time_zone(time_zone)?;
+        let (date, gradual) = match rest.trim().strip_suffix("gradual") {
+            Some(date) => (date.trim(), true),
+            None => (rest.trim(), false),
+        };
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")?;
+
+        match user_info
+            .set_trip(bot.clone(), msg.author.id, time_zone, date, gradual)
+            .await
+        {
+            Some(trip) => format!(
+                "I'll switch you to {} on {}, with bedtime {}",
+                trip.time_zone.name(),
+                trip.date.format("%B %-d"),
+                trip.bedtime
+            ),
+            None => "Set your time zone and bedtime first".to_string(),
+        }
+    };
+
+    state.save();
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Set your bedtime for tonight only, like `1:00 AM`"]
+async fn tonight(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let tm: Time = args.parse()?;
+
+    let mut state = bot.write().await;
 
     let http = &ctx.http;
 
-    state
+    let user_info = state.users.entry(msg.author.id).or_default();
+
+    let resp = match user_info.time_zone() {
+        Some(tz) => {
+            let tonight = tm.next_after(tz, Utc::now());
+            user_info
+                .set_tonight(bot.clone(), msg.author.id, tonight)
+                .await;
+            format!("Your bedtime tonight only has been set to {}", tm)
+        }
+        None => "Set your time zone first, so I know when tonight is".to_string(),
+    };
+
+    state.save();
+
+    msg.channel_id.say(http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Start winding down, going to bed after some time tonight only. \
+                 I'll check in along the way."]
+#[usage = "45m"]
+async fn winddown(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let length = time::parse_duration(args.message())?;
+    if length > chrono::Duration::hours(crate::winddown::MAX_HOURS) {
+        return Err(format!(
+            "A wind-down can be at most {} hours long",
+            crate::winddown::MAX_HOURS
+        )
+        .into());
+    }
+
+    let mut state = bot.write().await;
+
+    let started = state
         .users
         .entry(msg.author.id)
         .or_default()
-        .set_bedtime(Arc::clone(http), msg.author.id, tm)
+        .start_winddown(bot.clone(), msg.author.id, length)
         .await;
 
     state.save();
 
-    let resp = format!("Your bedtime has been set to {}", tm);
+    let resp = if started {
+        format!(
+            "Winding down 🕯️ Bedtime is in {}. I'll check in along the way.",
+            HumanDuration(length)
+        )
+    } else {
+        "Set up sleep reminders first with `setup`".to_string()
+    };
 
-    msg.channel_id.say(http, resp).await?;
+    msg.channel_id.say(&ctx.http, resp).await?;
 
     Ok(())
 }
 
 #[command]
-#[description = "Tell the bot that you woke up for the day"]
-async fn wake(ctx: &Context, msg: &Message) -> CommandResult {
-    ctx.data
-        .write()
-        .await
-        .get_mut::<State>()
-        .expect("No state in context")
+#[description = "Set how insistent your sleep reminders are. Stronger \
+                 reminders come more often, escalate faster, and are blunter."]
+#[usage = "gentle|normal|drill-sergeant"]
+async fn intensity(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let intensity: Intensity = args.message().parse()?;
+
+    let mut state = bot.write().await;
+
+    state
         .users
         .entry(msg.author.id)
         .or_default()
-        .allow_awake();
+        .set_intensity(bot.clone(), msg.author.id, intensity)
+        .await;
+
+    state.save();
 
-    msg.channel_id.say(&ctx.http, "Good morning 🌅").await?;
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Your sleep reminder intensity has been set to {}",
+                intensity
+            ),
+        )
+        .await?;
 
     Ok(())
 }
 
+/// Maximum number of minutes sleep reminders can be fuzzed by
+const MAX_FUZZ_MINUTES: i64 = 60;
 #[command]
-#[description = "View your settings"]
-async fn info(ctx: &Context, msg: &Message) -> CommandResult {
-    let resp = ctx
-        .data
-        .write()
-        .await
-        .get_mut::<State>()
-        .expect("No state in context")
+#[description = "Start your sleep reminders at a random time up to this long \
+                 before or after your bedtime, picked anew each night, so \
+                 they're harder to tune out. Use `off` to start them right at \
+                 bedtime."]
+#[usage = "10m|off"]
+async fn fuzz(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+    let fuzz = if text == "off" {
+        None
+    } else {
+        let fuzz = time::parse_duration(text)?;
+        if fuzz.num_minutes() > MAX_FUZZ_MINUTES {
+            return Err(format!(
+                "Reminders can't be fuzzed by more than {} minutes",
+                MAX_FUZZ_MINUTES
+            )
+            .into());
+        }
+        Some(fuzz.num_minutes() as u32).filter(|&minutes| minutes > 0)
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_fuzz(bot.clone(), msg.author.id, fuzz)
+        .await;
+
+    state.save();
+
+    let resp = match fuzz {
+        Some(minutes) => format!(
+            "Your sleep reminders will start up to {} before or after your bedtime",
+            HumanDuration(chrono::Duration::minutes(i64::from(minutes)))
+        ),
+        None => "Your sleep reminders will start right at your bedtime".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("max-tone")]
+#[description = "Cap how blunt your sleep reminders get the longer you stay up, \
+                 from 1 (gentlest) to 4. Use `off` to let them escalate all the way."]
+#[usage = "2|off"]
+async fn max_tone(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let max_tone = match args.message().trim() {
+        "off" => None,
+        tone => match tone.parse() {
+            Ok(tone) if (1..=intensity::TONES).contains(&tone) => Some(tone),
+            _ => return Err(format!("Use a tone from 1 to {}, or `off`", intensity::TONES).into()),
+        },
+    };
+
+    let mut state = bot.write().await;
+
+    state
         .users
         .entry(msg.author.id)
         .or_default()
-        .to_string();
+        .set_max_tone(bot.clone(), msg.author.id, max_tone)
+        .await;
+
+    state.save();
+
+    let resp = match max_tone {
+        Some(tone) => format!("Your sleep reminders won't get blunter than tone {}", tone),
+        None => "Your sleep reminders will get blunter the longer you stay up".to_string(),
+    };
 
     msg.channel_id.say(&ctx.http, resp).await?;
 
@@ -122,47 +666,1618 @@ async fn info(ctx: &Context, msg: &Message) -> CommandResult {
 }
 
 #[command]
-#[description = "Enable sleep reminders"]
-async fn on(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut data = ctx.data.write().await;
+#[description = "Pick a message pack to change what your reminders say. Run \
+                 without arguments to list them. Use `off` for the default \
+                 messages."]
+#[usage = "[pirate|off]"]
+async fn theme(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let name = args.message().trim().to_lowercase();
 
-    let state = data.get_mut::<State>().expect("No state in context");
+    let theme = match name.as_str() {
+        "" => {
+            let names = templates::pack_names();
+            let resp = if names.is_empty() {
+                "There are no message packs to pick from".to_string()
+            } else {
+                format!("Message packs: {}", names.join(", "))
+            };
+            msg.channel_id.say(&ctx.http, resp).await?;
+            return Ok(());
+        }
+        "off" => None,
+        _ if templates::has_pack(&name) => Some(name),
+        _ => return Err(format!("There's no message pack called '{}'", name).into()),
+    };
 
-    let http = &ctx.http;
+    let mut state = bot.write().await;
 
     state
         .users
         .entry(msg.author.id)
         .or_default()
-        .on(Arc::clone(http), msg.author.id)
+        .set_theme(bot.clone(), msg.author.id, theme.clone())
         .await;
 
     state.save();
 
-    msg.channel_id.say(http, "Sleep reminders enabled").await?;
+    let resp = match theme {
+        Some(theme) => format!("Your reminders will use the {} message pack", theme),
+        None => "Your reminders will use the default messages".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
 
     Ok(())
 }
 
 #[command]
-#[description = "Disable sleep reminders"]
-async fn off(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut data = ctx.data.write().await;
+#[description = "Set the country whose public holidays you observe, by its \
+                 two-letter code. Use `off` to clear it."]
+#[usage = "DE|off"]
+async fn country(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+    let country: Option<Country> = if text == "off" {
+        None
+    } else {
+        Some(text.parse()?)
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_country(bot.clone(), msg.author.id, country)
+        .await;
+
+    state.save();
+
+    let resp = match country {
+        Some(country) => format!(
+            "Your country has been set to {}. Use `holiday-skip on` to skip \
+             reminders on the eves of its public holidays.",
+            country
+        ),
+        None => "Your country has been cleared".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("holiday-skip")]
+#[description = "Skip (`on`) or keep (`off`) sleep reminders on the nights \
+                 before your country's public holidays"]
+#[usage = "on|off"]
+async fn holiday_skip(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let holiday_skip = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
 
-    let state = data.get_mut::<State>().expect("No state in context");
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    user_info
+        .set_holiday_skip(bot.clone(), msg.author.id, holiday_skip)
+        .await;
+    let has_country = user_info.country().is_some();
+
+    state.save();
+
+    let resp = match (holiday_skip, has_country) {
+        (true, true) => "I'll skip your reminders on the nights before public holidays",
+        (true, false) => {
+            "I'll skip your reminders on the nights before public holidays, once \
+             you set your country with `country`"
+        }
+        (false, _) => "I'll remind you to sleep before public holidays too",
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Show when your next sleep reminders start, and any nights \
+                 skipped before then"]
+async fn next(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let now = Utc::now();
+    let next = {
+        let state = bot.read().await;
+        state.users.get(&msg.author.id).and_then(|user_info| {
+            let tz = user_info.time_zone()?;
+            Some((tz, user_info.next_bedtime(now)?))
+        })
+    };
+
+    let resp = match next {
+        Some((tz, (at, skipped))) => {
+            let mut lines: Vec<String> = skipped
+                .iter()
+                .map(|(night, reason)| {
+                    format!(
+                        "Skipping the night of {}: {}",
+                        night.format("%A, %B %-d"),
+                        reason
+                    )
+                })
+                .collect();
+            let local = at.with_timezone(&tz);
+            lines.push(format!(
+                "Your next reminders start {} at {}, in {}",
+                local.format("%A, %B %-d"),
+                Time(local.time()),
+                HumanDuration(at - now)
+            ));
+            lines.join("\n")
+        }
+        None => "You don't have a bedtime schedule. Set one with `setup`.".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("skip-tonight")]
+#[description = "Skip sleep reminders for tonight only"]
+async fn skip_tonight(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let mut state = bot.write().await;
+
+    let http = &ctx.http;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    let resp = match user_info.skip_tonight(bot.clone(), msg.author.id).await {
+        Some(night) => templates::render(
+            user_info.theme(),
+            "skipped_tonight",
+            "Skipping sleep reminders for the night of {night}. See you tomorrow!",
+            &[
+                ("user", &msg.author.mention()),
+                ("night", &night.format("%B %-d")),
+            ],
+        ),
+        None => "You don't have a bedtime scheduled to skip".to_string(),
+    };
+
+    state.save();
+
+    msg.channel_id.say(http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Undo your most recent time zone or bedtime change"]
+async fn undo(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let mut state = bot.write().await;
 
     let http = &ctx.http;
 
+    let change = match state.users.get_mut(&msg.author.id) {
+        Some(user_info) => user_info.undo(bot.clone(), msg.author.id).await,
+        None => None,
+    };
+
+    state.save();
+
+    let resp = match change {
+        Some(change) => {
+            let restored: Vec<_> = change.previous.iter().map(|s| s.to_string()).collect();
+            format!(
+                "Undid your change from {} ago. You're back to {}.",
+                HumanDuration(Utc::now() - change.at),
+                restored.join(" and ")
+            )
+        }
+        None => "There's nothing to undo".to_string(),
+    };
+
+    msg.channel_id.say(http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases("gm")]
+#[description = "Tell the bot that you woke up for the day"]
+async fn wake(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let resp = {
+        let mut state = bot.write().await;
+        let user_info = state.users.entry(msg.author.id).or_default();
+        user_info.allow_awake();
+        let resp = good_morning(user_info, msg.author.id);
+        state.save();
+        resp
+    };
+    audit::record(AuditEvent::NagAcknowledged {
+        user: msg.author.id,
+    });
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Set a phrase, or just emoji, that works like `wake` when you \
+                 DM it to me. Use `off` to remove it."]
+#[usage = "\"rise and grind\"|off"]
+async fn wakeword(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+    let phrase = match text.trim_matches(|c| matches!(c, '"' | '“' | '”')).trim() {
+        "" => return Err("Write a phrase to wake up with".into()),
+        "off" => None,
+        phrase => Some(phrase),
+    };
+
+    if phrase.map_or(0, |phrase| phrase.chars().count()) > WAKE_PHRASE_MAX_LEN {
+        return Err(format!(
+            "Wake phrases can't be longer than {} characters",
+            WAKE_PHRASE_MAX_LEN
+        )
+        .into());
+    }
+
+    let mut state = bot.write().await;
+
     state
         .users
         .entry(msg.author.id)
         .or_default()
-        .off(Arc::clone(http), msg.author.id)
-        .await;
+        .set_wake_phrase(phrase);
 
     state.save();
 
-    msg.channel_id.say(http, "Sleep reminders disabled").await?;
+    let resp = match phrase {
+        Some(phrase) => format!("DM me \"{}\" when you wake up", phrase),
+        None => "I removed your wake phrase".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases("settings")]
+#[description = "View your settings"]
+async fn info(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let state = bot.read().await;
+    let mut resp = match state.users.get(&msg.author.id) {
+        Some(user_info) => user_info.to_string(),
+        None => UserInfo::default().to_string(),
+    };
+    if state.is_paused(msg.author.id) {
+        resp.push_str(
+            "\n🔧 Sleep reminders are paused for maintenance. They'll be back \
+             soon.",
+        );
+    }
+    drop(state);
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Show what time it is for you, to check your time zone is right"]
+async fn now(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let tz = bot
+        .read()
+        .await
+        .users
+        .get(&msg.author.id)
+        .and_then(UserInfo::time_zone);
+
+    let resp = match tz {
+        Some(tz) => {
+            let now = Utc::now().with_timezone(&tz);
+            format!(
+                "It's {} for you ({}, {})",
+                Time(now.time()),
+                tz.name(),
+                UtcOffset(now.offset().fix())
+            )
+        }
+        None => "You haven't set a time zone yet. Set one with `tz`.".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("nag-log")]
+#[description = "View the most recent sleep reminders sent to you"]
+#[usage = "[count]"]
+async fn nag_log(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let count = if args.is_empty() {
+        NAG_LOG_DEFAULT_COUNT
+    } else {
+        args.parse::<usize>()?.min(NAG_LOG_MAX_COUNT)
+    };
+
+    let state = bot.read().await;
+
+    let (nags, tz) = match state.users.get(&msg.author.id) {
+        Some(user_info) => (user_info.recent_nags(count), user_info.time_zone()),
+        None => (Vec::new(), None),
+    };
+
+    let resp = if nags.is_empty() {
+        "I haven't sent you any sleep reminders yet".to_string()
+    } else {
+        nags.iter()
+            .map(|nag| {
+                let at = match tz {
+                    Some(tz) => nag.at.with_timezone(&tz).format("%Y-%m-%d %I:%M %p"),
+                    None => nag.at.format("%Y-%m-%d %I:%M %p UTC"),
+                };
+                match (&nag.error, nag.attempts) {
+                    (None, 1) => format!("`{}` ✅ delivered", at),
+                    (None, n) => format!("`{}` ✅ delivered after {} tries", at, n),
+                    (Some(err), 1) => format!("`{}` ❌ failed: {}", at, err),
+                    (Some(err), n) => format!("`{}` ❌ failed after {} tries: {}", at, n, err),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Attach a note to last night in your sleep diary, like how \
+                 much caffeine you had"]
+#[usage = "<text>"]
+async fn note(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    if text.is_empty() {
+        return Err("Write something to note".into());
+    }
+
+    if text.chars().count() > NOTE_MAX_LEN {
+        return Err(format!("Notes can't be longer than {} characters", NOTE_MAX_LEN).into());
+    }
+
+    let mut state = bot.write().await;
+
+    let noted = state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .note_last_night(text.to_string());
+
+    state.save();
+
+    let resp = match noted {
+        Some(night) => format!("Noted for the night of {} 📝", night.format("%B %-d")),
+        None => "You don't have any nights recorded to add a note to".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Download your sleep history as a spreadsheet, or with \
+                 `presence`, every time you were seen going to sleep or \
+                 waking up. With `url`, get a short-lived link to your \
+                 settings instead, for bringing them back with `import`."]
+#[usage = "csv|presence|url"]
+async fn export(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let presence = match args.message().trim() {
+        "csv" => false,
+        "presence" => true,
+        "url" => return export_url(ctx, msg).await,
+        _ => return Err("Use `csv` for your sleep history, `presence`, or `url`".into()),
+    };
+
+    let csv = {
+        let state = bot.read().await;
+
+        match state.users.get(&msg.author.id) {
+            Some(user_info) => {
+                let tz = user_info.time_zone().unwrap_or(Tz::UTC);
+                if presence {
+                    export::presence_csv(&user_info.transitions(), tz)
+                } else {
+                    export::csv(&user_info.nights(), tz)
+                }
+            }
+            None => return Err("You don't have any sleep history yet".into()),
+        }
+    };
+
+    let (filename, content) = if presence {
+        (
+            "presence-history.csv",
+            "Here's when you went to sleep and woke up 📊",
+        )
+    } else {
+        ("sleep-history.csv", "Here's your sleep history 📊")
+    };
+    let file = AttachmentType::Bytes {
+        data: csv.into_bytes().into(),
+        filename: filename.to_string(),
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![file], |m| m.content(content))
+        .await?;
+
+    Ok(())
+}
+
+/// DM a user a link to their settings that works for as long as a link token
+async fn export_url(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let url = bot
+        .app
+        .config
+        .dashboard_url
+        .clone()
+        .ok_or("Links aren't set up on this bot, so use `export csv` instead")?;
+
+    let token = {
+        let mut state = bot.write().await;
+
+        if !state.users.contains_key(&msg.author.id) {
+            return Err("You don't have any settings to export yet".into());
+        }
+
+        let token = link::mint_export(&mut state, msg.author.id);
+
+        state.save();
+
+        token
+    };
+
+    let resp = format!(
+        "Here's a link to your settings. It works for {} minutes, and `unlink` \
+         revokes it. Bring them back with `import <link>`.\n<{}/export.json?token={}>",
+        link::TOKEN_MINUTES,
+        url.trim_end_matches('/'),
+        token
+    );
+
+    msg.author.dm(&ctx.http, |m| m.content(resp)).await?;
+
+    if msg.guild_id.is_some() {
+        msg.channel_id
+            .say(&ctx.http, "I sent you a link to your settings in DMs 📬")
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Check imported settings the same way the commands setting them would
+fn check_import(profile: &Profile) -> Result<(), String> {
+    if profile
+        .max_tone
+        .is_some_and(|tone| !(1..=intensity::TONES).contains(&tone))
+    {
+        return Err(format!(
+            "The maximum tone has to be from 1 to {}",
+            intensity::TONES
+        ));
+    }
+    if profile
+        .fuzz
+        .is_some_and(|fuzz| i64::from(fuzz) > MAX_FUZZ_MINUTES)
+    {
+        return Err(format!(
+            "The bedtime fuzz can't be more than {} minutes",
+            MAX_FUZZ_MINUTES
+        ));
+    }
+    if profile
+        .sleep_goal
+        .is_some_and(|goal| chrono::Duration::minutes(i64::from(goal)) >= chrono::Duration::days(1))
+    {
+        return Err("The sleep goal is more sleep than there is time in a day".to_string());
+    }
+    if profile.skipped.len() > IMPORT_MAX_SKIPPED {
+        return Err(format!(
+            "There can't be more than {} skipped nights",
+            IMPORT_MAX_SKIPPED
+        ));
+    }
+    if profile.routine.len() > crate::routine::MAX_ITEMS {
+        return Err(format!(
+            "A routine can't have more than {} items",
+            crate::routine::MAX_ITEMS
+        ));
+    }
+    if profile
+        .routine
+        .iter()
+        .any(|item| item.trim().is_empty() || item.chars().count() > crate::routine::MAX_ITEM_LEN)
+    {
+        return Err(format!(
+            "Routine items can't be empty or longer than {} characters",
+            crate::routine::MAX_ITEM_LEN
+        ));
+    }
+    Ok(())
+}
+
+#[command]
+#[description = "Bring back settings from a link made with `export url`, \
+                 replacing your time zone, bedtime, sleep goal, reminder \
+                 settings, skipped nights and routine"]
+#[usage = "<link>"]
+async fn import(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let url = args.message().trim();
+    if url.is_empty() {
+        return Err("Give me a link made with `export url`".into());
+    }
+
+    let profile = crate::profile::fetch(url, bot.app.config.dashboard_url.as_deref()).await?;
+    check_import(&profile)?;
+
+    let question = "Replace your time zone, bedtime, sleep goal, reminder \
+                    settings, skipped nights and routine with the imported ones?";
+    if !confirm::confirm(ctx, msg.channel_id, msg.author.id, question).await? {
+        return Ok(());
+    }
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .apply_profile(bot.clone(), msg.author.id, profile)
+        .await;
+
+    state.save();
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            "Imported your settings ✅ `undo` brings back your old time zone \
+             and bedtime.",
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "View a chart of how often you went to bed on time. Green \
+                 bars are nights on time, red bars are nights you needed \
+                 reminders, gray bars are skipped nights, and the blue line \
+                 is your weekly average."]
+#[usage = "[30d]"]
+async fn chart(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let days = if args.is_empty() {
+        CHART_DEFAULT_DAYS
+    } else {
+        time::parse_duration(args.message())?
+            .num_days()
+            .clamp(1, CHART_MAX_DAYS)
+    };
+
+    let png = {
+        let state = bot.read().await;
+
+        let user_info = match state.users.get(&msg.author.id) {
+            Some(user_info) => user_info,
+            None => return Err("You don't have any sleep history yet".into()),
+        };
+
+        let tz = user_info.time_zone().unwrap_or(Tz::UTC);
+        let today = time::night_of(&Utc::now().with_timezone(&tz));
+        chart::render(&user_info.nights(), days, today)?
+    };
+
+    let file = AttachmentType::Bytes {
+        data: png.into(),
+        filename: "sleep-chart.png".to_string(),
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![file], |m| {
+            m.content(format!("Your bedtimes over the last {} days 📈", days))
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Get a realistic bedtime suggestion based on when you usually \
+                 fall asleep. This needs presence tracking."]
+async fn suggest(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let suggestion = {
+        let state = bot.read().await;
+
+        state.users.get(&msg.author.id).and_then(|user_info| {
+            let tz = user_info.time_zone().unwrap_or(Tz::UTC);
+            suggest::suggest(&user_info.nights(), tz, user_info.sleep_debt())
+        })
+    };
+
+    let resp = match suggestion {
+        Some(Suggestion {
+            usual,
+            bedtime,
+            catch_up: Some(catch_up),
+        }) => format!(
+            "You usually go offline around {}, and you're short on sleep this week. \
+             Try setting `bedtime {}` to catch up, {} earlier than usual.",
+            usual,
+            bedtime,
+            HumanDuration(catch_up)
+        ),
+        Some(suggestion) => format!(
+            "You usually go offline around {}. Try setting `bedtime {}`.",
+            suggestion.usual, suggestion.bedtime
+        ),
+        None => "I haven't seen you fall asleep enough times to suggest a bedtime yet. \
+                 Make sure presence tracking is on with `presence on`."
+            .to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("sleep-goal")]
+#[description = "Set how long you want to sleep each night, to track your sleep \
+                 debt. It's 8 hours unless you change it."]
+#[usage = "7h30m"]
+async fn sleep_goal(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let goal = time::parse_duration(args.message())?;
+    if goal >= chrono::Duration::days(1) {
+        return Err("That's more sleep than there is time in a day".into());
+    }
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_sleep_goal(goal);
+
+    state.save();
+
+    let resp = format!(
+        "Your sleep goal has been set to {} a night",
+        HumanDuration(goal)
+    );
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Get reminded of something once, at the next time it's a \
+                 time of day. With no arguments, view your pending reminders."]
+#[usage = "7:15 AM take meds"]
+async fn remind(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    if text.is_empty() {
+        let state = bot.read().await;
+        let user_info = state.users.get(&msg.author.id);
+        let tz = user_info.and_then(UserInfo::time_zone).unwrap_or(Tz::UTC);
+        let reminders = user_info.map(UserInfo::reminders).unwrap_or_default();
+        let resp = if reminders.is_empty() {
+            "You don't have any reminders".to_string()
+        } else {
+            reminders
+                .iter()
+                .map(|reminder| {
+                    let at = reminder.at.with_timezone(&tz);
+                    format!(
+                        "{} {}: {}",
+                        at.format("%B %-d"),
+                        Time(at.time()),
+                        reminder.text
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        drop(state);
+        msg.channel_id.say(&ctx.http, resp).await?;
+        return Ok(());
+    }
+
+    // Times look like `7:15 AM`, so they're the first two words
+    let mut words = text.splitn(3, ' ');
+    let time = format!(
+        "{} {}",
+        words.next().unwrap_or_default(),
+        words.next().unwrap_or_default()
+    );
+    let time: Time = time.parse()?;
+    let what = words.next().unwrap_or_default().trim();
+
+    if what.is_empty() {
+        return Err("Say what to remind you of".into());
+    }
+
+    if what.chars().count() > reminder::MAX_TEXT_LEN {
+        return Err(format!(
+            "Reminders can't be longer than {} characters",
+            reminder::MAX_TEXT_LEN
+        )
+        .into());
+    }
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+
+    if user_info.reminders().len() >= reminder::MAX_REMINDERS {
+        return Err(format!(
+            "You can't have more than {} reminders",
+            reminder::MAX_REMINDERS
+        )
+        .into());
+    }
+
+    let at = user_info
+        .add_reminder(bot.clone(), msg.author.id, time, what.to_string())
+        .await;
+
+    state.save();
+
+    let resp = match at {
+        Some(_) => format!("I'll remind you at {}", time),
+        None => "Set your time zone first".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Enable sleep reminders"]
+async fn on(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let mut state = bot.write().await;
+
+    let http = &ctx.http;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    user_info.on(bot.clone(), msg.author.id).await;
+    let resp = templates::render(
+        user_info.theme(),
+        "reminders_on",
+        "Sleep reminders enabled",
+        &[("user", &msg.author.mention())],
+    );
+
+    state.save();
+
+    msg.channel_id.say(http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Disable sleep reminders"]
+async fn off(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let question = "Turn off sleep reminders? Your streak won't be tracked \
+                    while they're off.";
+    if !confirm::confirm(ctx, msg.channel_id, msg.author.id, question).await? {
+        return Ok(());
+    }
+
+    let mut state = bot.write().await;
+
+    let http = &ctx.http;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    user_info.off(bot.clone(), msg.author.id).await;
+    let resp = templates::render(
+        user_info.theme(),
+        "reminders_off",
+        "Sleep reminders disabled",
+        &[("user", &msg.author.mention())],
+    );
+
+    state.save();
+
+    msg.channel_id.say(http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Get a token to paste into an app or website, letting it act \
+                 for you with this bot. It's sent by DM and expires soon."]
+async fn link(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let token = {
+        let mut state = bot.write().await;
+
+        state.users.entry(msg.author.id).or_default();
+
+        let token = link::mint(&mut state, msg.author.id);
+
+        state.save();
+
+        token
+    };
+
+    let resp = format!(
+        "Here's your link token. It works for {} minutes, and `unlink` revokes \
+         it. Don't share it with anyone.\n`{}`",
+        link::TOKEN_MINUTES,
+        token
+    );
+
+    msg.author.dm(&ctx.http, |m| m.content(resp)).await?;
+
+    if msg.guild_id.is_some() {
+        msg.channel_id
+            .say(&ctx.http, "I sent you a link token in DMs 📬")
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = "Revoke all of your link tokens"]
+async fn unlink(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let mut state = bot.write().await;
+
+    if let Some(user_info) = state.users.get_mut(&msg.author.id) {
+        user_info.revoke_links();
+    }
+
+    state.save();
+
+    msg.channel_id
+        .say(&ctx.http, "All of your link tokens have been revoked")
+        .await?;
+
+    Ok(())
+}
+
+#[command("forget-me")]
+#[description = "Delete all of your settings and sleep history"]
+async fn forget_me(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let question = "Delete all of your settings and sleep history? This can't \
+                    be undone.";
+    if !confirm::confirm(ctx, msg.channel_id, msg.author.id, question).await? {
+        return Ok(());
+    }
+
+    let mut state = bot.write().await;
+
+    let forgotten = state.forget(bot.clone(), &[msg.author.id]).await;
+
+    state.save();
+
+    let resp = if forgotten > 0 {
+        "I've forgotten everything about you. Sleep well! 👋"
+    } else {
+        "I don't have anything saved about you"
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Move all of your settings, sleep history, and streaks to \
+                 another Discord account, once it confirms. They're deleted \
+                 from this account."]
+#[usage = "@newaccount"]
+async fn transfer(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let to: UserId = args.message().trim().parse()?;
+    if to == msg.author.id {
+        return Err("Mention the account you want to move your settings to".into());
+    }
+
+    if !bot.read().await.users.contains_key(&msg.author.id) {
+        return Err("I don't have anything saved about you to move".into());
+    }
+
+    transfer::spawn_request_consent(ctx, msg.author.id, to);
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(format!(
+                "I DMed {} to confirm the move. Accept it from that account.",
+                to.mention()
+            ))
+            .allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Allow (`on`) or stop (`off`) watching your online status, so \
+                 sleep reminders only go out while you're online. This is off \
+                 unless you turn it on."]
+#[usage = "on|off"]
+async fn presence(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let track_presence = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_track_presence(track_presence);
+
+    state.save();
+
+    let resp = if track_presence {
+        "I'll watch your online status, and only remind you to sleep while you're online"
+    } else {
+        "I've stopped watching your online status"
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Choose whether you count as awake while your status is idle, \
+                 since you might go idle while watching videos. `activity` \
+                 counts you as awake only if you sent a message recently."]
+#[usage = "awake|asleep|activity"]
+async fn idle(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let idle_policy: IdlePolicy = args.message().parse()?;
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_idle_policy(idle_policy);
+
+    state.save();
+
+    let resp = match idle_policy {
+        IdlePolicy::Awake => "You'll count as awake while you're idle".to_string(),
+        IdlePolicy::Asleep => "You'll count as asleep while you're idle".to_string(),
+        IdlePolicy::RecentActivity => format!(
+            "You'll count as awake while you're idle only if you sent a message \
+             in the last {} minutes",
+            presence::RECENT_ACTIVITY_MINUTES
+        ),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("awake-clients")]
+#[description = "Choose which Discord clients count as you being awake while \
+                 you're online on them, like `desktop web` if your phone stays \
+                 online in your pocket all night. Use `all` to count every client."]
+#[usage = "desktop mobile web|all"]
+async fn awake_clients(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+    let clients = if text == "all" {
+        None
+    } else {
+        let clients = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(str::parse)
+            .collect::<Result<BTreeSet<Client>, _>>()?;
+        if clients.is_empty() {
+            return Err("Name at least one client, like `desktop`, or use `all`".into());
+        }
+        Some(clients)
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_awake_clients(clients.clone());
+
+    state.save();
+
+    let resp = match clients {
+        Some(clients) => format!(
+            "You'll only count as awake while you're online on {}",
+            clients
+                .iter()
+                .map(Client::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => "You'll count as awake while you're online on any client".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("awake-threshold")]
+#[description = "Only send sleep reminders once you've been online for a while \
+                 in a row, or were active recently, so a brief blip online \
+                 doesn't set them off. Activity means sending a message, \
+                 reacting, or typing. Use `off` to send them on any sign of you \
+                 being up."]
+#[usage = "10m 15m|off"]
+async fn awake_threshold(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+    let threshold = if text == "off" {
+        None
+    } else {
+        let (online, active) = text
+            .split_once(char::is_whitespace)
+            .ok_or("Use something like `awake-threshold 10m 15m`")?;
+        Some(AwakeThreshold {
+            online_minutes: time::parse_duration(online)?.num_minutes() as u32,
+            active_minutes: time::parse_duration(active)?.num_minutes() as u32,
+        })
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_awake_threshold(bot.clone(), msg.author.id, threshold)
+        .await;
+
+    state.save();
+
+    let resp = match threshold {
+        Some(threshold) => format!(
+            "I'll only remind you to sleep once you've been online for {} in a \
+             row, or were active in the last {}",
+            HumanDuration(chrono::Duration::minutes(i64::from(
+                threshold.online_minutes
+            ))),
+            HumanDuration(chrono::Duration::minutes(i64::from(
+                threshold.active_minutes
+            )))
+        ),
+        None => "I'll remind you to sleep on any sign of you being up".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("single-nag")]
+#[description = "Get one sleep reminder message a night, edited with how long \
+                 you've been up (`on`), or a new message for each reminder (`off`)"]
+#[usage = "on|off"]
+async fn single_nag(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let single_nag = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_single_nag(bot.clone(), msg.author.id, single_nag)
+        .await;
+
+    state.save();
+
+    let resp = if single_nag {
+        "I'll send one sleep reminder a night and keep it updated"
+    } else {
+        "I'll send a new message for each sleep reminder"
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("clean-up")]
+#[description = "Have me delete (`on`) or keep (`off`) last night's sleep reminder \
+                 DMs once you say good morning, leaving a summary of the night"]
+#[usage = "on|off"]
+async fn clean_up(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let clean_up = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_clean_up(bot.clone(), msg.author.id, clean_up)
+        .await;
+
+    state.save();
+
+    let resp = if clean_up {
+        "I'll clear out last night's sleep reminders when you wake up"
+    } else {
+        "I'll leave your sleep reminders where they are"
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("morning-summary")]
+#[description = "Opt in (`on`) or out (`off`) of a DM the first time you come \
+                 online each morning, saying how late you went to sleep. This \
+                 needs presence tracking."]
+#[usage = "on|off"]
+async fn morning_summary(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let morning_summary = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    user_info.set_morning_summary(morning_summary);
+    let tracks_presence = user_info.tracks_presence();
+
+    state.save();
+
+    let resp = match (morning_summary, tracks_presence) {
+        (true, true) => "I'll tell you how last night went when you come online in the morning",
+        (true, false) => {
+            "I'll tell you how last night went when you come online in the morning, \
+             once you turn on presence tracking with `presence on`"
+        }
+        (false, _) => "I won't send you morning summaries anymore",
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("away-reply")]
+#[description = "Opt in (`on`) or out (`off`) of me replying for you when \
+                 someone mentions you while you're asleep past your bedtime, \
+                 in servers that allow it. This needs presence tracking."]
+#[usage = "on|off"]
+async fn away_reply(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let away_reply = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+    user_info.set_away_reply(away_reply);
+    let tracks_presence = user_info.tracks_presence();
+
+    state.save();
+
+    let resp = match (away_reply, tracks_presence) {
+        (true, true) => {
+            "I'll let people who mention you know when you're asleep, in servers \
+             that allow it"
+        }
+        (true, false) => {
+            "I'll let people who mention you know when you're asleep, in servers \
+             that allow it, once you turn on presence tracking with `presence on`"
+        }
+        (false, _) => "I won't reply for you when you're mentioned anymore",
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Opt in (`on`) to being timed out in this server from your \
+                 bedtime until you're expected to wake up, for when DMs alone \
+                 won't get you to bed. Use `off` to opt out, here or in DMs for \
+                 every server; a lockout that already started keeps going for \
+                 a while."]
+#[usage = "on|off"]
+async fn lockout(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let lockout = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    if lockout {
+        let guild = msg
+            .guild_id
+            .ok_or("Use `lockout on` in the server you want to be locked out of")?;
+        let can_moderate = ctx
+            .cache
+            .member(guild, ctx.cache.current_user_id())
+            .and_then(|member| member.permissions(&ctx.cache).ok())
+            .is_some_and(|permissions| permissions.moderate_members());
+        if !can_moderate {
+            return Err("I need the Moderate Members permission in this server to \
+                        time you out"
+                .into());
+        }
+
+        let mut state = bot.write().await;
+        state
+            .users
+            .entry(msg.author.id)
+            .or_default()
+            .lock_out_in(guild);
+        state.save();
+
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "I'll time you out in this server from your bedtime until you're \
+                 expected to wake up",
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let ends = {
+        let mut state = bot.write().await;
+        let ends = state
+            .users
+            .entry(msg.author.id)
+            .or_default()
+            .cancel_lockout(msg.guild_id, Utc::now());
+        state.save();
+        ends
+    };
+
+    for &(guild, end) in &ends {
+        guild
+            .edit_member(&ctx.http, msg.author.id, |member| {
+                member.disable_communication_until_datetime(end.into())
+            })
+            .await?;
+    }
+
+    let resp = match ends.iter().map(|&(_, end)| end).max() {
+        Some(end) => format!(
+            "I won't time you out at bedtime anymore. Tonight's lockout ends in {}.",
+            HumanDuration(end - Utc::now())
+        ),
+        None => "I won't time you out at bedtime anymore".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Opt in (`on`) or out (`off`) of being wished good night in \
+                 this server at your bedtime"]
+#[usage = "on|off"]
+#[only_in(guilds)]
+async fn announce(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let announce = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_announce(guild, announce);
+
+    state.save();
+
+    let resp = if !announce {
+        "I won't wish you good night in this server anymore"
+    } else if state.guild_config(Some(guild)).announce_channel.is_some() {
+        "I'll wish you good night in this server at your bedtime"
+    } else {
+        "I'll wish you good night in this server at your bedtime, once an admin \
+         sets an announcement channel"
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Let someone, like a parent, set your bedtime and reminder \
+                 intensity, once they agree. Use `off` to take that back at \
+                 any time."]
+#[usage = "@parent|off"]
+async fn manager(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    let manager = if text == "off" {
+        None
+    } else {
+        let id: UserId = text.parse()?;
+        if id == msg.author.id {
+            return Err("You already manage your own settings".into());
+        }
+        Some(id)
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_manager(manager);
+
+    state.save();
+
+    let resp = match manager {
+        Some(manager) => {
+            manager::spawn_request_consent(ctx, msg.author.id, manager);
+            format!(
+                "I asked {} if they're okay with managing your settings",
+                manager.mention()
+            )
+        }
+        None => "Only you can change your settings now".to_string(),
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Have me DM a friend if you're still up some time past your \
+                 bedtime, once they agree. Use `off` to stop."]
+#[usage = "@friend after 45m"]
+async fn notify(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    let buddy = if text == "off" {
+        None
+    } else {
+        let mut parts = text.split_whitespace();
+        let id: UserId = parts.next().unwrap_or_default().parse()?;
+        if parts.next() != Some("after") {
+            return Err("Use something like `notify @friend after 45m`".into());
+        }
+        let after = time::parse_duration(&parts.collect::<String>())?;
+        if id == msg.author.id {
+            return Err("You can't be your own buddy".into());
+        }
+        Some(Buddy {
+            id,
+            after_minutes: after.num_minutes(),
+            accepted: false,
+        })
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_buddy(bot.clone(), msg.author.id, buddy)
+        .await;
+
+    state.save();
+
+    let resp = match buddy {
+        Some(buddy) => {
+            buddy::spawn_request_consent(ctx, msg.author.id, buddy);
+            format!(
+                "I asked {} if they're okay with being told when you stay up late",
+                buddy.id.mention()
+            )
+        }
+        None => "I won't tell anyone when you stay up late".to_string(),
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "View the members of this server who earned the most XP by \
+                 going to bed on time"]
+#[only_in(guilds)]
+async fn leaderboard(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let members = match ctx.cache.guild(guild) {
+        Some(guild) => guild.members,
+        None => return Err("Server isn't cached yet".into()),
+    };
+
+    let state = bot.read().await;
+
+    let mut ranked: Vec<(UserId, u64)> = state
+        .users
+        .iter()
+        .filter(|(id, user_info)| members.contains_key(id) && user_info.xp() > 0)
+        .map(|(&id, user_info)| (id, user_info.xp()))
+        .collect();
+    ranked.sort_by_key(|&(_, xp)| std::cmp::Reverse(xp));
+
+    let resp = if ranked.is_empty() {
+        "Nobody in this server has earned any XP yet".to_string()
+    } else {
+        ranked
+            .iter()
+            .take(LEADERBOARD_COUNT)
+            .enumerate()
+            .map(|(i, &(id, xp))| {
+                format!(
+                    "{}. {} — level {} ({} XP)",
+                    i + 1,
+                    id.mention(),
+                    xp::level(xp),
+                    xp
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "View the badges you unlocked, and the ones left to unlock"]
+async fn badges(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let resp = {
+        let state = bot.read().await;
+
+        let unlocked = state
+            .users
+            .get(&msg.author.id)
+            .map(|user_info| user_info.badges())
+            .unwrap_or_default();
+
+        Badge::ALL
+            .iter()
+            .map(|badge| {
+                let icon = if unlocked.contains(badge) {
+                    "🏅"
+                } else {
+                    "🔒"
+                };
+                format!("{} {}", icon, badge)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Post a message that members can react to for help setting up sleep reminders"]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn opt_in_message(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let opt_in = msg
+        .channel_id
+        .say(
+            &ctx.http,
+            "Want reminders to go to bed? React to this message with 🌙 and \
+             I'll DM you to get set up.",
+        )
+        .await?;
+
+    opt_in.react(&ctx.http, '🌙').await?;
+
+    let mut state = bot.write().await;
+
+    state.add_opt_in_message(opt_in.id);
+
+    state.save();
 
     Ok(())
 }
@@ -0,0 +1,472 @@
+use crate::bot::Bot;
+use crate::confirm;
+use crate::features::{self, Feature};
+use crate::send_queue;
+use crate::templates;
+use crate::user_info::UserInfo;
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serenity::{
+    constants::MESSAGE_CODE_LIMIT,
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::*,
+    prelude::*,
+};
+
+#[group]
+#[prefixes("admin")]
+#[description = "Commands for the bot's operator"]
+#[owners_only]
+#[commands(
+    queue,
+    show_metrics,
+    feature,
+    usage,
+    reload_templates,
+    maintenance,
+    simulate,
+    broadcast
+)]
+pub struct Admin;
+
+/// Maximum number of active and upcoming sleep reminders to list each
+const QUEUE_COUNT: usize = 15;
+
+/// Number of most common time zones to list in usage statistics
+const TOP_TIME_ZONES: usize = 5;
+
+/// Number of days of sleep history the acknowledgment rate covers
+const ACK_DAYS: i64 = 7;
+
+/// Width of the longest bar in the bedtime histogram
+const HISTOGRAM_WIDTH: usize = 20;
+
+/// Number of days within which users must have used the bot to get
+/// broadcasts sent with `--only-active`
+const ACTIVE_DAYS: i64 = 30;
+
+/// Format a time for operators, who may not share a time zone with the user
+fn fmt_utc(at: chrono::DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+/// Parse an instant given either in RFC 3339, like `2024-03-31T01:30:00Z`, or
+/// as a date and time in a user's time zone, like `2024-03-31 01:30`
+fn parse_instant(s: &str, tz: Option<Tz>) -> Result<DateTime<Utc>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    let local = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").map_err(|_| {
+        format!(
+            "Couldn't read '{}'. Use something like `2024-03-31 01:30`, or \
+             `2024-03-31T01:30:00Z`.",
+            s
+        )
+    })?;
+    let tz = tz.ok_or("The user has no time zone, so give a time in RFC 3339")?;
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|at| at.with_timezone(&Utc))
+        .ok_or_else(|| {
+            format!(
+                "{} doesn't exist in {} because of a daylight saving transition",
+                s,
+                tz.name()
+            )
+        })
+}
+
+#[command]
+#[description = "List sleep reminders going out right now and coming up next"]
+async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+
+    let state = bot.read().await;
+
+    let last_fired = |id: &UserId| match state.users.get(id).and_then(UserInfo::last_fired) {
+        Some(at) => fmt_utc(at),
+        None => "never".to_string(),
+    };
+
+    let active: Vec<String> = state
+        .users
+        .iter()
+        .filter(|(_, user_info)| user_info.is_nagging())
+        .map(|(id, _)| format!("`{}` since {}", id, last_fired(id)))
+        .collect();
+
+    let upcoming = bot.app.scheduler.upcoming();
+
+    let mut lines = vec![format!("**Nagging now ({})**", active.len())];
+    lines.extend(active.into_iter().take(QUEUE_COUNT));
+    lines.push(format!("**Coming up ({} scheduled)**", upcoming.len()));
+    lines.extend(upcoming.iter().take(QUEUE_COUNT).map(|(at, id)| {
+        format!(
+            "`{}` at {}, last fired {}",
+            id,
+            fmt_utc(*at),
+            last_fired(id)
+        )
+    }));
+
+    msg.channel_id.say(&ctx.http, lines.join("\n")).await?;
+
+    Ok(())
+}
+
+#[command("metrics")]
+#[description = "Get command, state saving, and sleep reminder latencies, in \
+                 the Prometheus text format"]
+async fn show_metrics(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let file = AttachmentType::Bytes {
+        data: bot.app.metrics.render().into_bytes().into(),
+        filename: "metrics.txt".to_string(),
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![file], |m| m)
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "List experimental features, or turn one on or off until the \
+                 bot restarts"]
+#[usage = "[feature on|off]"]
+async fn feature(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let text = args.message().trim();
+
+    let resp = if text.is_empty() {
+        Feature::ALL
+            .iter()
+            .map(|&feature| {
+                let status = if features::is_enabled(feature) {
+                    "on"
+                } else {
+                    "off"
+                };
+                format!("`{}` ({}): {}", feature, status, feature.description())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        let (feature, enabled) = match text.split_once(' ') {
+            Some((feature, "on")) => (feature, true),
+            Some((feature, "off")) => (feature, false),
+            _ => return Err("Use something like `escalation off`".into()),
+        };
+        let feature: Feature = feature.parse()?;
+        features::set_enabled(feature, enabled);
+        format!(
+            "Feature `{}` turned {}",
+            feature,
+            if enabled { "on" } else { "off" }
+        )
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Summarize how the bot is used, without naming anyone"]
+async fn usage(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let now = Utc::now();
+    let ack_since = (now - Duration::days(ACK_DAYS)).date().naive_utc();
+
+    let state = bot.read().await;
+
+    let mut active = 0;
+    let mut time_zones: HashMap<&str, usize> = HashMap::new();
+    let mut bedtime_hours = [0; 24];
+    let mut nags = 0;
+    let (mut nights, mut acknowledged) = (0, 0);
+
+    for user_info in state.users.values() {
+        if let Some((tz, bedtime)) = user_info.active_schedule() {
+            active += 1;
+            *time_zones.entry(tz.name()).or_default() += 1;
+            bedtime_hours[bedtime.0.hour() as usize] += 1;
+        }
+        nags += user_info.nags_delivered_since(now - Duration::hours(24));
+        for night in user_info.nights_since(ack_since) {
+            if night.skipped || night.nags == 0 {
+                continue;
+            }
+            nights += 1;
+            if night.woke.is_some() || night.fell_asleep.is_some() {
+                acknowledged += 1;
+            }
+        }
+    }
+
+    let mut time_zones: Vec<_> = time_zones.into_iter().collect();
+    time_zones.sort_by_key(|&(name, count)| (std::cmp::Reverse(count), name));
+    let time_zones = time_zones
+        .iter()
+        .take(TOP_TIME_ZONES)
+        .map(|(name, count)| format!("{} ({})", name, count))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let ack_rate = if nights == 0 {
+        "n/a".to_string()
+    } else {
+        format!("{}%", acknowledged * 100 / nights)
+    };
+
+    let most = bedtime_hours.iter().copied().max().unwrap_or(0).max(1);
+    let histogram = bedtime_hours
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(hour, &count)| {
+            let bar = "█".repeat((count * HISTOGRAM_WIDTH).div_ceil(most));
+            format!("{:02}:00 {} {}", hour, bar, count)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let resp = format!(
+        "**Active users:** {} of {} known\n\
+         **Top time zones:** {}\n\
+         **Sleep reminders sent in the last 24h:** {}\n\
+         **Nights acknowledged in the last {} days:** {}\n\
+         **Bedtimes:**\n```\n{}\n```",
+        active,
+        state.users.len(),
+        if time_zones.is_empty() {
+            "none"
+        } else {
+            &time_zones
+        },
+        nags,
+        ACK_DAYS,
+        ack_rate,
+        if histogram.is_empty() {
+            "none"
+        } else {
+            &histogram
+        },
+    );
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("reload-templates")]
+#[description = "Load the message templates and message packs again, keeping \
+                 the old ones if the new ones have mistakes"]
+async fn reload_templates(ctx: &Context, msg: &Message) -> CommandResult {
+    let packs = templates::reload()?;
+
+    let resp = if packs.is_empty() {
+        "Reloaded message templates".to_string()
+    } else {
+        format!(
+            "Reloaded message templates and message packs: {}",
+            packs.join(", ")
+        )
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Pause sleep reminders for everyone, or for one user, while \
+                 deploying or debugging. Nights keep running, but nothing is \
+                 delivered. Run without arguments to see what's paused."]
+#[usage = "[on|off] [user ID]"]
+async fn maintenance(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    let mut state = bot.write().await;
+
+    if text.is_empty() {
+        let mut users: Vec<String> = state
+            .paused_users
+            .iter()
+            .map(|id| format!("`{}`", id))
+            .collect();
+        users.sort();
+        let resp = format!(
+            "Paused for everyone: {}\nPaused users: {}",
+            state.paused,
+            if users.is_empty() {
+                "none".to_string()
+            } else {
+                users.join(", ")
+            }
+        );
+        drop(state);
+        msg.channel_id.say(&ctx.http, resp).await?;
+        return Ok(());
+    }
+
+    let (paused, user) = match text.split_once(' ') {
+        Some((switch, user)) => (switch, Some(user.trim())),
+        None => (text, None),
+    };
+    let paused = match paused {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`, optionally followed by a user ID".into()),
+    };
+    let user = match user {
+        Some(user) => Some(
+            user.parse::<UserId>()
+                .map_err(|_| format!("'{}' isn't a user ID or mention", user))?,
+        ),
+        None => None,
+    };
+
+    let resp = match (user, paused) {
+        (Some(user), true) => {
+            state.paused_users.insert(user);
+            format!("Sleep reminders to `{}` are paused", user)
+        }
+        (Some(user), false) => {
+            state.paused_users.remove(&user);
+            format!("Sleep reminders to `{}` are no longer paused", user)
+        }
+        (None, true) => {
+            state.paused = true;
+            "Sleep reminders are paused for everyone".to_string()
+        }
+        (None, false) => {
+            state.paused = false;
+            "Sleep reminders are no longer paused for everyone".to_string()
+        }
+    };
+    println!("{}", resp);
+
+    state.save();
+    drop(state);
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Show what a user's sleep reminders would be doing at some \
+                 time, without sending anything. Times without an offset are \
+                 in the user's time zone."]
+#[usage = "<user ID> 2024-03-31 01:30"]
+async fn simulate(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let (user, at) = args
+        .message()
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or("Give a user and a time, like `<user ID> 2024-03-31 01:30`")?;
+    let user: UserId = user
+        .parse()
+        .map_err(|_| format!("'{}' isn't a user ID or mention", user))?;
+
+    let state = bot.read().await;
+    let user_info = state
+        .users
+        .get(&user)
+        .ok_or_else(|| format!("There's no user `{}`", user))?;
+    let at = parse_instant(at.trim(), user_info.time_zone())?;
+
+    let mut lines = vec![format!("**Simulating `{}` at {}**", user, fmt_utc(at))];
+    if state.is_paused(user) {
+        lines.push("Sleep reminders are paused for maintenance".to_string());
+    }
+    lines.extend(user_info.simulate(user, at, bot.app.config.nag_timeout));
+    drop(state);
+
+    msg.channel_id.say(&ctx.http, lines.join("\n")).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "DM a message to every user the bot can reach, like to announce \
+                 downtime or a change in how the bot behaves. With \
+                 `--only-active`, only users who used the bot in the last 30 \
+                 days get it. Messages go out through the send queue, so this \
+                 can take a while."]
+#[usage = "<message> [--only-active]"]
+async fn broadcast(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+    let (text, only_active) = match text.strip_suffix("--only-active") {
+        Some(text) => (text.trim(), true),
+        None => (text, false),
+    };
+    if text.is_empty() {
+        return Err("Write a message to broadcast".into());
+    }
+    if text.chars().count() > MESSAGE_CODE_LIMIT {
+        return Err(format!(
+            "Broadcasts can be at most {} characters long",
+            MESSAGE_CODE_LIMIT
+        )
+        .into());
+    }
+
+    let since = Utc::now() - Duration::days(ACTIVE_DAYS);
+    let users: Vec<UserId> = bot
+        .read()
+        .await
+        .users
+        .iter()
+        .filter(|(_, user_info)| !user_info.is_dormant())
+        .filter(|(_, user_info)| !only_active || user_info.active_since(since))
+        .map(|(&id, _)| id)
+        .collect();
+
+    let question = format!("DM that message to {} users?", users.len());
+    if !confirm::confirm(ctx, msg.channel_id, msg.author.id, &question).await? {
+        return Ok(());
+    }
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!("Broadcasting to {} users 📣", users.len()),
+        )
+        .await?;
+
+    let ctx = ctx.clone();
+    let channel = msg.channel_id;
+    let text = text.to_string();
+    tokio::spawn(async move {
+        let mut sent = 0;
+        for &user in &users {
+            send_queue::wait_turn().await;
+            let res = match user.create_dm_channel(&ctx.http).await {
+                Ok(dm) => dm.say(&ctx.http, &text).await.map(|_| ()),
+                Err(err) => Err(err),
+            };
+            match res {
+                Ok(()) => sent += 1,
+                Err(err) => println!("Error broadcasting to user '{}': {}", user, err),
+            }
+        }
+
+        let resp = format!("Broadcast delivered to {} of {} users", sent, users.len());
+        if let Err(err) = channel.say(&ctx.http, resp).await {
+            println!("Error reporting broadcast: {}", err);
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use crate::bot::Bot;
+use crate::challenge::Challenge;
+use crate::time;
+
+use std::collections::BTreeSet;
+
+use chrono::Utc;
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::*,
+    prelude::*,
+};
+
+#[group]
+#[prefixes("challenge")]
+#[description = "Compete with other server members to go to bed on time"]
+#[only_in(guilds)]
+#[default_command(standings)]
+#[commands(standings, start, join, leave)]
+pub struct Challenges;
+
+#[command]
+#[description = "View the standings of this server's bedtime challenge"]
+async fn standings(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let state = bot.read().await;
+
+    let resp = match state.guild_config(Some(guild)).challenge {
+        Some(challenge) => challenge.standings_message(&state),
+        None => "There's no bedtime challenge running in this server".to_string(),
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Start a bedtime challenge in this channel, lasting some number of days"]
+#[usage = "7d"]
+#[required_permissions("MANAGE_GUILD")]
+async fn start(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let length = time::parse_duration(args.message())?;
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    let config = state.guilds.entry(guild).or_default();
+
+    if config.challenge.is_some() {
+        return Err("A bedtime challenge is already running in this server".into());
+    }
+
+    let now = Utc::now();
+    config.challenge = Some(Challenge {
+        channel: msg.channel_id,
+        start: now,
+        end: now + length,
+        participants: BTreeSet::new(),
+    });
+
+    state.save();
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            "A bedtime challenge has started! 🏁 Join with `challenge join`, and \
+             I'll post the standings here every day.",
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Join this server's bedtime challenge"]
+async fn join(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    let challenge = state
+        .guilds
+        .get_mut(&guild)
+        .and_then(|config| config.challenge.as_mut());
+
+    let resp = match challenge {
+        Some(challenge) => {
+            challenge.participants.insert(msg.author.id);
+            "You joined the bedtime challenge. Good luck! 🌙"
+        }
+        None => "There's no bedtime challenge running in this server",
+    };
+
+    state.save();
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Leave this server's bedtime challenge"]
+async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    let challenge = state
+        .guilds
+        .get_mut(&guild)
+        .and_then(|config| config.challenge.as_mut());
+
+    let left = challenge.is_some_and(|challenge| challenge.participants.remove(&msg.author.id));
+
+    let resp = if left {
+        "You left the bedtime challenge"
+    } else {
+        "You're not in a bedtime challenge"
+    };
+
+    state.save();
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
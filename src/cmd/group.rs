@@ -0,0 +1,360 @@
+use crate::bot::Bot;
+use crate::group::{self, Group, GroupBedtime};
+use crate::state::State;
+use crate::time::{HumanDuration, Time};
+
+use chrono::Utc;
+
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::*,
+    prelude::*,
+};
+
+/// Maximum length of a group name
+const MAX_NAME_LEN: usize = 32;
+
+#[group]
+#[prefixes("group")]
+#[description = "Share a bedtime with a group of people, like your household"]
+#[default_command(show)]
+#[commands(show, status, create, invite, join, leave, bedtime, share)]
+pub struct Groups;
+
+/// Parse a group name from command arguments
+fn parse_name(args: &Args) -> Result<String, &'static str> {
+    let name = group::normalize_name(args.message());
+    if name.is_empty() {
+        Err("Give the group a name")
+    } else if name.chars().count() > MAX_NAME_LEN {
+        Err("That group name is too long")
+    } else {
+        Ok(name)
+    }
+}
+
+/// Get the name of a user's group and the group itself, if they're in one
+fn user_group(state: &State, user: UserId) -> Option<(&str, &Group)> {
+    let name = state.users.get(&user)?.group()?;
+    Some((name, state.groups.get(name)?))
+}
+
+#[command]
+#[description = "View your group, and who in it is still up"]
+async fn show(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let state = bot.read().await;
+
+    let (name, group) = match user_group(&state, msg.author.id) {
+        Some(group) => group,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "You're not in a group")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let bedtime = match group.bedtime {
+        Some(bedtime) => format!("{} ({})", bedtime.bedtime, bedtime.time_zone.name()),
+        None => "none".to_string(),
+    };
+
+    let members: Vec<String> = group
+        .members
+        .iter()
+        .map(|&member| {
+            let status = match state.users.get(&member) {
+                Some(user_info) if !user_info.shares_status() => "❔ unknown",
+                Some(user_info) if !user_info.tracks_presence() => "❔ unknown",
+                Some(user_info) if user_info.is_awake() => "👀 still up",
+                Some(_) => "💤 asleep",
+                None => "❔ unknown",
+            };
+            format!("{} {}", member.mention(), status)
+        })
+        .collect();
+
+    let resp = format!(
+        "**group**: {}\n**bedtime**: {}\n{}",
+        name,
+        bedtime,
+        members.join("\n")
+    );
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "See who in your group is asleep, who's still up, and how far \
+                 past bedtime they are"]
+async fn status(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let state = bot.read().await;
+
+    let (name, group) = match user_group(&state, msg.author.id) {
+        Some(group) => group,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "You're not in a group")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let now = Utc::now();
+    let mut asleep = Vec::new();
+    let mut up = Vec::new();
+    let mut unknown = Vec::new();
+    for &member in &group.members {
+        match state.users.get(&member) {
+            Some(user_info) if user_info.shares_status() && user_info.tracks_presence() => {
+                if !user_info.is_awake() {
+                    asleep.push(member.mention().to_string());
+                } else if let Some(late) = user_info.past_bedtime(now) {
+                    up.push(format!(
+                        "{} ({} past bedtime)",
+                        member.mention(),
+                        HumanDuration(late)
+                    ));
+                } else {
+                    up.push(member.mention().to_string());
+                }
+            }
+            _ => unknown.push(member.mention().to_string()),
+        }
+    }
+
+    let list = |members: Vec<String>| {
+        if members.is_empty() {
+            "nobody".to_string()
+        } else {
+            members.join("\n")
+        }
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!("{} 🏠", name))
+                    .field("💤 Asleep", list(asleep), true)
+                    .field("👀 Still up", list(up), true)
+                    .field("❔ Unknown", list(unknown), true)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Create a group and join it"]
+#[usage = "<name>"]
+async fn create(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let name = parse_name(&args)?;
+
+    let mut state = bot.write().await;
+
+    if state.groups.contains_key(&name) {
+        return Err(format!("The group '{}' already exists", name).into());
+    }
+
+    state
+        .join_group(bot.clone(), msg.author.id, name.clone())
+        .await;
+
+    state.save();
+
+    let resp = format!(
+        "Created the group '{}'. Invite others with `group invite @user`.",
+        name
+    );
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Invite someone to join your group. Only the group's owner can \
+                 do this."]
+#[usage = "@user"]
+async fn invite(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let user: UserId = args.single()?;
+
+    let mut state = bot.write().await;
+
+    let name = match state
+        .users
+        .get(&msg.author.id)
+        .and_then(|user_info| user_info.group())
+    {
+        Some(name) => name.to_string(),
+        None => return Err("You're not in a group".into()),
+    };
+
+    let group = state.groups.get_mut(&name).ok_or("You're not in a group")?;
+
+    if !group.is_managed_by(msg.author.id) {
+        return Err("Only the group's owner can invite people".into());
+    }
+
+    group.invited.insert(user);
+
+    state.save();
+
+    let resp = format!(
+        "{}, you're invited to the group '{}'. Join with `group join {}`.",
+        user.mention(),
+        name,
+        name
+    );
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Join a group you were invited to, sharing its bedtime"]
+#[usage = "<name>"]
+async fn join(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let name = parse_name(&args)?;
+
+    let mut state = bot.write().await;
+
+    match state.groups.get(&name) {
+        Some(group) if group.invited.contains(&msg.author.id) => {}
+        Some(_) => {
+            return Err(
+                format!("You need an invite from the owner of '{}' to join it", name).into(),
+            )
+        }
+        None => return Err(format!("There's no group named '{}'", name).into()),
+    }
+
+    state
+        .join_group(bot.clone(), msg.author.id, name.clone())
+        .await;
+
+    state.save();
+
+    let resp = format!("You joined the group '{}'", name);
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Leave your group, going back to your own bedtime"]
+async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let mut state = bot.write().await;
+
+    let left = state.leave_group(bot.clone(), msg.author.id).await;
+
+    state.save();
+
+    let resp = match left {
+        Some(name) => format!("You left the group '{}'", name),
+        None => "You're not in a group".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Set your group's shared bedtime, in your time zone. Only the \
+                 group's owner can do this."]
+#[usage = "10:30 PM"]
+async fn bedtime(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let tm: Time = args.parse()?;
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.get(&msg.author.id);
+
+    let name = match user_info.and_then(|user_info| user_info.group()) {
+        Some(name) => name.to_string(),
+        None => return Err("You're not in a group".into()),
+    };
+
+    let managed = state
+        .groups
+        .get(&name)
+        .is_some_and(|group| group.is_managed_by(msg.author.id));
+    if !managed {
+        return Err("Only the group's owner can set its bedtime".into());
+    }
+
+    let time_zone = match user_info.and_then(|user_info| user_info.time_zone()) {
+        Some(time_zone) => time_zone,
+        None => return Err("Set your time zone first".into()),
+    };
+
+    let group_bedtime = GroupBedtime {
+        time_zone,
+        bedtime: tm,
+    };
+
+    state
+        .set_group_bedtime(bot.clone(), &name, group_bedtime)
+        .await;
+
+    state.save();
+
+    let resp = format!("The group '{}' now goes to bed at {}", name, tm);
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Let your group see (`on`) or not see (`off`) whether you're \
+                 asleep"]
+#[usage = "on|off"]
+async fn share(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let share = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_share_status(share);
+
+    state.save();
+
+    let resp = if share {
+        "Your group can see whether you're asleep"
+    } else {
+        "Your group can't see whether you're asleep anymore"
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
@@ -0,0 +1,353 @@
+use crate::bot::Bot;
+use crate::guild_config::{ErrorCleanup, RolePing};
+use crate::time::{self, HumanDuration, Time};
+use crate::CMD_PREFIX;
+
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::*,
+    prelude::*,
+};
+
+#[group]
+#[prefixes("guild")]
+#[description = "Server settings, for server admins"]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[commands(
+    prefix,
+    default_tz,
+    default_bedtime,
+    announce_channel,
+    role_ping,
+    check_in_thread,
+    error_cleanup,
+    away_replies
+)]
+pub struct GuildSettings;
+
+/// Maximum length of a guild's command prefix
+const MAX_PREFIX_LEN: usize = 10;
+
+#[command]
+#[description = "Change the command prefix used in this server. Leave out the \
+                 prefix to go back to the default."]
+#[usage = "[!bed]"]
+async fn prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let prefix = args.message().trim();
+
+    if prefix.chars().count() > MAX_PREFIX_LEN {
+        return Err(format!(
+            "Prefixes can't be longer than {} characters",
+            MAX_PREFIX_LEN
+        )
+        .into());
+    }
+
+    if prefix.contains(char::is_whitespace) {
+        return Err("Prefixes can't contain spaces".into());
+    }
+
+    let prefix = if prefix.is_empty() || prefix == CMD_PREFIX {
+        None
+    } else {
+        Some(prefix.to_string())
+    };
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    let config = state.guilds.entry(guild).or_default();
+
+    config.prefix = prefix;
+
+    let resp = format!(
+        "This server's command prefix has been set to `{}`",
+        config.prefix()
+    );
+
+    state.save();
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("default-tz")]
+#[description = "Set the time zone suggested to members setting up in this server"]
+#[usage = "Europe/Berlin"]
+async fn default_tz(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let tz = time::parse_time_zone(args.message())?;
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    state.guilds.entry(guild).or_default().default_time_zone = Some(tz);
+
+    state.save();
+
+    let resp = format!(
+        "This server's default time zone has been set to {}",
+        tz.name()
+    );
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("default-bedtime")]
+#[description = "Set the bedtime suggested to members setting up in this server"]
+#[usage = "10:30 PM"]
+async fn default_bedtime(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let tm: Time = args.parse()?;
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    state.guilds.entry(guild).or_default().default_bedtime = Some(tm);
+
+    state.save();
+
+    let resp = format!("This server's suggested bedtime has been set to {}", tm);
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("announce-channel")]
+#[description = "Set the channel to wish members good night in at their \
+                 bedtime, if they opt in. Leave out the channel to stop."]
+#[usage = "[#channel]"]
+async fn announce_channel(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let channel: Option<ChannelId> = if args.is_empty() {
+        None
+    } else {
+        Some(args.parse()?)
+    };
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    if let Some(channel) = channel {
+        let in_guild = channel
+            .to_channel(ctx)
+            .await?
+            .guild()
+            .is_some_and(|channel| channel.guild_id == guild);
+        if !in_guild {
+            return Err("That channel isn't in this server".into());
+        }
+    }
+
+    let mut state = bot.write().await;
+
+    state.guilds.entry(guild).or_default().announce_channel = channel;
+
+    state.save();
+
+    let resp = match channel {
+        Some(channel) => format!(
+            "I'll wish opted-in members good night in {}. Members can opt in \
+             with the `announce on` command.",
+            channel.mention()
+        ),
+        None => "I'll stop wishing members good night".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("role-ping")]
+#[description = "Ping a role every night at a time in this server's default \
+                 time zone, reminding everyone with it to head to bed. Leave \
+                 out the channel to post here. Use `off` to stop."]
+#[usage = "@role 11:00 PM [#channel]"]
+async fn role_ping(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+
+    let ping = if text == "off" {
+        None
+    } else {
+        // Times look like `11:00 PM`, so they're two words
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (role, time, channel) = match words.as_slice() {
+            [role, time, period] => (role, format!("{} {}", time, period), None),
+            [role, time, period, channel] => (role, format!("{} {}", time, period), Some(channel)),
+            _ => return Err("Use something like `@night-owls 11:00 PM #general`".into()),
+        };
+        let channel = match channel {
+            Some(channel) => channel.parse()?,
+            None => msg.channel_id,
+        };
+        Some(RolePing {
+            role: role.parse()?,
+            channel,
+            time: time.parse()?,
+        })
+    };
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    let config = state.guilds.entry(guild).or_default();
+
+    config.role_ping = ping;
+
+    let resp = match ping {
+        Some(ping) if config.default_time_zone.is_some() => format!(
+            "I'll remind {} to head to bed at {} every night in {}",
+            ping.role.mention(),
+            ping.time,
+            ping.channel.mention()
+        ),
+        Some(_) => "I'll remind the role to head to bed every night, once this \
+                    server has a default time zone set with `guild default-tz`"
+            .to_string(),
+        None => "I'll stop pinging the role at bedtime".to_string(),
+    };
+
+    state.save();
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("check-in-thread")]
+#[description = "Open a sleep check-in thread in a channel every evening, in \
+                 this server's default time zone, and tally who checked in \
+                 the next morning. Leave out the channel to stop."]
+#[usage = "[#channel]"]
+async fn check_in_thread(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let channel: Option<ChannelId> = if args.is_empty() {
+        None
+    } else {
+        Some(args.parse()?)
+    };
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    let config = state.guilds.entry(guild).or_default();
+
+    config.check_in_channel = channel;
+
+    let resp = match channel {
+        Some(channel) if config.default_time_zone.is_some() => format!(
+            "I'll open a sleep check-in thread in {} every evening",
+            channel.mention()
+        ),
+        Some(_) => "I'll open a sleep check-in thread every evening, once this \
+                    server has a default time zone set with `guild default-tz`"
+            .to_string(),
+        None => "I'll stop opening sleep check-in threads".to_string(),
+    };
+
+    state.save();
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("error-cleanup")]
+#[description = "Delete my replies to failed commands after a while, so channels \
+                 don't fill up with errors. Add `with-command` to delete the failed \
+                 command too. Leave out the delay to stop."]
+#[usage = "[30s] [with-command]"]
+async fn error_cleanup(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let text = args.message().trim();
+    let cleanup = if text.is_empty() {
+        None
+    } else {
+        let (delay, delete_command) = match text.strip_suffix("with-command") {
+            Some(delay) => (delay, true),
+            None => (text, false),
+        };
+        let delay = time::parse_duration(delay)?;
+        Some(ErrorCleanup {
+            after_secs: delay.num_seconds() as u64,
+            delete_command,
+        })
+    };
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    state.guilds.entry(guild).or_default().error_cleanup = cleanup;
+
+    state.save();
+
+    let resp = match cleanup {
+        Some(cleanup) if cleanup.delete_command => format!(
+            "I'll delete failed commands and my replies to them after {}. \
+             I need the Manage Messages permission to delete commands.",
+            HumanDuration(chrono::Duration::seconds(cleanup.after_secs as i64))
+        ),
+        Some(cleanup) => format!(
+            "I'll delete my replies to failed commands after {}",
+            HumanDuration(chrono::Duration::seconds(cleanup.after_secs as i64))
+        ),
+        None => "I'll leave my replies to failed commands alone".to_string(),
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command("away-replies")]
+#[description = "Allow (`on`) or stop (`off`) me replying for members who opted \
+                 in when someone mentions them while they're asleep past their \
+                 bedtime"]
+#[usage = "on|off"]
+async fn away_replies(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let away_replies = match args.message().trim() {
+        "on" => true,
+        "off" => false,
+        _ => return Err("Use `on` or `off`".into()),
+    };
+
+    let guild = msg.guild_id.expect("Guild command outside guild");
+
+    let mut state = bot.write().await;
+
+    state.guilds.entry(guild).or_default().away_replies = away_replies;
+
+    state.save();
+
+    let resp = if away_replies {
+        "I'll let people know when a member they mention is asleep, if the member \
+         opted in with `away-reply on`"
+    } else {
+        "I won't reply for sleeping members in this server anymore"
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
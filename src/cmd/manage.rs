@@ -0,0 +1,110 @@
+use crate::bot::Bot;
+use crate::intensity::Intensity;
+use crate::manager;
+use crate::time::Time;
+
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::*,
+    prelude::*,
+};
+
+#[group]
+#[prefixes("manage")]
+#[description = "Change the settings of someone who asked you to manage them"]
+#[commands(bedtime, intensity)]
+pub struct Manage;
+
+/// Split command arguments into the mentioned user and the rest
+fn split_user(args: &Args) -> Result<(UserId, &str), String> {
+    let (user, rest) = args
+        .message()
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or("Mention who to change the settings of first")?;
+    let user = user
+        .parse()
+        .map_err(|_| "Mention who to change the settings of first")?;
+    Ok((user, rest.trim()))
+}
+
+#[command]
+#[description = "Set the bedtime of someone you manage"]
+#[usage = "@kid 9:00 PM"]
+async fn bedtime(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let (user, rest) = split_user(&args)?;
+    let tm: Time = rest.parse()?;
+
+    {
+        let mut state = bot.write().await;
+
+        manager::check(&state, msg.author.id, user)?;
+
+        state
+            .users
+            .entry(user)
+            .or_default()
+            .set_bedtime(bot.clone(), user, tm)
+            .await;
+
+        state.save();
+    }
+
+    let change = format!("set your bedtime to {}", tm);
+    manager::notify(&bot, user, msg.author.id, &change).await;
+
+    let resp = format!("{}'s bedtime has been set to {}", user.mention(), tm);
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Set how insistent the sleep reminders of someone you manage are"]
+#[usage = "@kid gentle|normal|drill-sergeant"]
+async fn intensity(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let (user, rest) = split_user(&args)?;
+    let intensity: Intensity = rest.parse()?;
+
+    {
+        let mut state = bot.write().await;
+
+        manager::check(&state, msg.author.id, user)?;
+
+        state
+            .users
+            .entry(user)
+            .or_default()
+            .set_intensity(bot.clone(), user, intensity)
+            .await;
+
+        state.save();
+    }
+
+    let change = format!("set your sleep reminder intensity to {}", intensity);
+    manager::notify(&bot, user, msg.author.id, &change).await;
+
+    let resp = format!(
+        "{}'s sleep reminder intensity has been set to {}",
+        user.mention(),
+        intensity
+    );
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.content(resp).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use crate::bot::Bot;
+use crate::profile::{self, MAX_NAME_LEN};
+
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::*,
+    prelude::*,
+};
+
+#[group]
+#[prefixes("profile")]
+#[description = "Save your schedule settings under a name, like `work` or \
+                 `vacation`, and switch between them at once"]
+#[default_command(list)]
+#[commands(list, save, use_profile, delete)]
+pub struct Profiles;
+
+/// Parse a profile name from command arguments
+fn parse_name(args: &Args) -> Result<String, &'static str> {
+    let name = profile::normalize_name(args.message());
+    if name.is_empty() {
+        Err("Give the profile a name")
+    } else if name.chars().count() > MAX_NAME_LEN {
+        Err("That profile name is too long")
+    } else {
+        Ok(name)
+    }
+}
+
+#[command]
+#[description = "View your saved profiles"]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let state = bot.read().await;
+
+    let names = state
+        .users
+        .get(&msg.author.id)
+        .map(|user_info| user_info.profile_names())
+        .unwrap_or_default();
+
+    let resp = if names.is_empty() {
+        "You don't have any saved profiles. Save one with `profile save`.".to_string()
+    } else {
+        format!("Your profiles: {}", names.join(", "))
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Save your time zone, bedtime, sleep goal, reminder settings, \
+                 skipped nights and routine as a profile, replacing any \
+                 profile with the same name"]
+#[usage = "work"]
+async fn save(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let name = parse_name(&args)?;
+
+    let mut state = bot.write().await;
+
+    let saved = state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .save_profile(name.clone());
+
+    if !saved {
+        return Err(format!(
+            "You can't have more than {} profiles. Delete one with `profile delete`.",
+            profile::MAX_PROFILES
+        )
+        .into());
+    }
+
+    state.save();
+
+    msg.channel_id
+        .say(&ctx.http, format!("Saved your settings as **{}**", name))
+        .await?;
+
+    Ok(())
+}
+
+#[command("use")]
+#[description = "Switch your settings to a saved profile"]
+#[usage = "vacation"]
+async fn use_profile(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let name = parse_name(&args)?;
+
+    let mut state = bot.write().await;
+
+    let used = match state.users.get_mut(&msg.author.id) {
+        Some(user_info) => {
+            user_info
+                .use_profile(bot.clone(), msg.author.id, &name)
+                .await
+        }
+        None => false,
+    };
+
+    if !used {
+        return Err("You don't have a profile with that name".into());
+    }
+
+    state.save();
+
+    msg.channel_id
+        .say(&ctx.http, format!("Switched to your **{}** settings", name))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Delete a saved profile"]
+#[usage = "work"]
+async fn delete(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let name = parse_name(&args)?;
+
+    let mut state = bot.write().await;
+
+    let deleted = state
+        .users
+        .get_mut(&msg.author.id)
+        .is_some_and(|user_info| user_info.delete_profile(&name));
+
+    if !deleted {
+        return Err("You don't have a profile with that name".into());
+    }
+
+    state.save();
+
+    msg.channel_id
+        .say(&ctx.http, format!("Deleted your **{}** profile", name))
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use crate::bot::Bot;
+use crate::routine;
+
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::*,
+    prelude::*,
+};
+
+#[group]
+#[prefixes("routine")]
+#[description = "Set up a wind-down checklist to get through before bed. \
+                 Reminders keep coming until everything is checked off."]
+#[default_command(show)]
+#[commands(show, add, remove, clear)]
+pub struct Routine;
+
+#[command]
+#[description = "View your wind-down routine"]
+async fn show(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let state = bot.read().await;
+
+    let items = state
+        .users
+        .get(&msg.author.id)
+        .map(|user_info| user_info.routine())
+        .unwrap_or_default();
+
+    let resp = if items.is_empty() {
+        "You don't have a wind-down routine. Add to it with `routine add`.".to_string()
+    } else {
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| format!("{}. {}", i + 1, item))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    msg.channel_id.say(&ctx.http, resp).await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Add an item to your wind-down routine"]
+#[usage = "\"brush teeth\""]
+async fn add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let item = args.message().trim().trim_matches('"').trim();
+
+    if item.is_empty() {
+        return Err("Say what to add to your routine".into());
+    }
+
+    if item.chars().count() > routine::MAX_ITEM_LEN {
+        return Err(format!(
+            "Routine items can't be longer than {} characters",
+            routine::MAX_ITEM_LEN
+        )
+        .into());
+    }
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+
+    if user_info.routine().len() >= routine::MAX_ITEMS {
+        return Err(format!("Routines can't have more than {} items", routine::MAX_ITEMS).into());
+    }
+
+    let mut items = user_info.routine().to_vec();
+    items.push(item.to_string());
+    user_info
+        .set_routine(bot.clone(), msg.author.id, items)
+        .await;
+
+    state.save();
+
+    msg.channel_id
+        .say(&ctx.http, format!("Added **{}** to your routine", item))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Remove an item from your wind-down routine by its number"]
+#[usage = "<number>"]
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let number = args.single::<usize>()?;
+
+    let mut state = bot.write().await;
+
+    let user_info = state.users.entry(msg.author.id).or_default();
+
+    let mut items = user_info.routine().to_vec();
+
+    if number == 0 || number > items.len() {
+        return Err("You don't have a routine item with that number".into());
+    }
+
+    let item = items.remove(number - 1);
+    user_info
+        .set_routine(bot.clone(), msg.author.id, items)
+        .await;
+
+    state.save();
+
+    msg.channel_id
+        .say(&ctx.http, format!("Removed **{}** from your routine", item))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Remove every item from your wind-down routine"]
+async fn clear(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot = Bot::of(ctx).await?;
+    let mut state = bot.write().await;
+
+    state
+        .users
+        .entry(msg.author.id)
+        .or_default()
+        .set_routine(bot.clone(), msg.author.id, Vec::new())
+        .await;
+
+    state.save();
+
+    msg.channel_id
+        .say(&ctx.http, "Cleared your wind-down routine")
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::env;
+
+/// Default number of minutes past bedtime after which sleep reminders give up
+/// for the night
+const DEFAULT_NAG_TIMEOUT_MINUTES: i64 = 120;
+
+/// Longest `NAG_TIMEOUT_MINUTES` can be, so a night's reminders end before the
+/// next night's begin
+const MAX_NAG_TIMEOUT_MINUTES: i64 = 24 * 60;
+
+/// Number of months without interaction after which users are warned and
+/// then forgotten, if not set with the `STALE_USER_MONTHS` environment
+/// variable
+const DEFAULT_STALE_MONTHS: i64 = 6;
+
+/// Settings the bot's operator configures with environment variables, read
+/// once at startup
+pub struct Config {
+    /// How long sleep reminders can go on before giving up for the night, set
+    /// in minutes with `NAG_TIMEOUT_MINUTES`
+    pub nag_timeout: chrono::Duration,
+
+    /// How long users can go without interacting with the bot before they're
+    /// warned and then forgotten, set in months with `STALE_USER_MONTHS`
+    pub stale_after: chrono::Duration,
+
+    /// Public URL of the dashboard, set with `DASHBOARD_URL`, which links
+    /// point to
+    pub dashboard_url: Option<String>,
+}
+
+impl Config {
+    /// Read the configuration from environment variables, using defaults for
+    /// those that aren't set or aren't valid
+    pub fn from_env() -> Self {
+        let stale_months = env::var("STALE_USER_MONTHS")
+            .ok()
+            .and_then(|months| months.parse().ok())
+            .unwrap_or(DEFAULT_STALE_MONTHS);
+        Config {
+            nag_timeout: chrono::Duration::minutes(nag_timeout_minutes()),
+            stale_after: chrono::Duration::days(stale_months * 30),
+            dashboard_url: env::var("DASHBOARD_URL").ok(),
+        }
+    }
+}
+
+/// Get the number of minutes sleep reminders can go on for, clamped to a day
+fn nag_timeout_minutes() -> i64 {
+    let minutes = match env::var("NAG_TIMEOUT_MINUTES") {
+        Ok(minutes) => minutes,
+        Err(_) => return DEFAULT_NAG_TIMEOUT_MINUTES,
+    };
+    match minutes.parse::<i64>() {
+        Ok(minutes) if (1..=MAX_NAG_TIMEOUT_MINUTES).contains(&minutes) => minutes,
+        Ok(minutes) => {
+            let clamped = minutes.clamp(1, MAX_NAG_TIMEOUT_MINUTES);
+            println!(
+                "NAG_TIMEOUT_MINUTES must be from 1 to {}, using {}",
+                MAX_NAG_TIMEOUT_MINUTES, clamped
+            );
+            clamped
+        }
+        Err(_) => {
+            println!(
+                "Invalid NAG_TIMEOUT_MINUTES '{}', using the default of {}",
+                minutes, DEFAULT_NAG_TIMEOUT_MINUTES
+            );
+            DEFAULT_NAG_TIMEOUT_MINUTES
+        }
+    }
+}
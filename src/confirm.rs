@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use serenity::{
+    model::interactions::message_component::ButtonStyle, model::prelude::*, prelude::*, Result,
+};
+
+/// How long to wait for the user to confirm
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Custom ID of the button confirming the action
+const YES_ID: &str = "confirm_yes";
+
+/// Custom ID of the button cancelling the action
+const NO_ID: &str = "confirm_no";
+
+/// Ask a user in a channel to confirm a destructive action with yes/no buttons.
+/// Returns whether they pressed yes in time.
+pub async fn confirm(
+    ctx: &Context,
+    channel: ChannelId,
+    user: UserId,
+    question: &str,
+) -> Result<bool> {
+    let mut prompt = channel
+        .send_message(&ctx.http, |m| {
+            m.content(question).components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| b.custom_id(YES_ID).label("Yes").style(ButtonStyle::Danger))
+                        .create_button(|b| {
+                            b.custom_id(NO_ID).label("No").style(ButtonStyle::Secondary)
+                        })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = prompt
+        .await_component_interaction(ctx)
+        .author_id(user)
+        .timeout(TIMEOUT)
+        .await;
+
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => {
+            prompt
+                .edit(ctx, |m| {
+                    m.content(format!("{} **Timed out**", question))
+                        .components(|c| c)
+                })
+                .await?;
+            return Ok(false);
+        }
+    };
+
+    let confirmed = interaction.data.custom_id == YES_ID;
+    let answer = if confirmed { "Yes" } else { "No" };
+
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| {
+                    d.content(format!("{} **{}**", question, answer))
+                        .components(|c| c)
+                })
+        })
+        .await?;
+
+    Ok(confirmed)
+}
@@ -0,0 +1,483 @@
+use crate::admin_api;
+use crate::audit::{self, AuditEvent};
+use crate::bot::Bot;
+use crate::chart;
+use crate::link;
+use crate::time::{self, Time};
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use chrono_tz::Tz;
+use hyper::header::{CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Deserialize;
+use serenity::model::id::UserId;
+
+/// How long a dashboard login lasts
+const SESSION_DAYS: i64 = 7;
+
+/// How long a user has to finish logging in with Discord
+const LOGIN_MINUTES: i64 = 10;
+
+/// Number of days of sleep history the dashboard chart covers
+const CHART_DAYS: i64 = 30;
+
+/// Name of the cookie holding the session token
+const SESSION_COOKIE: &str = "session";
+
+/// Discord's OAuth2 authorization page
+const AUTHORIZE_URL: &str = "https://discord.com/api/oauth2/authorize";
+
+/// Discord's OAuth2 token endpoint
+const TOKEN_URL: &str = "https://discord.com/api/oauth2/token";
+
+/// Discord endpoint for the user an access token belongs to
+const CURRENT_USER_URL: &str = "https://discord.com/api/users/@me";
+
+/// Settings for logging in with Discord, from environment variables
+#[derive(Clone)]
+struct OAuth {
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+}
+
+lazy_static! {
+    /// Logged-in users by session token, with when the session expires
+    static ref SESSIONS: Mutex<HashMap<String, (UserId, DateTime<Utc>)>> =
+        Mutex::new(HashMap::new());
+
+    /// OAuth2 `state` values of logins in progress, with when they expire
+    static ref LOGINS: Mutex<HashMap<String, DateTime<Utc>>> = Mutex::new(HashMap::new());
+}
+
+/// Make a random token for sessions and logins
+fn random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Escape text for including in HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Wrap a page body in the dashboard's HTML skeleton
+fn page(body: &str) -> Response<Body> {
+    let html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <title>Bedtime dashboard</title></head><body>{}</body></html>",
+        body
+    );
+    Response::builder()
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(html))
+        .expect("Invalid response")
+}
+
+/// Redirect to another page
+fn redirect(to: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header(LOCATION, to)
+        .body(Body::empty())
+        .expect("Invalid response")
+}
+
+/// Respond with an error status and message
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(message.to_string()))
+        .expect("Invalid response")
+}
+
+/// Get the session token from a request's cookies, if it has one
+fn session_token(req: &Request<Body>) -> Option<String> {
+    let cookies = req.headers().get(COOKIE)?.to_str().ok()?;
+    cookies.split(';').find_map(|cookie| {
+        let (name, value) = cookie.trim().split_once('=')?;
+        (name == SESSION_COOKIE).then(|| value.to_string())
+    })
+}
+
+/// Get the user logged in to a request's session, if any
+fn session_user(req: &Request<Body>) -> Option<UserId> {
+    let token = session_token(req)?;
+    let mut sessions = SESSIONS.lock().expect("Sessions lock poisoned");
+    match sessions.get(&token) {
+        Some(&(user, expires)) if expires > Utc::now() => Some(user),
+        Some(_) => {
+            sessions.remove(&token);
+            None
+        }
+        None => None,
+    }
+}
+
+/// Query string of Discord's OAuth2 redirect back to the dashboard
+#[derive(Deserialize)]
+struct Callback {
+    code: String,
+    state: String,
+}
+
+/// Response from Discord's OAuth2 token endpoint
+#[derive(Deserialize)]
+struct Token {
+    access_token: String,
+}
+
+/// The parts of a Discord user the dashboard needs
+#[derive(Deserialize)]
+struct CurrentUser {
+    id: String,
+}
+
+/// Trade an OAuth2 authorization code for the ID of the user who logged in
+async fn exchange_code(oauth: &OAuth, code: &str) -> reqwest::Result<Option<UserId>> {
+    let client = reqwest::Client::new();
+    let token: Token = client
+        .post(TOKEN_URL)
+        .form(&[
+            ("client_id", oauth.client_id.as_str()),
+            ("client_secret", oauth.client_secret.as_str()),
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", oauth.redirect_uri.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let user: CurrentUser = client
+        .get(CURRENT_USER_URL)
+        .bearer_auth(token.access_token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(user.id.parse().ok().map(UserId))
+}
+
+/// Send the user to Discord to log in
+fn login(oauth: &OAuth) -> Response<Body> {
+    let state = random_token();
+    let mut logins = LOGINS.lock().expect("Logins lock poisoned");
+    let now = Utc::now();
+    logins.retain(|_, &mut expires| expires > now);
+    logins.insert(state.clone(), now + Duration::minutes(LOGIN_MINUTES));
+    let query = serde_urlencoded::to_string([
+        ("client_id", oauth.client_id.as_str()),
+        ("redirect_uri", oauth.redirect_uri.as_str()),
+        ("response_type", "code"),
+        ("scope", "identify"),
+        ("state", state.as_str()),
+    ])
+    .expect("Failed to encode query");
+    redirect(&format!("{}?{}", AUTHORIZE_URL, query))
+}
+
+/// Finish logging in after Discord sends the user back, starting a session
+async fn callback(oauth: &OAuth, req: &Request<Body>) -> Response<Body> {
+    let callback: Callback = match serde_urlencoded::from_str(req.uri().query().unwrap_or("")) {
+        Ok(callback) => callback,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "Missing login code"),
+    };
+
+    let started = LOGINS
+        .lock()
+        .expect("Logins lock poisoned")
+        .remove(&callback.state);
+    if started.is_none_or(|expires| expires <= Utc::now()) {
+        return error(StatusCode::BAD_REQUEST, "Login expired, try again");
+    }
+
+    let user = match exchange_code(oauth, &callback.code).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return error(StatusCode::BAD_GATEWAY, "Discord sent an invalid user"),
+        Err(err) => {
+            println!("Error logging in to dashboard: {}", err);
+            return error(StatusCode::BAD_GATEWAY, "Couldn't log in with Discord");
+        }
+    };
+
+    start_session(user)
+}
+
+/// Log a user in to the dashboard with a new session cookie
+fn start_session(user: UserId) -> Response<Body> {
+    let token = random_token();
+    SESSIONS.lock().expect("Sessions lock poisoned").insert(
+        token.clone(),
+        (user, Utc::now() + Duration::days(SESSION_DAYS)),
+    );
+
+    let mut resp = redirect("/");
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        SESSION_COOKIE,
+        token,
+        Duration::days(SESSION_DAYS).num_seconds()
+    );
+    resp.headers_mut()
+        .insert(SET_COOKIE, cookie.parse().expect("Invalid cookie"));
+    resp
+}
+
+/// Query string of a link token login
+#[derive(Deserialize)]
+struct LinkLogin {
+    token: String,
+}
+
+/// Start a session from a link token made with the `link` command, as an
+/// alternative to logging in with Discord
+async fn link_login(bot: &Bot, req: &Request<Body>) -> Response<Body> {
+    let login: LinkLogin = match serde_urlencoded::from_str(req.uri().query().unwrap_or("")) {
+        Ok(login) => login,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "Missing link token"),
+    };
+
+    let user = {
+        let state = bot.read().await;
+        link::verify(&state, &login.token)
+    };
+
+    match user {
+        Some(user) => start_session(user),
+        None => error(StatusCode::UNAUTHORIZED, "Invalid or expired link token"),
+    }
+}
+
+/// Serve a user's settings as JSON for the link made with `export url`, which
+/// carries an export token
+async fn export_json(bot: &Bot, req: &Request<Body>) -> Response<Body> {
+    let login: LinkLogin = match serde_urlencoded::from_str(req.uri().query().unwrap_or("")) {
+        Ok(login) => login,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "Missing export token"),
+    };
+
+    let state = bot.read().await;
+    let user = link::verify_export(&state, &login.token);
+    let user_info = match user.and_then(|user| state.users.get(&user)) {
+        Some(user_info) => user_info,
+        None => return error(StatusCode::UNAUTHORIZED, "Invalid or expired export token"),
+    };
+
+    match serde_json::to_string_pretty(&user_info.current_profile()) {
+        Ok(json) => Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(json))
+            .expect("Invalid response"),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+    }
+}
+
+/// Show a user their settings and stats, with forms to change them
+async fn home(bot: &Bot, user: UserId) -> Response<Body> {
+    let state = bot.read().await;
+    let user_info = state.users.get(&user);
+
+    let info = user_info.map_or_else(
+        || "You haven't set anything up yet.".to_string(),
+        ToString::to_string,
+    );
+    let tz = user_info
+        .and_then(|user_info| user_info.time_zone())
+        .map_or("", |tz| tz.name());
+
+    page(&format!(
+        "<h1>Bedtime dashboard</h1>\
+         <pre>{}</pre>\
+         <img src=\"/chart.png\" alt=\"Sleep chart\">\
+         <h2>Settings</h2>\
+         <form method=\"post\" action=\"/settings\">\
+         <label>Bedtime <input type=\"time\" name=\"bedtime\"></label> \
+         <label>Time zone <input name=\"time_zone\" value=\"{}\" \
+         placeholder=\"America/Chicago\"></label> \
+         <button>Save</button></form>\
+         <form method=\"post\" action=\"/skip\"><button>Skip tonight</button></form>\
+         <form method=\"post\" action=\"/logout\"><button>Log out</button></form>",
+        escape(&info),
+        escape(tz)
+    ))
+}
+
+/// Form for changing settings. Empty fields are left alone.
+#[derive(Deserialize)]
+struct SettingsForm {
+    bedtime: String,
+    time_zone: String,
+}
+
+/// Change a user's settings from the dashboard form
+async fn settings(bot: &Bot, user: UserId, req: Request<Body>) -> Response<Body> {
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "Couldn't read form"),
+    };
+    let form: SettingsForm = match serde_urlencoded::from_bytes(&body) {
+        Ok(form) => form,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "Invalid form"),
+    };
+
+    let bedtime = match form.bedtime.trim() {
+        "" => None,
+        bedtime => match NaiveTime::parse_from_str(bedtime, "%H:%M") {
+            Ok(bedtime) => Some(Time(bedtime)),
+            Err(_) => return error(StatusCode::BAD_REQUEST, "Invalid bedtime"),
+        },
+    };
+    let time_zone = match form.time_zone.trim() {
+        "" => None,
+        time_zone => match time_zone.parse::<Tz>() {
+            Ok(time_zone) => Some(time_zone),
+            Err(_) => return error(StatusCode::BAD_REQUEST, "Unknown time zone"),
+        },
+    };
+
+    let mut state = bot.write().await;
+    let user_info = state.users.entry(user).or_default();
+    if let Some(time_zone) = time_zone.filter(|&tz| user_info.time_zone() != Some(tz)) {
+        user_info.set_time_zone(bot.clone(), user, time_zone).await;
+    }
+    if let Some(bedtime) = bedtime {
+        user_info.set_bedtime(bot.clone(), user, bedtime).await;
+    }
+    state.save();
+    audit::record(AuditEvent::SettingsChanged {
+        user,
+        via: "dashboard".to_string(),
+    });
+
+    redirect("/")
+}
+
+/// Skip tonight's sleep reminders for a user
+async fn skip(bot: &Bot, user: UserId) -> Response<Body> {
+    let mut state = bot.write().await;
+    if let Some(user_info) = state.users.get_mut(&user) {
+        user_info.skip_tonight(bot.clone(), user).await;
+        state.save();
+    }
+    redirect("/")
+}
+
+/// Render a user's sleep chart
+async fn chart_png(bot: &Bot, user: UserId) -> Response<Body> {
+    let png = {
+        let state = bot.read().await;
+        let user_info = match state.users.get(&user) {
+            Some(user_info) => user_info,
+            None => return error(StatusCode::NOT_FOUND, "No sleep history"),
+        };
+        let tz = user_info.time_zone().unwrap_or(Tz::UTC);
+        let today = time::night_of(&Utc::now().with_timezone(&tz));
+        chart::render(&user_info.nights(), CHART_DAYS, today)
+    };
+    match png {
+        Ok(png) => Response::builder()
+            .header(CONTENT_TYPE, "image/png")
+            .body(Body::from(png))
+            .expect("Invalid response"),
+        Err(err) => {
+            println!("Error rendering dashboard chart: {}", err);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "Couldn't render chart")
+        }
+    }
+}
+
+/// End a user's session
+fn logout(req: &Request<Body>) -> Response<Body> {
+    if let Some(token) = session_token(req) {
+        SESSIONS
+            .lock()
+            .expect("Sessions lock poisoned")
+            .remove(&token);
+    }
+    redirect("/")
+}
+
+/// Route a dashboard request
+async fn handle(bot: Bot, oauth: OAuth, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if req.uri().path().starts_with("/admin/") {
+        return Ok(admin_api::handle(&bot, &req).await);
+    }
+    let user = session_user(&req);
+    let resp = match (req.method(), req.uri().path(), user) {
+        (&Method::GET, "/login", _) => login(&oauth),
+        (&Method::GET, "/callback", _) => callback(&oauth, &req).await,
+        (&Method::GET, "/link", _) => link_login(&bot, &req).await,
+        (&Method::GET, "/export.json", _) => export_json(&bot, &req).await,
+        (&Method::POST, "/logout", _) => logout(&req),
+        (&Method::GET, "/", None) => page(
+            "<h1>Bedtime dashboard</h1>\
+             <p><a href=\"/login\">Log in with Discord</a></p>",
+        ),
+        (_, _, None) => redirect("/"),
+        (&Method::GET, "/", Some(user)) => home(&bot, user).await,
+        (&Method::GET, "/chart.png", Some(user)) => chart_png(&bot, user).await,
+        (&Method::POST, "/settings", Some(user)) => settings(&bot, user, req).await,
+        (&Method::POST, "/skip", Some(user)) => skip(&bot, user).await,
+        _ => error(StatusCode::NOT_FOUND, "Not found"),
+    };
+    Ok(resp)
+}
+
+/// Serve the web dashboard and the admin API, if the `DASHBOARD_ADDR`,
+/// `DASHBOARD_URL`, `OAUTH_CLIENT_ID`, and `OAUTH_CLIENT_SECRET` environment
+/// variables are set
+pub fn spawn(bot: Bot) {
+    let vars = (
+        env::var("DASHBOARD_ADDR"),
+        env::var("DASHBOARD_URL"),
+        env::var("OAUTH_CLIENT_ID"),
+        env::var("OAUTH_CLIENT_SECRET"),
+    );
+    let (addr, url, client_id, client_secret) = match vars {
+        (Ok(addr), Ok(url), Ok(client_id), Ok(client_secret)) => {
+            (addr, url, client_id, client_secret)
+        }
+        _ => return,
+    };
+    let addr: SocketAddr = addr.parse().expect("Invalid DASHBOARD_ADDR");
+    let oauth = OAuth {
+        client_id,
+        client_secret,
+        redirect_uri: format!("{}/callback", url.trim_end_matches('/')),
+    };
+
+    let make_service = make_service_fn(move |_| {
+        let bot = bot.clone();
+        let oauth = oauth.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle(bot.clone(), oauth.clone(), req)
+            }))
+        }
+    });
+
+    println!("Serving dashboard on {}", addr);
+    tokio::spawn(async move {
+        if let Err(err) = Server::bind(&addr).serve(make_service).await {
+            println!("Error serving dashboard: {}", err);
+        }
+    });
+}
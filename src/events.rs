@@ -0,0 +1,149 @@
+use crate::audit::{self, AuditEvent};
+use crate::bot::Bot;
+use crate::morning;
+use crate::presence::Status;
+use crate::state::State;
+
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serenity::model::id::UserId;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Something that happened outside of a command that changes a user's state.
+/// The gateway handler and scheduled tasks send these instead of locking the
+/// state themselves, and the core state task applies them in order.
+#[derive(Debug)]
+pub enum Event {
+    /// A user's online status changed
+    PresenceChanged { user: UserId, status: Status },
+
+    /// A user sent a message, reacted, or started typing
+    Active(UserId),
+
+    /// Whether a user counts as awake should be checked again, since their
+    /// last activity isn't recent anymore
+    PresenceRecheck(UserId),
+
+    /// A user's sleep reminders couldn't be delivered
+    Unreachable(UserId),
+
+    /// A sleep reminder reached a user, so they shouldn't be forgotten as
+    /// stale
+    NagDelivered(UserId),
+
+    /// A user's one-off reminders due at or before a time were sent
+    RemindersSent { user: UserId, until: DateTime<Utc> },
+}
+
+lazy_static! {
+    /// Channel of events waiting to be applied by the core state task
+    static ref BUS: (UnboundedSender<Event>, Mutex<Option<UnboundedReceiver<Event>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, Mutex::new(Some(rx)))
+    };
+}
+
+/// Queue an event for the core state task
+pub fn send(event: Event) {
+    // Sending only fails once the core state task is gone, when the bot is
+    // shutting down anyway
+    let _ = BUS.0.send(event);
+}
+
+/// Apply an event to the state. Returns whether the state changed in a way
+/// that should be saved.
+fn apply(bot: &Bot, state: &mut State, event: Event) -> bool {
+    match event {
+        Event::PresenceChanged { user, status } => match state.users.get_mut(&user) {
+            Some(user_info) if user_info.tracks_presence() => {
+                let before = user_info.last_transition();
+                let awake = user_info.presence_changed(status);
+                // Save new transitions, so the log survives restarts
+                let transitioned = user_info.last_transition() != before;
+                if !awake {
+                    if transitioned && user_info.is_nagging() {
+                        audit::record(AuditEvent::SleepDetected { user });
+                    }
+                    return transitioned;
+                }
+                // Coming online is the first sign of the user being up in the
+                // morning
+                match user_info.take_morning_summary(Utc::now()) {
+                    Some(summary) => {
+                        let bot = bot.clone();
+                        tokio::spawn(async move { morning::send(&bot, user, summary).await });
+                        true
+                    }
+                    None => transitioned,
+                }
+            }
+            _ => false,
+        },
+        Event::Active(user) => {
+            if let Some(user_info) = state.users.get_mut(&user) {
+                if user_info.tracks_presence() {
+                    user_info.active(user);
+                }
+            }
+            // Activity is too frequent to save for, and when the user was last
+            // around only needs to be roughly right, so it's saved with the
+            // next change
+            false
+        }
+        Event::PresenceRecheck(user) => {
+            if let Some(user_info) = state.users.get_mut(&user) {
+                if user_info.tracks_presence() {
+                    user_info.update_awake();
+                }
+            }
+            false
+        }
+        Event::Unreachable(user) => match state.users.get_mut(&user) {
+            Some(user_info) => {
+                println!("User '{}' is unreachable, marking them dormant", user);
+                user_info.set_dormant();
+                true
+            }
+            None => false,
+        },
+        Event::NagDelivered(user) => match state.users.get_mut(&user) {
+            Some(user_info) => {
+                user_info.noticed();
+                true
+            }
+            None => false,
+        },
+        Event::RemindersSent { user, until } => match state.users.get_mut(&user) {
+            Some(user_info) => {
+                user_info.forget_reminders(until);
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+/// Spawn the core state task, applying events as they come in
+pub fn spawn(bot: Bot) {
+    let mut rx = BUS
+        .1
+        .lock()
+        .expect("Event bus lock poisoned")
+        .take()
+        .expect("Core state task already spawned");
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            let mut state = bot.write().await;
+            let mut changed = apply(&bot, &mut state, event);
+            // Apply any other events that queued up while waiting for the lock
+            while let Ok(event) = rx.try_recv() {
+                changed |= apply(&bot, &mut state, event);
+            }
+            if changed {
+                state.save();
+            }
+        }
+    });
+}
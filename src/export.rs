@@ -0,0 +1,96 @@
+use crate::history::Night;
+use crate::presence_log::Transition;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+/// Header row of sleep history CSV exports
+const CSV_HEADER: &str = "date,bedtime,fell asleep,woke up,reminders,skipped,gave up,rating,note";
+
+/// Header row of awake and asleep transition CSV exports
+const PRESENCE_CSV_HEADER: &str = "time,state";
+
+/// Format a time for a CSV field, in a user's time zone
+fn csv_time(time: Option<DateTime<Utc>>, tz: Tz) -> String {
+    match time {
+        Some(time) => time.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string(),
+        None => String::new(),
+    }
+}
+
+/// Quote a CSV field if it contains characters that need it
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Format nights of a user's sleep history as CSV, with times in their time
+/// zone
+pub fn csv(nights: &[Night], tz: Tz) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push('\n');
+    for night in nights {
+        let rating = night.rating.map(|r| r.to_string()).unwrap_or_default();
+        let note = csv_escape(night.note.as_deref().unwrap_or_default());
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            night.date,
+            night.bedtime.0.format("%H:%M"),
+            csv_time(night.fell_asleep, tz),
+            csv_time(night.woke, tz),
+            night.nags,
+            night.skipped,
+            night.gave_up,
+            rating,
+            note
+        ));
+    }
+    out
+}
+
+/// Format a user's transitions between awake and asleep as CSV, with times in
+/// their time zone
+pub fn presence_csv(transitions: &[Transition], tz: Tz) -> String {
+    let mut out = String::from(PRESENCE_CSV_HEADER);
+    out.push('\n');
+    for transition in transitions {
+        let state = if transition.awake { "awake" } else { "asleep" };
+        out.push_str(&format!(
+            "{},{}\n",
+            csv_time(Some(transition.at), tz),
+            state
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escape_plain() {
+        assert_eq!(csv_escape(""), "");
+        assert_eq!(csv_escape("slept well"), "slept well");
+    }
+
+    #[test]
+    fn csv_escape_comma() {
+        assert_eq!(csv_escape("tired, but ok"), "\"tired, but ok\"");
+    }
+
+    #[test]
+    fn csv_escape_quotes() {
+        assert_eq!(csv_escape("a \"long\" night"), "\"a \"\"long\"\" night\"");
+        assert_eq!(csv_escape("\""), "\"\"\"\"");
+    }
+
+    #[test]
+    fn csv_escape_newlines() {
+        assert_eq!(csv_escape("woke up\ntwice"), "\"woke up\ntwice\"");
+        assert_eq!(csv_escape("woke up\r\ntwice"), "\"woke up\r\ntwice\"");
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// Experimental part of the bot that operators can turn on or off
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    Escalation,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 1] = [Feature::Escalation];
+
+    /// Get the name the feature is configured by
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Escalation => "escalation",
+        }
+    }
+
+    /// Get a description of what the feature does
+    pub fn description(self) -> &'static str {
+        match self {
+            Feature::Escalation => "Sleep reminders get more insistent the longer users stay up",
+        }
+    }
+
+    /// Whether the feature is on unless configured otherwise
+    fn default_on(self) -> bool {
+        match self {
+            Feature::Escalation => true,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Feature::ALL
+            .iter()
+            .copied()
+            .find(|feature| feature.name() == s)
+            .ok_or_else(|| format!("There's no feature called '{}'", s))
+    }
+}
+
+/// Get the features named in a comma-separated environment variable
+fn from_env(var: &str) -> Vec<Feature> {
+    let names = env::var(var).unwrap_or_default();
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| match name.parse() {
+            Ok(feature) => Some(feature),
+            Err(err) => {
+                println!("Ignoring {}: {}", var, err);
+                None
+            }
+        })
+        .collect()
+}
+
+lazy_static! {
+    /// Features that are on. These start out as the defaults, adjusted by the
+    /// `ENABLE_FEATURES` and `DISABLE_FEATURES` environment variables, and the
+    /// bot's owner can change them while it runs.
+    static ref ENABLED: RwLock<HashSet<Feature>> = {
+        let mut enabled: HashSet<Feature> = Feature::ALL
+            .iter()
+            .copied()
+            .filter(|feature| feature.default_on())
+            .collect();
+        enabled.extend(from_env("ENABLE_FEATURES"));
+        for feature in from_env("DISABLE_FEATURES") {
+            enabled.remove(&feature);
+        }
+        RwLock::new(enabled)
+    };
+}
+
+/// Whether a feature is on
+pub fn is_enabled(feature: Feature) -> bool {
+    ENABLED
+        .read()
+        .expect("Features lock poisoned")
+        .contains(&feature)
+}
+
+/// Turn a feature on or off until the bot restarts
+pub fn set_enabled(feature: Feature, enabled: bool) {
+    let mut features = ENABLED.write().expect("Features lock poisoned");
+    if enabled {
+        features.insert(feature);
+    } else {
+        features.remove(&feature);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::time::Time;
+
+use std::collections::BTreeSet;
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+
+/// Shared bedtime of a group, in the time zone of whoever set it
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct GroupBedtime {
+    pub time_zone: Tz,
+    pub bedtime: Time,
+}
+
+/// Set of users, like a household, who share a bedtime
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Group {
+    /// Users in the group
+    pub members: BTreeSet<UserId>,
+
+    /// User who manages the group. Groups created before groups had owners
+    /// have none, and are managed by all of their members.
+    pub owner: Option<UserId>,
+
+    /// Users the owner invited to join the group
+    pub invited: BTreeSet<UserId>,
+
+    /// The group's shared bedtime, if one is set
+    pub bedtime: Option<GroupBedtime>,
+}
+
+impl Group {
+    /// Whether a user may invite people to the group and set its bedtime
+    pub fn is_managed_by(&self, id: UserId) -> bool {
+        match self.owner {
+            Some(owner) => owner == id,
+            None => self.members.contains(&id),
+        }
+    }
+}
+
+/// Normalize a group name, so lookups ignore case and surrounding whitespace
+pub fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
@@ -0,0 +1,84 @@
+use crate::challenge::Challenge;
+use crate::time::Time;
+use crate::CMD_PREFIX;
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, MessageId, RoleId};
+
+/// Collective bedtime reminder pinging a role, independent of members' own
+/// bedtimes
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct RolePing {
+    /// Role to ping
+    pub role: RoleId,
+
+    /// Channel to post the reminder in
+    pub channel: ChannelId,
+
+    /// Time of day to post the reminder, in the guild's default time zone
+    pub time: Time,
+}
+
+/// Thread opened for members to check in when they head to bed
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct CheckInThread {
+    /// The thread
+    pub thread: ChannelId,
+
+    /// Message the thread was started from, which members can react to
+    pub starter: MessageId,
+}
+
+/// How to clean up after commands that fail in the guild, so its channels
+/// don't fill up with error replies
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct ErrorCleanup {
+    /// Seconds to wait before deleting the error reply
+    pub after_secs: u64,
+
+    /// Whether to delete the message with the failed command too
+    pub delete_command: bool,
+}
+
+/// Per-guild settings, configured by guild admins
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildConfig {
+    /// Command prefix used in the guild instead of the default one
+    pub prefix: Option<String>,
+
+    /// Time zone suggested to new users setting up in the guild
+    pub default_time_zone: Option<Tz>,
+
+    /// Bedtime suggested to new users setting up in the guild
+    pub default_bedtime: Option<Time>,
+
+    /// Channel to wish opted-in members good night in at their bedtime
+    pub announce_channel: Option<ChannelId>,
+
+    /// Collective bedtime reminder for a role, if any
+    pub role_ping: Option<RolePing>,
+
+    /// Channel to open a nightly sleep check-in thread in, if any
+    pub check_in_channel: Option<ChannelId>,
+
+    /// Tonight's sleep check-in thread, until it's tallied
+    pub check_in_thread: Option<CheckInThread>,
+
+    /// Bedtime challenge running in the guild, if any
+    pub challenge: Option<Challenge>,
+
+    /// How to clean up after failed commands, if at all
+    pub error_cleanup: Option<ErrorCleanup>,
+
+    /// Whether to reply for opted-in members mentioned while they're asleep
+    pub away_replies: bool,
+}
+
+impl GuildConfig {
+    /// Get the command prefix used in the guild
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or(CMD_PREFIX)
+    }
+}
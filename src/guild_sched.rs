@@ -0,0 +1,229 @@
+use crate::bot::Bot;
+use crate::guild_config::CheckInThread;
+use crate::state::State;
+use crate::time::Time;
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveTime, Utc};
+use serenity::model::{
+    id::{ChannelId, GuildId},
+    mention::Mentionable,
+};
+
+/// How often to check for guild jobs that are due
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Emoji members react with to check in
+const CHECK_IN_EMOJI: char = '🛏';
+
+/// Maximum number of thread messages and reactions looked at when tallying a
+/// check-in thread
+const TALLY_LIMIT: u8 = 100;
+
+/// Time of day to open the nightly check-in thread, in the guild's default
+/// time zone
+fn check_in_open_time() -> Time {
+    Time(NaiveTime::from_hms(20, 0, 0))
+}
+
+/// Time of day to tally the nightly check-in thread, in the guild's default
+/// time zone
+fn check_in_tally_time() -> Time {
+    Time(NaiveTime::from_hms(9, 0, 0))
+}
+
+/// Guild job that's due
+enum Job {
+    /// Post a message in a channel, allowing role pings
+    RolePing(ChannelId, String),
+
+    /// Open tonight's check-in thread in a channel
+    OpenCheckIn(GuildId, ChannelId, String),
+
+    /// Tally last night's check-in thread
+    TallyCheckIn(GuildId, CheckInThread),
+}
+
+/// Get the guild jobs due between `since` and `now`
+fn due_jobs(state: &State, since: DateTime<Utc>, now: DateTime<Utc>) -> Vec<Job> {
+    let mut jobs = Vec::new();
+    for (&guild, config) in &state.guilds {
+        let tz = match config.default_time_zone {
+            Some(tz) => tz,
+            None => continue,
+        };
+        let due = |time: Time| time.next_after(tz, since) <= now;
+
+        if let Some(ping) = config.role_ping.filter(|ping| due(ping.time)) {
+            let post = format!(
+                "{} It's {}. Time to start heading to bed! 🌙",
+                ping.role.mention(),
+                ping.time
+            );
+            jobs.push(Job::RolePing(ping.channel, post));
+        }
+
+        if let Some(channel) = config.check_in_channel {
+            if due(check_in_open_time()) {
+                let date = now.with_timezone(&tz).format("%B %-d").to_string();
+                jobs.push(Job::OpenCheckIn(guild, channel, date));
+            }
+        }
+
+        if let Some(thread) = config.check_in_thread {
+            if due(check_in_tally_time()) {
+                jobs.push(Job::TallyCheckIn(guild, thread));
+            }
+        }
+    }
+    jobs
+}
+
+/// Open a check-in thread in a channel for the night of `date`
+async fn open_check_in(
+    bot: &Bot,
+    channel: ChannelId,
+    date: &str,
+) -> serenity::Result<CheckInThread> {
+    let starter = channel
+        .say(
+            &bot.http,
+            format!(
+                "Heading to bed? Check in here by reacting with {} or posting \
+                 in the thread. I'll tally everyone tomorrow morning.",
+                CHECK_IN_EMOJI
+            ),
+        )
+        .await?;
+    starter.react(&bot.http, CHECK_IN_EMOJI).await?;
+    let thread = channel
+        .create_public_thread(&bot.http, starter.id, |t| {
+            t.name(format!("Sleep check-in — {}", date))
+        })
+        .await?;
+    Ok(CheckInThread {
+        thread: thread.id,
+        starter: starter.id,
+    })
+}
+
+/// Post the tally of members who checked in to a check-in thread
+async fn tally_check_in(
+    bot: &Bot,
+    channel: ChannelId,
+    thread: CheckInThread,
+) -> serenity::Result<()> {
+    let mut members = BTreeSet::new();
+
+    let messages = thread
+        .thread
+        .messages(&bot.http, |r| r.limit(TALLY_LIMIT.into()))
+        .await?;
+    members.extend(
+        messages
+            .iter()
+            .filter(|message| !message.author.bot)
+            .map(|message| message.author.id),
+    );
+
+    let reactors = channel
+        .reaction_users(
+            &bot.http,
+            thread.starter,
+            CHECK_IN_EMOJI,
+            Some(TALLY_LIMIT),
+            None,
+        )
+        .await?;
+    members.extend(reactors.iter().filter(|user| !user.bot).map(|user| user.id));
+
+    let post = if members.is_empty() {
+        "Nobody checked in last night".to_string()
+    } else {
+        let mentions: Vec<String> = members.iter().map(|id| id.mention().to_string()).collect();
+        format!(
+            "🌅 {} checked in last night: {}",
+            members.len(),
+            mentions.join(", ")
+        )
+    };
+
+    thread
+        .thread
+        .send_message(&bot.http, |m| {
+            m.content(post).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Run the guild jobs due between `since` and now
+async fn run_due(bot: &Bot, since: DateTime<Utc>) {
+    let jobs = {
+        let state = bot.read().await;
+        due_jobs(&state, since, Utc::now())
+    };
+
+    for job in jobs {
+        match job {
+            Job::RolePing(channel, post) => {
+                let res = channel
+                    .send_message(&bot.http, |m| {
+                        m.content(post)
+                            .allowed_mentions(|am| am.parse(serenity::builder::ParseValue::Roles))
+                    })
+                    .await;
+                if let Err(err) = res {
+                    println!("Error posting guild bedtime reminder: {}", err);
+                }
+            }
+            Job::OpenCheckIn(guild, channel, date) => {
+                let thread = match open_check_in(bot, channel, &date).await {
+                    Ok(thread) => thread,
+                    Err(err) => {
+                        println!("Error opening check-in thread: {}", err);
+                        continue;
+                    }
+                };
+                let mut state = bot.write().await;
+                state.guilds.entry(guild).or_default().check_in_thread = Some(thread);
+                state.save();
+            }
+            Job::TallyCheckIn(guild, thread) => {
+                let channel = {
+                    let mut state = bot.write().await;
+                    let config = state.guilds.entry(guild).or_default();
+                    config.check_in_thread = None;
+                    let channel = config.check_in_channel;
+                    state.save();
+                    channel
+                };
+                let channel = match channel {
+                    Some(channel) => channel,
+                    None => continue,
+                };
+                if let Err(err) = tally_check_in(bot, channel, thread).await {
+                    println!("Error tallying check-in thread: {}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Run guild-level scheduled jobs, like role pings and check-in threads,
+/// forever
+pub fn spawn(bot: Bot) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut since = Utc::now();
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            run_due(&bot, since).await;
+            since = now;
+        }
+    });
+}
@@ -1,17 +1,95 @@
+use crate::app::AppState;
+use crate::audit::{self, AuditEvent};
+use crate::bot::Bot;
+use crate::check_in;
+use crate::cmd;
+use crate::events::{self, Event};
+use crate::onboarding;
+use crate::presence::Status;
+use crate::routine;
 use crate::say;
-use crate::State;
-use crate::CMD_PREFIX;
+
+use std::collections::HashSet;
+use std::sync::Arc;
 
 use serenity::async_trait;
-use serenity::model::channel::Message;
+use serenity::client::bridge::gateway::ChunkGuildFilter;
+use serenity::model::channel::{Message, Reaction};
+use serenity::model::event::{GuildMembersChunkEvent, ResumedEvent, TypingStartEvent};
 use serenity::model::gateway::Presence;
 use serenity::model::gateway::Ready;
-use serenity::model::user::OnlineStatus;
+use serenity::model::id::{GuildId, UserId};
+use serenity::model::interactions::Interaction;
 use serenity::prelude::*;
 
 /// Serenity handler for bot. This implements `EventHandler` to process all the
 /// bot events.
-pub struct Handler;
+pub struct Handler {
+    /// Shared app state, which holds the bot's state
+    app: Arc<AppState>,
+}
+
+/// Most users Discord looks up per guild member request
+const CHUNK_USER_IDS: usize = 100;
+
+impl Handler {
+    pub fn new(app: Arc<AppState>) -> Self {
+        Handler { app }
+    }
+
+    /// If a DM is the author's wake phrase, treat it like the `wake` command
+    /// and return the reply to it
+    async fn wake_phrase(&self, msg: &Message) -> Option<String> {
+        let mut state = self.app.state.write().await;
+        let reply = match state.users.get_mut(&msg.author.id) {
+            Some(user_info) if user_info.is_wake_phrase(&msg.content) => {
+                user_info.allow_awake();
+                cmd::good_morning(user_info, msg.author.id)
+            }
+            _ => return None,
+        };
+
+        state.save();
+        audit::record(AuditEvent::NagAcknowledged {
+            user: msg.author.id,
+        });
+
+        Some(reply)
+    }
+
+    /// Get replies for the opted-in users mentioned in a message who are asleep
+    /// past their bedtime, if the guild allows them
+    async fn away_replies(&self, msg: &Message) -> Vec<String> {
+        let state = self.app.state.read().await;
+        if msg.guild_id.is_none() || !state.guild_config(msg.guild_id).away_replies {
+            return Vec::new();
+        }
+        msg.mentions
+            .iter()
+            .filter(|user| user.id != msg.author.id && !user.bot)
+            .filter_map(|user| {
+                let bedtime = state.users.get(&user.id)?.away_reply_bedtime()?;
+                Some(format!(
+                    "💤 {} is asleep (bedtime was {}) — they'll see this in the morning.",
+                    user.name, bedtime
+                ))
+            })
+            .collect()
+    }
+
+    /// Get the users who consented to presence tracking
+    async fn tracked(&self) -> HashSet<UserId> {
+        self.app
+            .state
+            .read()
+            .await
+            .users
+            .iter()
+            .filter(|(_, user_info)| user_info.tracks_presence())
+            .map(|(&id, _)| id)
+            .collect()
+    }
+}
 
 /// Implementation of event handler
 #[async_trait]
@@ -21,38 +99,192 @@ impl EventHandler for Handler {
         println!("{} is ready!", ready.user.name);
     }
 
+    /// Once every guild is cached, ask Discord which of the users whose
+    /// presence is tracked are in each guild, so their online status is known
+    /// before their bedtime instead of assumed until their next presence
+    /// update
+    async fn cache_ready(&self, ctx: Context, guilds: Vec<GuildId>) {
+        let tracked: Vec<UserId> = self.tracked().await.into_iter().collect();
+        if tracked.is_empty() {
+            return;
+        }
+        println!(
+            "Warming up presences of {} users in {} guilds",
+            tracked.len(),
+            guilds.len()
+        );
+        for guild in guilds {
+            for ids in tracked.chunks(CHUNK_USER_IDS) {
+                ctx.shard
+                    .chunk_guild(guild, None, ChunkGuildFilter::UserIds(ids.to_vec()), None);
+            }
+        }
+    }
+
+    /// Flag the tracked users in a chunk of guild members as awake or asleep.
+    /// Presences of online members come with the guild when it's created, so
+    /// tracked members without one are offline.
+    async fn guild_members_chunk(&self, ctx: Context, chunk: GuildMembersChunkEvent) {
+        let tracked = self.tracked().await;
+        let presences = ctx
+            .cache
+            .guild_field(chunk.guild_id, |g| g.presences.clone())
+            .unwrap_or_default();
+        for &user in chunk.members.keys() {
+            if !tracked.contains(&user) {
+                continue;
+            }
+            let status = presences
+                .get(&user)
+                .map_or_else(Status::offline, Status::from);
+            events::send(Event::PresenceChanged { user, status });
+        }
+    }
+
+    /// When the gateway session resumes after a disconnect, make sure every
+    /// user's jobs are still scheduled, and catch up on the online status of
+    /// users whose presence is tracked, in case an update was missed
+    async fn resume(&self, ctx: Context, _: ResumedEvent) {
+        println!("Gateway session resumed");
+
+        let bot = Bot::new(&ctx, Arc::clone(&self.app));
+        let tracked: HashSet<UserId> = {
+            let mut state = self.app.state.write().await;
+            let mut rescheduled = 0;
+            for (&id, user_info) in state.users.iter_mut() {
+                if !user_info.is_scheduled() {
+                    user_info.update_sched(bot.clone(), id).await;
+                    rescheduled += 1;
+                }
+            }
+            if rescheduled > 0 {
+                println!("Rescheduled {} users after resuming", rescheduled);
+            }
+            state
+                .users
+                .iter()
+                .filter(|(_, user_info)| user_info.tracks_presence())
+                .map(|(&id, _)| id)
+                .collect()
+        };
+
+        let mut seen = HashSet::new();
+        for guild in ctx.cache.guilds() {
+            let presences = match ctx.cache.guild_field(guild, |g| g.presences.clone()) {
+                Some(presences) => presences,
+                None => continue,
+            };
+            for (user, presence) in presences {
+                if tracked.contains(&user) && seen.insert(user) {
+                    events::send(Event::PresenceChanged {
+                        user,
+                        status: Status::from(&presence),
+                    });
+                }
+            }
+        }
+    }
+
     /// When a user's presence updates, flag the user as either awake or asleep,
-    /// depending on the new online status
-    async fn presence_update(&self, ctx: Context, presence: Presence) {
-        let mut data = ctx.data.write().await;
-        let user_info = data
-            .get_mut::<State>()
-            .expect("No state in context")
-            .users
-            .entry(presence.user.id)
-            .or_default();
+    /// depending on the new online status. This only applies to users who
+    /// consented to presence tracking, and never to bots.
+    async fn presence_update(&self, _ctx: Context, presence: Presence) {
+        if presence.user.bot == Some(true) {
+            return;
+        }
+
+        events::send(Event::PresenceChanged {
+            user: presence.user.id,
+            status: Status::from(&presence),
+        });
+    }
+
+    /// Note that a user who reacts is active, and when they first react to an
+    /// opt-in message, DM them to help them get set up
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let user = match reaction.user_id {
+            Some(user) if user != ctx.cache.current_user_id() => user,
+            _ => return,
+        };
+
+        events::send(Event::Active(user));
 
-        match presence.status {
-            OnlineStatus::Offline => user_info.asleep(),
-            _ => user_info.awake(),
+        let mut state = self.app.state.write().await;
+
+        if !state.opt_in_messages.contains(&reaction.message_id) || !state.first_contact(user) {
+            return;
         }
+
+        state.save();
+
+        onboarding::spawn_welcome(&ctx, user, reaction.guild_id);
     }
 
-    /// Reply with usage information when bot is pinged
+    /// Note that a user who starts typing is active
+    async fn typing_start(&self, _ctx: Context, event: TypingStartEvent) {
+        events::send(Event::Active(event.user_id));
+    }
+
+    /// Handle button presses on messages that outlive the command that sent
+    /// them, noting that the user who pressed them interacted with the bot
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let component = match interaction {
+            Interaction::MessageComponent(component) => component,
+            _ => return,
+        };
+
+        self.app.state.write().await.touch(component.user.id);
+
+        if let Err(err) = check_in::handle(&ctx, &component).await {
+            println!("Error handling morning check-in: {}", err);
+        }
+
+        if let Err(err) = routine::handle(&ctx, &component).await {
+            println!("Error handling routine checklist: {}", err);
+        }
+    }
+
+    /// Note that the author of a message is active, and reply with usage
+    /// information when bot is pinged, unless the ping is used as a command
+    /// prefix
     async fn message(&self, ctx: Context, msg: Message) {
-        let bot_user_id = ctx
-            .http
-            .get_current_user()
-            .await
-            .expect("Failed getting current user")
-            .id;
+        if !msg.author.bot {
+            events::send(Event::Active(msg.author.id));
+        }
 
-        let pinged = msg.mentions_user_id(bot_user_id);
+        if msg.guild_id.is_none() && !msg.author.bot {
+            if let Some(reply) = self.wake_phrase(&msg).await {
+                say(&ctx, &msg, reply).await;
+                return;
+            }
+        }
+
+        if !msg.author.bot {
+            for reply in self.away_replies(&msg).await {
+                say(&ctx, &msg, reply).await
+            }
+        }
+
+        let bot_user_id = ctx.cache.current_user_id();
+
+        let content = msg.content.trim_start();
+        let mention_prefixed = content.starts_with(&format!("<@{}>", bot_user_id))
+            || content.starts_with(&format!("<@!{}>", bot_user_id));
+        let pinged = msg.mentions_user_id(bot_user_id) && !mention_prefixed;
 
         if pinged {
+            let prefix = self
+                .app
+                .state
+                .read()
+                .await
+                .guild_config(msg.guild_id)
+                .prefix()
+                .to_string();
+
             let resp = format!(
                 "My command prefix is `{}`. Try `{} help` for a list of commands.",
-                CMD_PREFIX, CMD_PREFIX
+                prefix, prefix
             );
 
             say(&ctx, &msg, resp).await
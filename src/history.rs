@@ -0,0 +1,229 @@
+use crate::time::Time;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Maximum number of nights remembered per user
+const MAX_NIGHTS: usize = 366;
+
+/// Record of how one night went for a user
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Night {
+    /// Local date the night's bedtime fell on
+    pub date: NaiveDate,
+
+    /// Bedtime in effect for the night
+    pub bedtime: Time,
+
+    /// When the user was last detected going to sleep during the night
+    #[serde(default)]
+    pub fell_asleep: Option<DateTime<Utc>>,
+
+    /// When the user said they woke up
+    #[serde(default)]
+    pub woke: Option<DateTime<Utc>>,
+
+    /// Number of sleep reminders sent during the night
+    pub nags: u32,
+
+    /// Whether the user chose to skip the night
+    pub skipped: bool,
+
+    /// Whether sleep reminders gave up for the night after the user stayed up
+    /// too long
+    #[serde(default)]
+    pub gave_up: bool,
+
+    /// How well the user said they slept, from 1 to 5
+    #[serde(default)]
+    pub rating: Option<u8>,
+
+    /// Free-text note the user attached to the night
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl Night {
+    /// Whether the user went to bed on time, without needing any reminders
+    pub fn on_time(&self) -> bool {
+        !self.skipped && self.nags == 0
+    }
+
+    /// Get how long the user slept, from when they were detected going to
+    /// sleep to when they said they woke up, if both are known
+    pub fn slept(&self) -> Option<Duration> {
+        let slept = self.woke? - self.fell_asleep?;
+        if slept > Duration::zero() {
+            Some(slept)
+        } else {
+            None
+        }
+    }
+}
+
+/// Per-night history of a user's sleep. This is shared between the user's
+/// state and their scheduled jobs.
+#[derive(Clone, Default)]
+pub struct History(Arc<Mutex<VecDeque<Night>>>);
+
+impl History {
+    /// Start recording a new night, forgetting the oldest night if the
+    /// history is full. If the night was already started, it's replaced.
+    pub fn start_night(&self, date: NaiveDate, bedtime: Time, skipped: bool) {
+        let mut nights = self.0.lock().expect("History lock poisoned");
+        if nights.back().map(|night| night.date) == Some(date) {
+            nights.pop_back();
+        }
+        if nights.len() == MAX_NIGHTS {
+            nights.pop_front();
+        }
+        nights.push_back(Night {
+            date,
+            bedtime,
+            fell_asleep: None,
+            woke: None,
+            nags: 0,
+            skipped,
+            gave_up: false,
+            rating: None,
+            note: None,
+        });
+    }
+
+    /// Count a sleep reminder sent during the current night
+    pub fn count_nag(&self) {
+        let mut nights = self.0.lock().expect("History lock poisoned");
+        if let Some(night) = nights.back_mut() {
+            night.nags += 1;
+        }
+    }
+
+    /// Record that sleep reminders gave up on the current night
+    pub fn give_up(&self) {
+        let mut nights = self.0.lock().expect("History lock poisoned");
+        if let Some(night) = nights.back_mut() {
+            night.gave_up = true;
+        }
+    }
+
+    /// Record that the user was detected going to sleep during the current
+    /// night
+    pub fn fall_asleep(&self) {
+        let mut nights = self.0.lock().expect("History lock poisoned");
+        if let Some(night) = nights.back_mut() {
+            night.fell_asleep = Some(Utc::now());
+        }
+    }
+
+    /// Record that the user woke up, ending the current night
+    pub fn wake(&self) {
+        let mut nights = self.0.lock().expect("History lock poisoned");
+        if let Some(night) = nights.back_mut() {
+            night.woke = Some(Utc::now());
+        }
+    }
+
+    /// Rate how well the user slept the night of a date. Returns `false` if
+    /// the night isn't remembered.
+    pub fn rate(&self, date: NaiveDate, rating: u8) -> bool {
+        let mut nights = self.0.lock().expect("History lock poisoned");
+        match nights.iter_mut().rev().find(|night| night.date == date) {
+            Some(night) => {
+                night.rating = Some(rating);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Attach a note to the most recent night, replacing any note it had.
+    /// Returns the night's date, or `None` if there are no nights.
+    pub fn note_last(&self, note: String) -> Option<NaiveDate> {
+        let mut nights = self.0.lock().expect("History lock poisoned");
+        let night = nights.back_mut()?;
+        night.note = Some(note);
+        Some(night.date)
+    }
+
+    /// Get the current night, if any
+    pub fn last(&self) -> Option<Night> {
+        let nights = self.0.lock().expect("History lock poisoned");
+        nights.back().cloned()
+    }
+
+    /// Get the average sleep rating over all rated nights on or after a date
+    pub fn average_rating(&self, date: NaiveDate) -> Option<f64> {
+        let ratings: Vec<f64> = self
+            .since(date)
+            .iter()
+            .filter_map(|night| night.rating)
+            .map(f64::from)
+            .collect();
+        if ratings.is_empty() {
+            None
+        } else {
+            Some(ratings.iter().sum::<f64>() / ratings.len() as f64)
+        }
+    }
+
+    /// Get the number of nights in a row, up to and including the current
+    /// one, that the user went to bed on time. Skipped nights don't break the
+    /// streak.
+    pub fn streak(&self) -> u64 {
+        let nights = self.0.lock().expect("History lock poisoned");
+        nights
+            .iter()
+            .rev()
+            .filter(|night| !night.skipped)
+            .take_while(|night| night.on_time())
+            .count() as u64
+    }
+
+    /// Get the streak of on-time nights before the current one, which the user
+    /// loses if they stay up tonight
+    pub fn streak_at_risk(&self) -> u64 {
+        let nights = self.0.lock().expect("History lock poisoned");
+        nights
+            .iter()
+            .rev()
+            .skip(1)
+            .filter(|night| !night.skipped)
+            .take_while(|night| night.on_time())
+            .count() as u64
+    }
+
+    /// Get all remembered nights, oldest first
+    pub fn all(&self) -> Vec<Night> {
+        let nights = self.0.lock().expect("History lock poisoned");
+        nights.iter().cloned().collect()
+    }
+
+    /// Get all nights on or after a date, oldest first
+    pub fn since(&self, date: NaiveDate) -> Vec<Night> {
+        let nights = self.0.lock().expect("History lock poisoned");
+        nights
+            .iter()
+            .filter(|night| night.date >= date)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Serialize for History {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0
+            .lock()
+            .expect("History lock poisoned")
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for History {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let nights = VecDeque::deserialize(deserializer)?;
+        Ok(History(Arc::new(Mutex::new(nights))))
+    }
+}
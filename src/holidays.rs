@@ -0,0 +1,449 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Country whose public holidays a user observes
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Country {
+    Australia,
+    Canada,
+    France,
+    Germany,
+    Netherlands,
+    Spain,
+    UnitedKingdom,
+    UnitedStates,
+}
+
+/// When a public holiday falls each year
+enum Rule {
+    /// On a month and day
+    Fixed(u32, u32),
+
+    /// Some days after Easter Sunday, or before if negative
+    Easter(i64),
+
+    /// On the nth weekday of a month, counting from 1, or the last one if 0
+    Nth(u32, Weekday, u32),
+}
+
+/// Public holiday, by name and when it falls
+type Holiday = (&'static str, Rule);
+
+/// How a country gives a day off for public holidays that fall on a weekend
+#[derive(Clone, Copy)]
+enum Substitute {
+    /// It doesn't
+    Never,
+
+    /// The next weekday that isn't already a holiday is off instead
+    NextWeekday,
+
+    /// A Saturday holiday is observed the Friday before, and a Sunday one the
+    /// Monday after
+    NearestWeekday,
+}
+
+const AUSTRALIA: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Australia Day", Rule::Fixed(1, 26)),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("Anzac Day", Rule::Fixed(4, 25)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+    ("Boxing Day", Rule::Fixed(12, 26)),
+];
+
+const CANADA: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Canada Day", Rule::Fixed(7, 1)),
+    ("Labour Day", Rule::Nth(9, Weekday::Mon, 1)),
+    ("Thanksgiving", Rule::Nth(10, Weekday::Mon, 2)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+];
+
+const FRANCE: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("Labour Day", Rule::Fixed(5, 1)),
+    ("Victory in Europe Day", Rule::Fixed(5, 8)),
+    ("Ascension Day", Rule::Easter(39)),
+    ("Whit Monday", Rule::Easter(50)),
+    ("Bastille Day", Rule::Fixed(7, 14)),
+    ("Assumption Day", Rule::Fixed(8, 15)),
+    ("All Saints' Day", Rule::Fixed(11, 1)),
+    ("Armistice Day", Rule::Fixed(11, 11)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+];
+
+const GERMANY: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("Labour Day", Rule::Fixed(5, 1)),
+    ("Ascension Day", Rule::Easter(39)),
+    ("Whit Monday", Rule::Easter(50)),
+    ("German Unity Day", Rule::Fixed(10, 3)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+    ("Second Day of Christmas", Rule::Fixed(12, 26)),
+];
+
+const NETHERLANDS: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("King's Day", Rule::Fixed(4, 27)),
+    ("Liberation Day", Rule::Fixed(5, 5)),
+    ("Ascension Day", Rule::Easter(39)),
+    ("Whit Monday", Rule::Easter(50)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+    ("Second Day of Christmas", Rule::Fixed(12, 26)),
+];
+
+const SPAIN: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Epiphany", Rule::Fixed(1, 6)),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Labour Day", Rule::Fixed(5, 1)),
+    ("Assumption Day", Rule::Fixed(8, 15)),
+    ("National Day", Rule::Fixed(10, 12)),
+    ("All Saints' Day", Rule::Fixed(11, 1)),
+    ("Constitution Day", Rule::Fixed(12, 6)),
+    ("Immaculate Conception", Rule::Fixed(12, 8)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+];
+
+const UNITED_KINGDOM: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Good Friday", Rule::Easter(-2)),
+    ("Easter Monday", Rule::Easter(1)),
+    ("Early May Bank Holiday", Rule::Nth(5, Weekday::Mon, 1)),
+    ("Spring Bank Holiday", Rule::Nth(5, Weekday::Mon, 0)),
+    ("Summer Bank Holiday", Rule::Nth(8, Weekday::Mon, 0)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+    ("Boxing Day", Rule::Fixed(12, 26)),
+];
+
+const UNITED_STATES: &[Holiday] = &[
+    ("New Year's Day", Rule::Fixed(1, 1)),
+    ("Martin Luther King Jr. Day", Rule::Nth(1, Weekday::Mon, 3)),
+    ("Presidents' Day", Rule::Nth(2, Weekday::Mon, 3)),
+    ("Memorial Day", Rule::Nth(5, Weekday::Mon, 0)),
+    ("Juneteenth", Rule::Fixed(6, 19)),
+    ("Independence Day", Rule::Fixed(7, 4)),
+    ("Labor Day", Rule::Nth(9, Weekday::Mon, 1)),
+    ("Columbus Day", Rule::Nth(10, Weekday::Mon, 2)),
+    ("Veterans Day", Rule::Fixed(11, 11)),
+    ("Thanksgiving", Rule::Nth(11, Weekday::Thu, 4)),
+    ("Christmas Day", Rule::Fixed(12, 25)),
+];
+
+impl Country {
+    /// All countries with a holiday calendar
+    pub const ALL: [Country; 8] = [
+        Country::Australia,
+        Country::Canada,
+        Country::France,
+        Country::Germany,
+        Country::Netherlands,
+        Country::Spain,
+        Country::UnitedKingdom,
+        Country::UnitedStates,
+    ];
+
+    /// Two-letter ISO code used to select this country in commands
+    pub fn code(self) -> &'static str {
+        match self {
+            Country::Australia => "AU",
+            Country::Canada => "CA",
+            Country::France => "FR",
+            Country::Germany => "DE",
+            Country::Netherlands => "NL",
+            Country::Spain => "ES",
+            Country::UnitedKingdom => "GB",
+            Country::UnitedStates => "US",
+        }
+    }
+
+    /// Get the country's nationwide public holidays
+    fn holidays(self) -> &'static [Holiday] {
+        match self {
+            Country::Australia => AUSTRALIA,
+            Country::Canada => CANADA,
+            Country::France => FRANCE,
+            Country::Germany => GERMANY,
+            Country::Netherlands => NETHERLANDS,
+            Country::Spain => SPAIN,
+            Country::UnitedKingdom => UNITED_KINGDOM,
+            Country::UnitedStates => UNITED_STATES,
+        }
+    }
+
+    /// Get how the country makes up for holidays that fall on a weekend
+    fn substitute(self) -> Substitute {
+        match self {
+            Country::Australia | Country::Canada | Country::UnitedKingdom => {
+                Substitute::NextWeekday
+            }
+            Country::UnitedStates => Substitute::NearestWeekday,
+            Country::France | Country::Germany | Country::Netherlands | Country::Spain => {
+                Substitute::Never
+            }
+        }
+    }
+
+    /// Get the days off for the country's public holidays in a year, with the
+    /// names of the holidays they're for. Holidays on a weekend also get a
+    /// weekday off, if the country substitutes them, which can be in the year
+    /// before.
+    fn days_off(self, year: i32) -> Vec<(NaiveDate, String)> {
+        let mut holidays: Vec<(NaiveDate, &'static str)> = self
+            .holidays()
+            .iter()
+            .filter_map(|(name, rule)| Some((rule.date(year)?, *name)))
+            .collect();
+        holidays.sort_unstable();
+
+        let mut days: Vec<(NaiveDate, String)> = holidays
+            .iter()
+            .map(|&(date, name)| (date, name.to_string()))
+            .collect();
+        for &(date, name) in holidays.iter().filter(|(date, _)| is_weekend(*date)) {
+            let day = match self.substitute() {
+                Substitute::Never => continue,
+                Substitute::NearestWeekday if date.weekday() == Weekday::Sat => date.pred(),
+                Substitute::NearestWeekday => date.succ(),
+                Substitute::NextWeekday => {
+                    let mut day = date.succ();
+                    while is_weekend(day) || days.iter().any(|&(taken, _)| taken == day) {
+                        day = day.succ();
+                    }
+                    day
+                }
+            };
+            days.push((day, format!("{} (observed)", name)));
+        }
+        days
+    }
+
+    /// Get the name of the public holiday on a date, if there is one, counting
+    /// days off for holidays that fell on a weekend
+    pub fn holiday_on(self, date: NaiveDate) -> Option<String> {
+        // Holidays early next year can be observed at the end of this one
+        [date.year(), date.year() + 1]
+            .iter()
+            .flat_map(|&year| self.days_off(year))
+            .find(|&(day, _)| day == date)
+            .map(|(_, name)| name)
+    }
+}
+
+impl fmt::Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for Country {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        // `UK` is common, but not the ISO code
+        let s = if s.eq_ignore_ascii_case("UK") {
+            "GB"
+        } else {
+            s
+        };
+        Country::ALL
+            .iter()
+            .copied()
+            .find(|country| country.code().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let codes: Vec<_> = Country::ALL.iter().map(|c| c.code()).collect();
+                format!(
+                    "I don't have holidays for '{}'. I know {}.",
+                    s,
+                    codes.join(", ")
+                )
+            })
+    }
+}
+
+impl Rule {
+    /// Get the date the holiday falls on in a year
+    fn date(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            Rule::Fixed(month, day) => NaiveDate::from_ymd_opt(year, month, day),
+            Rule::Easter(offset) => Some(easter(year) + Duration::days(offset)),
+            Rule::Nth(month, weekday, 0) => {
+                let (next_year, next_month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                let last = NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred();
+                let back = (7 + last.weekday().num_days_from_monday()
+                    - weekday.num_days_from_monday())
+                    % 7;
+                Some(last - Duration::days(i64::from(back)))
+            }
+            Rule::Nth(month, weekday, n) => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let ahead = (7 + weekday.num_days_from_monday()
+                    - first.weekday().num_days_from_monday())
+                    % 7;
+                Some(first + Duration::days(i64::from(ahead + 7 * (n - 1))))
+            }
+        }
+    }
+}
+
+/// Get the date of Easter Sunday in a year, with the anonymous Gregorian
+/// algorithm
+fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd(year, month as u32, day as u32)
+}
+
+/// Whether a date is on a weekend
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// Get the name of the public holiday the night of a date is the eve of, if
+/// any
+pub fn eve_of(country: Country, night: NaiveDate) -> Option<String> {
+    country.holiday_on(night.succ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    #[test]
+    fn easter_dates() {
+        assert_eq!(easter(2000), date(2000, 4, 23));
+        assert_eq!(easter(2019), date(2019, 4, 21));
+        assert_eq!(easter(2024), date(2024, 3, 31));
+        assert_eq!(easter(2025), date(2025, 4, 20));
+        assert_eq!(easter(2038), date(2038, 4, 25));
+    }
+
+    #[test]
+    fn rule_dates() {
+        assert_eq!(Rule::Fixed(7, 4).date(2024), Some(date(2024, 7, 4)));
+        assert_eq!(Rule::Easter(-2).date(2024), Some(date(2024, 3, 29)));
+        assert_eq!(Rule::Easter(50).date(2025), Some(date(2025, 6, 9)));
+        // Martin Luther King Jr. Day and Thanksgiving
+        assert_eq!(
+            Rule::Nth(1, Weekday::Mon, 3).date(2025),
+            Some(date(2025, 1, 20))
+        );
+        assert_eq!(
+            Rule::Nth(11, Weekday::Thu, 4).date(2024),
+            Some(date(2024, 11, 28))
+        );
+        // Memorial Day and the UK's Summer Bank Holiday
+        assert_eq!(
+            Rule::Nth(5, Weekday::Mon, 0).date(2024),
+            Some(date(2024, 5, 27))
+        );
+        assert_eq!(
+            Rule::Nth(8, Weekday::Mon, 0).date(2024),
+            Some(date(2024, 8, 26))
+        );
+        assert_eq!(
+            Rule::Nth(12, Weekday::Tue, 0).date(2024),
+            Some(date(2024, 12, 31))
+        );
+    }
+
+    #[test]
+    fn uk_substitute_days() {
+        let uk = Country::UnitedKingdom;
+        // Christmas on a Saturday and Boxing Day on a Sunday
+        assert_eq!(
+            uk.holiday_on(date(2021, 12, 27)).as_deref(),
+            Some("Christmas Day (observed)")
+        );
+        assert_eq!(
+            uk.holiday_on(date(2021, 12, 28)).as_deref(),
+            Some("Boxing Day (observed)")
+        );
+        // Christmas on a Sunday, with Boxing Day already on the Monday
+        assert_eq!(
+            uk.holiday_on(date(2022, 12, 26)).as_deref(),
+            Some("Boxing Day")
+        );
+        assert_eq!(
+            uk.holiday_on(date(2022, 12, 27)).as_deref(),
+            Some("Christmas Day (observed)")
+        );
+        assert_eq!(
+            uk.holiday_on(date(2022, 1, 3)).as_deref(),
+            Some("New Year's Day (observed)")
+        );
+        assert_eq!(uk.holiday_on(date(2022, 12, 28)), None);
+    }
+
+    #[test]
+    fn us_observed_days() {
+        let us = Country::UnitedStates;
+        assert_eq!(
+            us.holiday_on(date(2020, 7, 3)).as_deref(),
+            Some("Independence Day (observed)")
+        );
+        assert_eq!(
+            us.holiday_on(date(2021, 7, 5)).as_deref(),
+            Some("Independence Day (observed)")
+        );
+        // New Year's Day on a Saturday is observed the year before
+        assert_eq!(
+            us.holiday_on(date(2021, 12, 31)).as_deref(),
+            Some("New Year's Day (observed)")
+        );
+        assert_eq!(us.holiday_on(date(2024, 7, 5)), None);
+    }
+
+    #[test]
+    fn no_substitutes() {
+        assert_eq!(Country::Germany.holiday_on(date(2021, 12, 27)), None);
+        assert_eq!(
+            Country::Germany.holiday_on(date(2021, 12, 25)).as_deref(),
+            Some("Christmas Day")
+        );
+    }
+
+    #[test]
+    fn eves() {
+        assert_eq!(
+            eve_of(Country::UnitedKingdom, date(2021, 12, 26)).as_deref(),
+            Some("Christmas Day (observed)")
+        );
+        assert_eq!(
+            eve_of(Country::Germany, date(2024, 10, 2)).as_deref(),
+            Some("German Unity Day")
+        );
+        assert_eq!(eve_of(Country::Germany, date(2024, 10, 3)), None);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::features::{self, Feature};
+use crate::templates;
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::{id::UserId, mention::Mentionable};
+
+/// Number of tones reminder messages go through, from gentle to blunt
+pub const TONES: u8 = 4;
+
+/// How long the user stays up past bedtime before reminders move to the next
+/// tone, in minutes
+const TONE_MINUTES: i64 = 30;
+
+/// How sleep reminders behave at an intensity level
+struct Profile {
+    /// Reminder message for each tone, from the first sent to the most blunt
+    messages: [&'static str; TONES as usize],
+
+    /// Time to wait after the first reminder
+    first_interval: Duration,
+
+    /// Shortest time to wait between reminders, once fully escalated
+    min_interval: Duration,
+
+    /// Number of reminders sent before escalating a step
+    escalate_after: u32,
+}
+
+const GENTLE: Profile = Profile {
+    messages: [
+        "It's past your bedtime. Time to wind down. 🌙",
+        "Still up? Your bed misses you. 🌙",
+        "Gentle reminder: sleep now, feel better tomorrow. 🌙",
+        "It's getting really late. Please put everything down and sleep. 🌙",
+    ],
+    first_interval: Duration::from_secs(60),
+    min_interval: Duration::from_secs(30),
+    escalate_after: 10,
+};
+
+const NORMAL: Profile = Profile {
+    messages: [
+        "Go to bed. 😴 🛏  💤",
+        "Seriously, go to bed. 😴",
+        "You're going to regret this in the morning. Go to bed. 🛏",
+        "You've been up over an hour and a half past bedtime. Bed. Now. 🛏",
+    ],
+    first_interval: Duration::from_secs(5),
+    min_interval: Duration::from_secs(3),
+    escalate_after: 6,
+};
+
+const DRILL_SERGEANT: Profile = Profile {
+    messages: [
+        "BED. NOW. NO EXCUSES. 🪖",
+        "DID I STUTTER? BED! 🪖",
+        "DROP WHAT YOU'RE DOING AND GET IN THAT BED, RECRUIT! 🪖📢",
+        "THIS IS AN ORDER. LIGHTS OUT. THAT MEANS YOU. 🪖📢🚨",
+    ],
+    first_interval: Duration::from_secs(3),
+    min_interval: Duration::from_secs(1),
+    escalate_after: 3,
+};
+
+/// What a sleep reminder can say about the user and their night
+pub struct NagContext<'a> {
+    /// The user being reminded
+    pub user: UserId,
+
+    /// The user's name, to address them by
+    pub name: &'a str,
+
+    /// The bedtime the user is up past
+    pub bedtime: &'a str,
+
+    /// Number of on-time nights in a row the user loses by staying up
+    pub streak: u64,
+
+    /// Message pack the user picked, if any
+    pub pack: Option<&'a str>,
+}
+
+/// How insistent sleep reminders are
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum Intensity {
+    Gentle,
+    #[default]
+    Normal,
+    DrillSergeant,
+}
+
+impl Intensity {
+    /// All intensity levels, from least to most insistent
+    pub const ALL: [Intensity; 3] = [
+        Intensity::Gentle,
+        Intensity::Normal,
+        Intensity::DrillSergeant,
+    ];
+
+    /// Name used to select this intensity in commands
+    pub fn name(self) -> &'static str {
+        match self {
+            Intensity::Gentle => "gentle",
+            Intensity::Normal => "normal",
+            Intensity::DrillSergeant => "drill-sergeant",
+        }
+    }
+
+    /// Get how sleep reminders behave at this intensity
+    fn profile(self) -> &'static Profile {
+        match self {
+            Intensity::Gentle => &GENTLE,
+            Intensity::Normal => &NORMAL,
+            Intensity::DrillSergeant => &DRILL_SERGEANT,
+        }
+    }
+
+    /// Get how many times reminders escalated after `sent` reminders tonight.
+    /// They never escalate if the escalation feature is off.
+    fn escalation(self, sent: u32) -> u32 {
+        if !features::is_enabled(Feature::Escalation) {
+            return 0;
+        }
+        sent / self.profile().escalate_after
+    }
+
+    /// Sleep reminder message sent at this intensity, after the user stayed up
+    /// for some time past bedtime. It starts with how late it is and the
+    /// streak at stake, then a message whose tone gets blunter every half
+    /// hour, up to the user's chosen maximum tone, counting from 1. It stays
+    /// gentle if the escalation feature is off. The user's message pack or the
+    /// operator's template for the tone is used instead if there is one.
+    pub fn nag_message(
+        self,
+        elapsed: chrono::Duration,
+        max_tone: Option<u8>,
+        context: &NagContext<'_>,
+    ) -> String {
+        let mut tone = (elapsed.num_minutes() / TONE_MINUTES).clamp(0, i64::from(TONES - 1)) as u8;
+        if !features::is_enabled(Feature::Escalation) {
+            tone = 0;
+        }
+        if let Some(max_tone) = max_tone {
+            tone = tone.min(max_tone.saturating_sub(1));
+        }
+
+        let minutes_late = elapsed.num_minutes();
+        let mut default = if minutes_late > 0 {
+            "{name}, it's {minutes_late} minutes past your {bedtime} bedtime".to_string()
+        } else {
+            "{name}, it's your {bedtime} bedtime".to_string()
+        };
+        if context.streak > 0 {
+            default.push_str(" — your {streak}-night streak is on the line");
+        }
+        default = format!(
+            "{}. {}",
+            default,
+            self.profile().messages[usize::from(tone)]
+        );
+
+        templates::render(
+            context.pack,
+            &templates::nag_key(self, tone + 1),
+            &default,
+            &[
+                ("user", &context.user.mention()),
+                ("name", &context.name),
+                ("bedtime", &context.bedtime),
+                ("minutes_late", &minutes_late),
+                ("streak", &context.streak),
+            ],
+        )
+    }
+
+    /// Time to wait between sleep reminders at this intensity, after `sent`
+    /// reminders tonight. Reminders come faster the longer the user stays up.
+    pub fn nag_interval(self, sent: u32) -> Duration {
+        let profile = self.profile();
+        let halvings = self.escalation(sent).min(u32::BITS - 1);
+        (profile.first_interval / 2u32.pow(halvings)).max(profile.min_interval)
+    }
+}
+
+impl fmt::Display for Intensity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Intensity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Intensity::ALL
+            .iter()
+            .copied()
+            .find(|intensity| intensity.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("Unknown intensity '{}'", s))
+    }
+}
@@ -0,0 +1,183 @@
+use crate::state::State;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rand::RngCore;
+use ring::hmac;
+use serenity::model::id::UserId;
+
+/// How long a link token can be used after it's minted
+pub const TOKEN_MINUTES: i64 = 15;
+
+/// Length of the key link tokens are signed with, in bytes
+const KEY_LEN: usize = 32;
+
+/// Get the key link tokens are signed with, making one the first time
+fn key(state: &mut State) -> hmac::Key {
+    if state.link_key.is_empty() {
+        let mut key = vec![0; KEY_LEN];
+        rand::thread_rng().fill_bytes(&mut key);
+        state.link_key = base64::encode(key);
+        state.save();
+    }
+    existing_key(state).expect("Invalid link key")
+}
+
+/// Get the key link tokens are signed with, if there is one
+fn existing_key(state: &State) -> Option<hmac::Key> {
+    let key = base64::decode(&state.link_key).ok()?;
+    if key.is_empty() {
+        return None;
+    }
+    Some(hmac::Key::new(hmac::HMAC_SHA256, &key))
+}
+
+/// What a token is for, signed into it so one kind can't stand in for another
+#[derive(Clone, Copy)]
+enum Purpose {
+    /// Acting for the user, like logging in to the dashboard
+    Link,
+    /// Only downloading the user's settings
+    Export,
+}
+
+impl Purpose {
+    /// Prefix of the signed payload for tokens with this purpose
+    fn prefix(self) -> &'static str {
+        match self {
+            Purpose::Link => "link",
+            Purpose::Export => "export",
+        }
+    }
+}
+
+/// Mint a token proving that someone is a user, for pasting into external
+/// integrations. It expires after a while, or when the user unlinks.
+pub fn mint(state: &mut State, id: UserId) -> String {
+    mint_for(state, id, Purpose::Link, Utc::now())
+}
+
+/// Mint a token that only lets its holder download a user's settings, for the
+/// link made with `export url`. It expires like a link token.
+pub fn mint_export(state: &mut State, id: UserId) -> String {
+    mint_for(state, id, Purpose::Export, Utc::now())
+}
+
+/// Check a link token, getting the user it belongs to if it's valid
+pub fn verify(state: &State, token: &str) -> Option<UserId> {
+    verify_for(state, token, Purpose::Link, Utc::now())
+}
+
+/// Check an export token, getting the user it belongs to if it's valid
+pub fn verify_export(state: &State, token: &str) -> Option<UserId> {
+    verify_for(state, token, Purpose::Export, Utc::now())
+}
+
+/// Mint a token for `purpose`, as if it were `now`
+fn mint_for(state: &mut State, id: UserId, purpose: Purpose, now: DateTime<Utc>) -> String {
+    let key = key(state);
+    let generation = state
+        .users
+        .get(&id)
+        .map_or(0, |user_info| user_info.link_generation());
+    let expires = (now + Duration::minutes(TOKEN_MINUTES)).timestamp();
+    let payload = format!("{}.{}.{}", id, generation, expires);
+    let tag = hmac::sign(&key, signed(purpose, &payload).as_bytes());
+    format!(
+        "{}.{}",
+        payload,
+        base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD)
+    )
+}
+
+/// Check a token minted for `purpose`, as if it were `now`
+fn verify_for(state: &State, token: &str, purpose: Purpose, now: DateTime<Utc>) -> Option<UserId> {
+    let key = existing_key(state)?;
+    let (payload, tag) = token.trim().rsplit_once('.')?;
+    let tag = base64::decode_config(tag, base64::URL_SAFE_NO_PAD).ok()?;
+    hmac::verify(&key, signed(purpose, payload).as_bytes(), &tag).ok()?;
+
+    let mut parts = payload.split('.');
+    let id = UserId(parts.next()?.parse().ok()?);
+    let generation: u32 = parts.next()?.parse().ok()?;
+    let expires = Utc.timestamp_opt(parts.next()?.parse().ok()?, 0).single()?;
+
+    let current = state.users.get(&id)?.link_generation();
+    if expires <= now || generation != current {
+        return None;
+    }
+    Some(id)
+}
+
+/// Get the message signed for a token payload with `purpose`
+fn signed(purpose: Purpose, payload: &str) -> String {
+    format!("{}:{}", purpose.prefix(), payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_info::UserInfo;
+
+    /// Make a state with one user who can mint tokens
+    fn state() -> (State, UserId) {
+        let id = UserId(1);
+        let mut state = State::default();
+        state.users.insert(id, UserInfo::default());
+        (state, id)
+    }
+
+    #[test]
+    fn verify_valid() {
+        let (mut state, id) = state();
+        let token = mint(&mut state, id);
+        assert_eq!(verify(&state, &token), Some(id));
+        let token = mint_export(&mut state, id);
+        assert_eq!(verify_export(&state, &token), Some(id));
+    }
+
+    #[test]
+    fn verify_expired() {
+        let (mut state, id) = state();
+        let minted = Utc::now() - Duration::minutes(TOKEN_MINUTES);
+        let token = mint_for(&mut state, id, Purpose::Link, minted);
+        assert_eq!(verify(&state, &token), None);
+        let just_before = minted + Duration::minutes(TOKEN_MINUTES) - Duration::seconds(1);
+        assert_eq!(
+            verify_for(&state, &token, Purpose::Link, just_before),
+            Some(id)
+        );
+    }
+
+    #[test]
+    fn verify_wrong_generation() {
+        let (mut state, id) = state();
+        let token = mint(&mut state, id);
+        state.users.get_mut(&id).unwrap().revoke_links();
+        assert_eq!(verify(&state, &token), None);
+    }
+
+    #[test]
+    fn verify_tampered() {
+        let (mut state, id) = state();
+        let token = mint(&mut state, id);
+
+        let (payload, tag) = token.rsplit_once('.').unwrap();
+        let first = if tag.starts_with('A') { 'B' } else { 'A' };
+        let tag = format!("{}{}", first, &tag[1..]);
+        assert_eq!(verify(&state, &format!("{}.{}", payload, tag)), None);
+
+        // Claiming to be someone else with a valid tag doesn't work either
+        state.users.insert(UserId(2), UserInfo::default());
+        let forged = token.replacen('1', "2", 1);
+        assert_eq!(verify(&state, &forged), None);
+    }
+
+    #[test]
+    fn verify_wrong_purpose() {
+        let (mut state, id) = state();
+        let link = mint(&mut state, id);
+        let export = mint_export(&mut state, id);
+        assert_eq!(verify_export(&state, &link), None);
+        assert_eq!(verify(&state, &export), None);
+    }
+}
@@ -0,0 +1,56 @@
+use chrono_tz::Tz;
+use serenity::{model::prelude::*, prelude::*};
+
+/// Most populous time zone for each Discord locale, used to guess where a
+/// user lives. Locales that span many time zones, like `en-US`, can only give
+/// a rough guess, so it's only ever a suggestion.
+const TIME_ZONES: &[(&str, &str)] = &[
+    ("bg", "Europe/Sofia"),
+    ("cs", "Europe/Prague"),
+    ("da", "Europe/Copenhagen"),
+    ("de", "Europe/Berlin"),
+    ("el", "Europe/Athens"),
+    ("en-GB", "Europe/London"),
+    ("en-US", "America/New_York"),
+    ("es-ES", "Europe/Madrid"),
+    ("fi", "Europe/Helsinki"),
+    ("fr", "Europe/Paris"),
+    ("hi", "Asia/Kolkata"),
+    ("hr", "Europe/Zagreb"),
+    ("hu", "Europe/Budapest"),
+    ("id", "Asia/Jakarta"),
+    ("it", "Europe/Rome"),
+    ("ja", "Asia/Tokyo"),
+    ("ko", "Asia/Seoul"),
+    ("lt", "Europe/Vilnius"),
+    ("nl", "Europe/Amsterdam"),
+    ("no", "Europe/Oslo"),
+    ("pl", "Europe/Warsaw"),
+    ("pt-BR", "America/Sao_Paulo"),
+    ("ro", "Europe/Bucharest"),
+    ("ru", "Europe/Moscow"),
+    ("sv-SE", "Europe/Stockholm"),
+    ("th", "Asia/Bangkok"),
+    ("tr", "Europe/Istanbul"),
+    ("uk", "Europe/Kiev"),
+    ("vi", "Asia/Ho_Chi_Minh"),
+    ("zh-CN", "Asia/Shanghai"),
+    ("zh-TW", "Asia/Taipei"),
+];
+
+/// Guess a likely time zone from a Discord locale, like `de` or `pt-BR`
+pub fn time_zone(locale: &str) -> Option<Tz> {
+    TIME_ZONES
+        .iter()
+        .find(|(l, _)| l.eq_ignore_ascii_case(locale.trim()))
+        .and_then(|(_, name)| name.parse().ok())
+}
+
+/// Guess a likely time zone from a guild's preferred locale, for when the
+/// user's own locale isn't known, like with text commands
+pub fn guild_time_zone(ctx: &Context, guild: Option<GuildId>) -> Option<Tz> {
+    let locale = ctx
+        .cache
+        .guild_field(guild?, |guild| guild.preferred_locale.clone())?;
+    time_zone(&locale)
+}
@@ -0,0 +1,95 @@
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+/// Major cities that aren't in any time zone's name, and the time zone they're
+/// in
+const CITIES: &[(&str, Tz)] = &[
+    ("atlanta", Tz::America__New_York),
+    ("austin", Tz::America__Chicago),
+    ("bangalore", Tz::Asia__Kolkata),
+    ("barcelona", Tz::Europe__Madrid),
+    ("beijing", Tz::Asia__Shanghai),
+    ("boston", Tz::America__New_York),
+    ("cape town", Tz::Africa__Johannesburg),
+    ("dallas", Tz::America__Chicago),
+    ("delhi", Tz::Asia__Kolkata),
+    ("frankfurt", Tz::Europe__Berlin),
+    ("hamburg", Tz::Europe__Berlin),
+    ("houston", Tz::America__Chicago),
+    ("las vegas", Tz::America__Los_Angeles),
+    ("lyon", Tz::Europe__Paris),
+    ("miami", Tz::America__New_York),
+    ("milan", Tz::Europe__Rome),
+    ("montreal", Tz::America__Toronto),
+    ("mumbai", Tz::Asia__Kolkata),
+    ("munich", Tz::Europe__Berlin),
+    ("new delhi", Tz::Asia__Kolkata),
+    ("osaka", Tz::Asia__Tokyo),
+    ("ottawa", Tz::America__Toronto),
+    ("philadelphia", Tz::America__New_York),
+    ("portland", Tz::America__Los_Angeles),
+    ("rio de janeiro", Tz::America__Sao_Paulo),
+    ("san diego", Tz::America__Los_Angeles),
+    ("san francisco", Tz::America__Los_Angeles),
+    ("seattle", Tz::America__Los_Angeles),
+    ("st petersburg", Tz::Europe__Moscow),
+    ("washington", Tz::America__New_York),
+];
+
+/// Find the time zone of a city, like `Berlin` or `San Francisco, CA`.
+/// Returns `None` if the city isn't known.
+pub fn lookup(place: &str) -> Option<Tz> {
+    // Ignore any state or country after the city
+    let city = place
+        .split(',')
+        .next()?
+        .trim()
+        .replace('.', "")
+        .to_lowercase();
+    if city.is_empty() {
+        return None;
+    }
+
+    if let Some(&(_, tz)) = CITIES.iter().find(|(name, _)| *name == city) {
+        return Some(tz);
+    }
+
+    TZ_VARIANTS.iter().copied().find(|tz| {
+        let name = tz.name();
+        let zone_city = name.rsplit('/').next().unwrap_or(name);
+        name.contains('/') && zone_city.replace('_', " ").to_lowercase() == city
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_exact() {
+        assert_eq!(lookup("Berlin"), Some(Tz::Europe__Berlin));
+        assert_eq!(lookup("New York"), Some(Tz::America__New_York));
+        assert_eq!(lookup("seattle"), Some(Tz::America__Los_Angeles));
+    }
+
+    #[test]
+    fn lookup_case_insensitive() {
+        assert_eq!(lookup("bERLIN"), Some(Tz::Europe__Berlin));
+        assert_eq!(lookup("SAN FRANCISCO"), Some(Tz::America__Los_Angeles));
+        assert_eq!(lookup("  St. Petersburg "), Some(Tz::Europe__Moscow));
+    }
+
+    #[test]
+    fn lookup_ignores_region() {
+        assert_eq!(lookup("San Francisco, CA"), Some(Tz::America__Los_Angeles));
+        assert_eq!(lookup("Munich, Germany"), Some(Tz::Europe__Berlin));
+    }
+
+    #[test]
+    fn lookup_unknown() {
+        assert_eq!(lookup("Atlantis"), None);
+        assert_eq!(lookup(""), None);
+        assert_eq!(lookup(", Germany"), None);
+        // Regions of time zone names aren't cities
+        assert_eq!(lookup("Europe"), None);
+    }
+}
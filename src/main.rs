@@ -1,12 +1,68 @@
+pub mod achievements;
+pub mod actor;
+pub mod admin_api;
+pub mod alert;
+pub mod app;
+pub mod audit;
+pub mod bot;
+pub mod buddy;
+pub mod challenge;
+pub mod change_log;
+pub mod chart;
+pub mod check_in;
+pub mod clock;
 pub mod cmd;
+pub mod config;
+pub mod confirm;
+pub mod dashboard;
+pub mod events;
+pub mod export;
+pub mod features;
+pub mod group;
+pub mod guild_config;
+pub mod guild_sched;
 pub mod handler;
+pub mod history;
+pub mod holidays;
+pub mod intensity;
+pub mod link;
+pub mod locale;
+pub mod location;
+pub mod maintenance;
+pub mod manager;
+pub mod metrics;
+pub mod morning;
+pub mod nag_log;
+pub mod onboarding;
+pub mod presence;
+pub mod presence_log;
+pub mod profile;
+pub mod reminder;
+pub mod routine;
+pub mod scheduler;
+pub mod send_queue;
+pub mod sleep_debt;
 pub mod state;
+pub mod suggest;
+pub mod supervisor;
+pub mod target;
+pub mod templates;
 pub mod time;
+pub mod transfer;
+pub mod travel;
+pub mod tz_picker;
+pub mod user_error;
 pub mod user_info;
+pub mod winddown;
+pub mod wizard;
+pub mod xp;
 
 #[macro_use]
 extern crate lazy_static;
 
+use app::AppState;
+use audit::AuditEvent;
+use bot::Bot;
 use handler::Handler;
 use state::State;
 
@@ -14,29 +70,66 @@ use std::env;
 use std::fmt;
 use std::iter;
 use std::sync::Arc;
+use std::time::Duration;
 
 use serenity::{
     framework::{
         standard::{macros::hook, CommandResult, Delimiter},
         StandardFramework,
     },
+    http::Http,
     model::{gateway::GatewayIntents, prelude::*},
     prelude::*,
     Result,
 };
 
-/// Bot command prefix
+/// Default bot command prefix
 pub static CMD_PREFIX: &str = "b,";
 
-/// Reply to a message with the debug representation of `dbg`
-async fn say_dbg<T: fmt::Debug>(ctx: &Context, msg: &Message, dbg: T) {
-    say(ctx, msg, format!("```{:#?}```", dbg)).await
-}
-
-/// If `res` holds an error, reply to a message with the error
+/// If `res` holds an error, reply to a message explaining it. The bot's
+/// owner also gets the debug representation of the error. In guilds that
+/// clean up after failed commands, the reply is deleted after a while, along
+/// with the command if the guild wants.
 async fn say_if_err(ctx: &Context, msg: &Message, res: &CommandResult) {
-    if let Err(err) = res {
-        say_dbg(ctx, msg, err).await
+    let err = match res {
+        Ok(()) => return,
+        Err(err) => err,
+    };
+    let mut resp = user_error::describe(&**err);
+    let bot = match Bot::of(ctx).await {
+        Ok(bot) => bot,
+        Err(_) => return say(ctx, msg, resp).await,
+    };
+    if msg.author.id == bot.app.owner {
+        resp = format!("{}\n```{:#?}```", resp, err);
+    }
+    let cleanup = match msg.guild_id {
+        Some(guild) => bot.read().await.guild_config(Some(guild)).error_cleanup,
+        None => None,
+    };
+
+    let reply = match msg.channel_id.say(&ctx.http, &resp).await {
+        Ok(reply) => reply,
+        Err(err) => {
+            println!("Error saying message '{}': {}", resp, err);
+            return;
+        }
+    };
+
+    if let Some(cleanup) = cleanup {
+        let mut messages = vec![reply.id];
+        if cleanup.delete_command {
+            messages.push(msg.id);
+        }
+        let channel = msg.channel_id;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(cleanup.after_secs)).await;
+            for message in messages {
+                if let Err(err) = channel.delete_message(&bot.http, message).await {
+                    println!("Error cleaning up after failed command: {}", err);
+                }
+            }
+        });
     }
 }
 
@@ -47,14 +140,72 @@ pub async fn say<T: fmt::Display>(ctx: &Context, msg: &Message, content: T) {
     }
 }
 
+/// Warn the author of a message about anything in their settings stopping
+/// their sleep reminders, so they don't think they're done setting up when
+/// they aren't
+async fn warn_incomplete_setup(ctx: &Context, msg: &Message) {
+    let bot = match Bot::of(ctx).await {
+        Ok(bot) => bot,
+        Err(_) => return,
+    };
+    let warnings = match bot.read().await.users.get(&msg.author.id) {
+        Some(user_info) => user_info.setup_warnings(),
+        None => return,
+    };
+    if !warnings.is_empty() {
+        say(ctx, msg, warnings.join("\n")).await
+    }
+}
+
+/// Get the command prefix for the guild a message was sent in
 #[hook]
-async fn before_command_hook(_ctx: &Context, msg: &Message, cmd: &str) -> bool {
+async fn dynamic_prefix_hook(ctx: &Context, msg: &Message) -> Option<String> {
+    let bot = match Bot::of(ctx).await {
+        Ok(bot) => bot,
+        Err(_) => return Some(CMD_PREFIX.to_string()),
+    };
+    let state = bot.read().await;
+    Some(state.guild_config(msg.guild_id).prefix().to_string())
+}
+
+#[hook]
+async fn before_command_hook(ctx: &Context, msg: &Message, cmd: &str) -> bool {
     println!("Got command '{}' by user '{}'", cmd, msg.author.name);
+    let bot = match Bot::of(ctx).await {
+        Ok(bot) => bot,
+        Err(err) => {
+            say(ctx, msg, err).await;
+            return false;
+        }
+    };
+    bot.app.metrics.command_started(msg.id);
+
+    let mut state = bot.write().await;
+    state.touch(msg.author.id);
+    if state.first_contact(msg.author.id) {
+        state.save();
+        if cmd != "setup" {
+            onboarding::spawn_welcome(ctx, msg.author.id, msg.guild_id);
+        }
+    }
+
     true
 }
 
 #[hook]
-async fn after_command_hook(ctx: &Context, msg: &Message, _cmd: &str, res: CommandResult) {
+async fn after_command_hook(ctx: &Context, msg: &Message, cmd: &str, res: CommandResult) {
+    if let Ok(bot) = Bot::of(ctx).await {
+        if let Some(took) = bot.app.metrics.command_finished(msg.id, cmd) {
+            tracing::info!(command = cmd, ok = res.is_ok(), ?took, "Command finished");
+        }
+    }
+    if res.is_ok() && cmd::SETTINGS_COMMANDS.contains(&cmd) {
+        audit::record(AuditEvent::SettingsChanged {
+            user: msg.author.id,
+            via: format!("command {}", cmd),
+        });
+        warn_incomplete_setup(ctx, msg).await;
+    }
     say_if_err(ctx, msg, &res).await
 }
 
@@ -69,49 +220,78 @@ async fn prefix_only_hook(ctx: &Context, msg: &Message) {
     say(ctx, msg, "Try the `help` sub-command for help.").await
 }
 
-async fn create_client(token: &str) -> Result<Client> {
-    Client::builder(token, GatewayIntents::all())
-        .event_handler(Handler)
+async fn create_client(token: &str, state: State) -> Result<(Client, Arc<AppState>)> {
+    let http = Http::new(token);
+
+    // Mentioning the bot works as a prefix too, which needs its user ID
+    let bot_id = http.get_current_user().await?.id;
+
+    // The bot's owner can use admin commands
+    let owner = http.get_current_application_info().await?.owner.id;
+
+    let app = AppState::new(state, owner);
+
+    let client = Client::builder(token, GatewayIntents::all())
+        .type_map_insert::<AppState>(Arc::clone(&app))
+        .event_handler(Handler::new(Arc::clone(&app)))
         .framework(
             StandardFramework::new()
                 .configure(|c| {
-                    c.prefix(CMD_PREFIX)
+                    // Guilds can change the prefix, so it's always dynamic
+                    c.prefix("")
+                        .dynamic_prefix(dynamic_prefix_hook)
+                        .on_mention(Some(bot_id))
+                        .owners(iter::once(owner).collect())
+                        // Typing command names exactly is fiddly on mobile
+                        .case_insensitivity(true)
                         // Disable argument delimiters
                         .delimiters::<Delimiter, _>(iter::empty())
                 })
                 .group(&cmd::GENERAL_GROUP)
+                .group(&cmd::challenge::CHALLENGES_GROUP)
+                .group(&cmd::group::GROUPS_GROUP)
+                .group(&cmd::manage::MANAGE_GROUP)
+                .group(&cmd::profile::PROFILES_GROUP)
+                .group(&cmd::routine::ROUTINE_GROUP)
+                .group(&cmd::guild::GUILDSETTINGS_GROUP)
+                .group(&cmd::admin::ADMIN_GROUP)
                 .help(&cmd::HELP)
                 .before(before_command_hook)
                 .after(after_command_hook)
                 .unrecognised_command(unrecognized_command_hook)
                 .prefix_only(prefix_only_hook),
         )
-        .await
+        .await?;
+    Ok((client, app))
 }
 
-/// Load saved state from previous run, schedule bedtime alerts accordingly, and
-/// store state in client context
-async fn client_load_state(client: &Client) {
-    // Load state from previous run
-    let state = State::load();
-
-    // Store state in context
-    client.data.write().await.insert::<State>(state);
+/// Schedule bedtime alerts and background jobs for the state loaded from the
+/// previous run
+async fn client_load_state(client: &Client, app: Arc<AppState>) {
+    let bot = Bot {
+        http: Arc::clone(&client.cache_and_http.http),
+        cache: Arc::clone(&client.cache_and_http.cache),
+        app,
+    };
+    alert::spawn(bot.clone());
+    audit::spawn();
+    clock::spawn();
+    state::spawn_saver(Arc::clone(&bot.app));
+    events::spawn(bot.clone());
 
     // Schedule bedtime alerts
-
-    let http = &client.cache_and_http.http;
-    let mut map = client.data.write().await;
-    let users = map
-        .get_mut::<State>()
-        .expect("No state in client")
-        .users
-        .iter_mut();
-
-    for (&user_id, ref mut user_info) in users {
-        let http = Arc::clone(http);
-        user_info.update_sched(http, user_id).await;
+    let mut state = bot.write().await;
+    for (&user_id, user_info) in state.users.iter_mut() {
+        user_info.update_sched(bot.clone(), user_id).await;
     }
+    drop(state);
+
+    // Schedule guild jobs
+    challenge::spawn_daily(bot.clone());
+    guild_sched::spawn(bot.clone());
+    target::spawn_hourly(bot.clone());
+    maintenance::spawn_daily(bot.clone());
+    dashboard::spawn(bot);
 }
 
 #[tokio::main]
@@ -121,11 +301,24 @@ async fn main() {
          environment variable",
     );
 
-    println!("Creating client...");
-    let mut client = create_client(&tok).await.expect("Couldn't create client");
+    // Held until the bot exits
+    let _lock = State::lock().expect(
+        "Couldn't lock the state file. Is another instance of the bot \
+         running?",
+    );
+
+    templates::init();
 
     println!("Loading previous state...");
-    client_load_state(&client).await;
+    let state = State::load().await;
+
+    println!("Creating client...");
+    let (mut client, app) = create_client(&tok, state)
+        .await
+        .expect("Couldn't create client");
+
+    println!("Scheduling jobs...");
+    client_load_state(&client, app).await;
 
     println!("Starting client...");
     client.start().await.expect("Error running client");
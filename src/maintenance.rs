@@ -0,0 +1,84 @@
+use crate::bot::Bot;
+
+use std::time::Duration;
+
+use chrono::Utc;
+use serenity::model::id::UserId;
+
+/// How often to run maintenance
+const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number of days after warning a stale user before they're forgotten
+const WARNING_DAYS: i64 = 7;
+
+/// DM a user that they'll be forgotten soon unless they interact with the bot
+async fn warn(bot: &Bot, id: UserId) -> serenity::Result<()> {
+    println!("Warning stale user '{}'", id);
+    let dm = id.create_dm_channel(&bot.http).await?;
+    dm.say(
+        &bot.http,
+        format!(
+            "I haven't heard from you in a while, so I'll forget your settings \
+             in {} days. Run any command, like `info`, to keep them.",
+            WARNING_DAYS
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Warn users who haven't interacted with the bot in a while, forget users who
+/// were warned and still didn't, and forget users with nothing worth keeping
+async fn run(bot: &Bot) {
+    let now = Utc::now();
+    let stale_after = bot.app.config.stale_after;
+
+    let to_warn: Vec<UserId> = {
+        let mut state = bot.write().await;
+
+        let mut to_warn = Vec::new();
+        let mut to_forget = Vec::new();
+        for (&id, user_info) in state.users.iter_mut() {
+            if now - user_info.last_seen() < stale_after {
+                continue;
+            }
+            match user_info.prune_warned() {
+                Some(warned) if now - warned >= chrono::Duration::days(WARNING_DAYS) => {
+                    to_forget.push(id)
+                }
+                Some(_) => {}
+                None => {
+                    user_info.warn_prune();
+                    to_warn.push(id);
+                }
+            }
+        }
+
+        let stale = state.forget(bot.clone(), &to_forget).await;
+        let idle = state.prune();
+        state.save();
+        println!(
+            "Maintenance forgot {} stale and {} idle users, {} in total",
+            stale, idle, state.pruned
+        );
+
+        to_warn
+    };
+
+    for id in to_warn {
+        if let Err(err) = warn(bot, id).await {
+            println!("Error warning stale user: {}", err);
+        }
+    }
+}
+
+/// Run maintenance once a day, starting now, forever
+pub fn spawn_daily(bot: Bot) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(INTERVAL);
+        loop {
+            interval.tick().await;
+            run(&bot).await;
+        }
+    });
+}
@@ -0,0 +1,146 @@
+use crate::bot::Bot;
+use crate::state::State;
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::interactions::message_component::ButtonStyle, model::prelude::*, prelude::*, Result,
+};
+
+/// How long a manager has to respond to a request
+const TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Custom ID of the button accepting a request to manage a user
+const ACCEPT_ID: &str = "manager_accept";
+
+/// Custom ID of the button declining a request to manage a user
+const DECLINE_ID: &str = "manager_decline";
+
+/// Someone, like a parent, who a user lets change their bedtime settings
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Manager {
+    /// The manager's user ID
+    pub id: UserId,
+
+    /// Whether the manager agreed to manage the user
+    pub accepted: bool,
+}
+
+/// Check that someone may change a user's settings, meaning the user asked
+/// them to and they accepted
+pub fn check(state: &State, manager: UserId, user: UserId) -> std::result::Result<(), String> {
+    let manages = state
+        .users
+        .get(&user)
+        .and_then(|user_info| user_info.manager())
+        .is_some_and(|m| m.id == manager && m.accepted);
+    if manages {
+        Ok(())
+    } else {
+        Err(format!(
+            "You don't manage {}'s settings. They can ask you to with `manager @you`.",
+            user.mention()
+        ))
+    }
+}
+
+/// Ask a manager in a DM whether they agree to manage a user's settings, and
+/// record their answer
+async fn request_consent(ctx: &Context, user: UserId, manager: UserId) -> Result<()> {
+    let bot = Bot::of(ctx).await?;
+    let dm = manager.create_dm_channel(ctx).await?;
+
+    let mut prompt = dm
+        .send_message(&ctx.http, |m| {
+            m.content(format!(
+                "{} wants you to be able to set their bedtime and reminder \
+                 intensity. They can take that back at any time. Is that okay?",
+                user.mention()
+            ))
+            .components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id(ACCEPT_ID)
+                            .label("Sure")
+                            .style(ButtonStyle::Success)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(DECLINE_ID)
+                            .label("No thanks")
+                            .style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = prompt
+        .await_component_interaction(ctx)
+        .author_id(manager)
+        .timeout(TIMEOUT)
+        .await;
+
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => return prompt.edit(ctx, |m| m.components(|c| c)).await,
+    };
+
+    let accepted = interaction.data.custom_id == ACCEPT_ID;
+
+    {
+        let mut state = bot.write().await;
+        if let Some(user_info) = state.users.get_mut(&user) {
+            user_info.answer_manager(manager, accepted);
+            state.save();
+        }
+    }
+
+    let resp = if accepted {
+        "Got it. Change their settings with commands like `manage bedtime`."
+    } else {
+        "Okay, you won't manage their settings"
+    };
+
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(resp).components(|c| c))
+        })
+        .await?;
+
+    let resp = if accepted {
+        format!("{} agreed to manage your settings", manager.mention())
+    } else {
+        format!("{} declined to manage your settings", manager.mention())
+    };
+    user.create_dm_channel(ctx)
+        .await?
+        .say(&ctx.http, resp)
+        .await?;
+
+    Ok(())
+}
+
+/// Ask a manager for consent in the background, so the caller doesn't wait
+/// for them to respond
+pub fn spawn_request_consent(ctx: &Context, user: UserId, manager: UserId) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Err(err) = request_consent(&ctx, user, manager).await {
+            println!("Error requesting manager consent: {}", err);
+        }
+    });
+}
+
+/// Tell a user that their manager changed their settings
+pub async fn notify(bot: &Bot, user: UserId, manager: UserId, change: &str) {
+    let resp = format!("{} {}", manager.mention(), change);
+    let res = match user.create_dm_channel(&bot.http).await {
+        Ok(dm) => dm.say(&bot.http, resp).await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = res {
+        println!("Error telling user about manager change: {}", err);
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serenity::model::id::MessageId;
+
+/// Upper bounds of histogram buckets, in seconds
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Observations of one histogram with one label value
+#[derive(Default)]
+struct Series {
+    /// Number of observations in each bucket, not counting earlier buckets
+    buckets: [u64; BUCKETS.len()],
+
+    /// Number of observations
+    count: u64,
+
+    /// Sum of all observations, in seconds
+    sum: f64,
+}
+
+/// Latency histogram, exported in the Prometheus text format
+pub struct Histogram {
+    /// Metric name
+    name: &'static str,
+
+    /// Description of the metric
+    help: &'static str,
+
+    /// Name of the label splitting the histogram into series, if any
+    label: Option<&'static str>,
+
+    /// Series by label value
+    series: Mutex<BTreeMap<String, Series>>,
+}
+
+impl Histogram {
+    fn new(name: &'static str, help: &'static str, label: Option<&'static str>) -> Self {
+        Histogram {
+            name,
+            help,
+            label,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record how long something took, for the series with a label value
+    pub fn observe_labeled(&self, value: &str, took: Duration) {
+        let secs = took.as_secs_f64();
+        let mut series = self.series.lock().expect("Metrics lock poisoned");
+        let series = series.entry(value.to_string()).or_default();
+        if let Some(i) = BUCKETS.iter().position(|&bound| secs <= bound) {
+            series.buckets[i] += 1;
+        }
+        series.count += 1;
+        series.sum += secs;
+    }
+
+    /// Record how long something took
+    pub fn observe(&self, took: Duration) {
+        self.observe_labeled("", took)
+    }
+
+    /// Get the count and sum of observations in each series, as JSON
+    fn snapshot(&self) -> serde_json::Value {
+        let series = self.series.lock().expect("Metrics lock poisoned");
+        let series: serde_json::Map<_, _> = series
+            .iter()
+            .map(|(value, series)| {
+                let summary = serde_json::json!({
+                    "count": series.count,
+                    "sum_seconds": series.sum,
+                });
+                (value.clone(), summary)
+            })
+            .collect();
+        series.into()
+    }
+
+    /// Write the histogram in the Prometheus text format
+    fn render(&self, out: &mut String) {
+        writeln!(out, "# HELP {} {}", self.name, self.help).unwrap();
+        writeln!(out, "# TYPE {} histogram", self.name).unwrap();
+        let series = self.series.lock().expect("Metrics lock poisoned");
+        for (value, series) in series.iter() {
+            let label = match self.label {
+                Some(label) => format!("{}=\"{}\"", label, value),
+                None => String::new(),
+            };
+            let mut cumulative = 0;
+            let bounds = BUCKETS.iter().map(|bound| bound.to_string());
+            let counts = series.buckets.iter().map(|&count| {
+                cumulative += count;
+                cumulative
+            });
+            let buckets = bounds
+                .zip(counts)
+                .chain(std::iter::once(("+Inf".to_string(), series.count)));
+            for (bound, count) in buckets {
+                let le = format!("le=\"{}\"", bound);
+                let labels = fmt_labels(&[&label, &le]);
+                writeln!(out, "{}_bucket{} {}", self.name, labels, count).unwrap();
+            }
+            let labels = fmt_labels(&[&label]);
+            writeln!(out, "{}_sum{} {}", self.name, labels, series.sum).unwrap();
+            writeln!(out, "{}_count{} {}", self.name, labels, series.count).unwrap();
+        }
+    }
+}
+
+/// Format label pairs like `{a="1",b="2"}`, leaving out empty ones
+fn fmt_labels(pairs: &[&str]) -> String {
+    let pairs: Vec<&str> = pairs
+        .iter()
+        .copied()
+        .filter(|pair| !pair.is_empty())
+        .collect();
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// Metrics the bot keeps about itself
+pub struct Metrics {
+    /// How long commands take to run, by command
+    pub command_latency: Histogram,
+
+    /// How long saving state takes
+    pub state_save_latency: Histogram,
+
+    /// How long each try at DMing a sleep reminder takes
+    pub nag_dm_latency: Histogram,
+
+    /// When commands that are still running started
+    command_starts: Mutex<HashMap<MessageId, Instant>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            command_latency: Histogram::new(
+                "bedtime_command_seconds",
+                "Time taken to run a command",
+                Some("command"),
+            ),
+            state_save_latency: Histogram::new(
+                "bedtime_state_save_seconds",
+                "Time taken to save state to disk",
+                None,
+            ),
+            nag_dm_latency: Histogram::new(
+                "bedtime_nag_dm_seconds",
+                "Time taken to try sending a sleep reminder DM",
+                None,
+            ),
+            command_starts: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Metrics {
+    /// Get every histogram
+    fn histograms(&self) -> [&Histogram; 3] {
+        [
+            &self.command_latency,
+            &self.state_save_latency,
+            &self.nag_dm_latency,
+        ]
+    }
+
+    /// Remember that the command in a message started running
+    pub fn command_started(&self, msg: MessageId) {
+        self.command_starts
+            .lock()
+            .expect("Metrics lock poisoned")
+            .insert(msg, Instant::now());
+    }
+
+    /// Record how long the command in a message took to run. Returns how long
+    /// it took, if it was started.
+    pub fn command_finished(&self, msg: MessageId, cmd: &str) -> Option<Duration> {
+        let started = self
+            .command_starts
+            .lock()
+            .expect("Metrics lock poisoned")
+            .remove(&msg)?;
+        let took = started.elapsed();
+        self.command_latency.observe_labeled(cmd, took);
+        Some(took)
+    }
+
+    /// Get all metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for histogram in &self.histograms() {
+            histogram.render(&mut out);
+        }
+        out
+    }
+
+    /// Get the count and sum of observations of each metric, by label value,
+    /// as JSON
+    pub fn snapshot(&self) -> serde_json::Value {
+        let metrics: serde_json::Map<_, _> = self
+            .histograms()
+            .iter()
+            .map(|histogram| (histogram.name.to_string(), histogram.snapshot()))
+            .collect();
+        metrics.into()
+    }
+}
@@ -0,0 +1,73 @@
+use crate::bot::Bot;
+use crate::history::Night;
+use crate::templates;
+use crate::time::{self, HumanDuration, SleepWindow};
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serenity::model::id::UserId;
+
+/// How long after their wake time a user can still get a summary of the night
+/// before, in hours
+const SUMMARY_WINDOW_HOURS: i64 = 12;
+
+/// Whether it's morning after a night, meaning the end of the night's sleep
+/// window has passed but not too long ago
+pub fn is_morning_after(night: &Night, tz: Tz, goal: Duration, now: DateTime<Utc>) -> bool {
+    match SleepWindow::of(night.date, night.bedtime, tz, goal) {
+        Some(window) => {
+            now >= window.end && now < window.end + Duration::hours(SUMMARY_WINDOW_HOURS)
+        }
+        None => false,
+    }
+}
+
+/// Summarize how late the user went to sleep on a night, in their message
+/// pack if they picked one, or `None` if it's not known when they fell asleep
+pub fn summary(night: &Night, tz: Tz, pack: Option<&str>) -> Option<String> {
+    if night.skipped {
+        return None;
+    }
+    let late = night.fell_asleep? - time::bedtime_on(night.date, night.bedtime, tz)?;
+    let mut nags = match night.nags {
+        1 => "1 nag".to_string(),
+        n => format!("{} nags", n),
+    };
+    if night.gave_up {
+        nags.push_str(", then I gave up");
+    }
+    let summary = if late > Duration::zero() {
+        templates::render(
+            pack,
+            "morning.late",
+            "You went to sleep {late} past bedtime last night ({nags}).",
+            &[
+                ("bedtime", &night.bedtime),
+                ("minutes_late", &late.num_minutes()),
+                ("late", &HumanDuration(late)),
+                ("nags", &nags),
+            ],
+        )
+    } else {
+        templates::render(
+            pack,
+            "morning.on_time",
+            "You went to sleep on time last night. 🌟",
+            &[("bedtime", &night.bedtime)],
+        )
+    };
+    Some(summary)
+}
+
+/// DM a user their morning summary
+pub async fn send(bot: &Bot, user: UserId, summary: String) {
+    println!("Sending user '{}' a morning summary", user);
+    let res = async {
+        let dm = user.create_dm_channel(&bot.http).await?;
+        dm.say(&bot.http, summary).await
+    }
+    .await;
+    if let Err(err) = res {
+        println!("Error sending user morning summary: {}", err);
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serenity::model::id::{ChannelId, MessageId};
+
+/// Maximum number of sleep reminders remembered per user
+const MAX_RECORDS: usize = 200;
+
+/// Record of an attempt to send a user a sleep reminder
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NagRecord {
+    /// When the reminder was sent
+    pub at: DateTime<Utc>,
+
+    /// How many tries it took to send the reminder, or how many were made
+    /// before giving up
+    #[serde(default = "one")]
+    pub attempts: u32,
+
+    /// Why the reminder failed to send, if it did
+    pub error: Option<String>,
+
+    /// DM channel and ID of the message the reminder was sent or edited into,
+    /// if it was
+    #[serde(default)]
+    pub message: Option<(ChannelId, MessageId)>,
+}
+
+/// Number of tries assumed for records saved before tries were counted
+fn one() -> u32 {
+    1
+}
+
+/// Log of a user's most recent sleep reminders. This is shared between the
+/// user's state and their nag loop.
+#[derive(Clone, Default)]
+pub struct NagLog(Arc<Mutex<VecDeque<NagRecord>>>);
+
+impl NagLog {
+    /// Record an attempt to send a sleep reminder, forgetting the oldest
+    /// record if the log is full
+    pub fn record(
+        &self,
+        attempts: u32,
+        error: Option<String>,
+        message: Option<(ChannelId, MessageId)>,
+    ) {
+        let mut records = self.0.lock().expect("Nag log lock poisoned");
+        if records.len() == MAX_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(NagRecord {
+            at: Utc::now(),
+            attempts,
+            error,
+            message,
+        });
+    }
+
+    /// Get how many of the most recent reminders in a row failed to send
+    pub fn failure_streak(&self) -> usize {
+        let records = self.0.lock().expect("Nag log lock poisoned");
+        records
+            .iter()
+            .rev()
+            .take_while(|record| record.error.is_some())
+            .count()
+    }
+
+    /// Count the sleep reminders sent successfully since a time
+    pub fn delivered_since(&self, since: DateTime<Utc>) -> usize {
+        let records = self.0.lock().expect("Nag log lock poisoned");
+        records
+            .iter()
+            .filter(|record| record.at >= since && record.error.is_none())
+            .count()
+    }
+
+    /// Get the messages reminders since a time were sent in, each only once
+    pub fn messages_since(&self, since: DateTime<Utc>) -> Vec<(ChannelId, MessageId)> {
+        let records = self.0.lock().expect("Nag log lock poisoned");
+        let mut messages: Vec<(ChannelId, MessageId)> = records
+            .iter()
+            .filter(|record| record.at >= since)
+            .filter_map(|record| record.message)
+            .collect();
+        messages.dedup();
+        messages
+    }
+
+    /// Get up to `n` of the most recent records, oldest first
+    pub fn recent(&self, n: usize) -> Vec<NagRecord> {
+        let records = self.0.lock().expect("Nag log lock poisoned");
+        let skip = records.len().saturating_sub(n);
+        records.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Serialize for NagLog {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0
+            .lock()
+            .expect("Nag log lock poisoned")
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NagLog {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let records = VecDeque::deserialize(deserializer)?;
+        Ok(NagLog(Arc::new(Mutex::new(records))))
+    }
+}
@@ -0,0 +1,134 @@
+use crate::bot::Bot;
+use crate::locale;
+use crate::wizard;
+
+use std::time::Duration;
+
+use chrono_tz::Tz;
+use serenity::{
+    model::interactions::message_component::ButtonStyle, model::prelude::*, prelude::*, Result,
+};
+
+/// How long the welcome message buttons stay usable
+const TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Custom ID of the welcome message button that starts setup
+const START_ID: &str = "onboarding_start";
+
+/// Custom ID of the welcome message button that dismisses it
+const DISMISS_ID: &str = "onboarding_dismiss";
+
+/// Run the setup wizard for a user in a channel, apply the chosen settings, and
+/// reply with a summary. If the user came from a guild, its defaults are
+/// suggested. Otherwise, the time zone guessed from their locale is.
+pub async fn setup(
+    ctx: &Context,
+    channel: ChannelId,
+    user: UserId,
+    guild: Option<GuildId>,
+    locale: Option<Tz>,
+) -> Result<()> {
+    let bot = Bot::of(ctx).await?;
+    let defaults = bot.read().await.guild_config(guild);
+
+    let setup = match wizard::run(ctx, channel, user, &defaults, locale).await? {
+        Some(setup) => setup,
+        None => return Ok(()),
+    };
+
+    let mut state = bot.write().await;
+
+    let http = &ctx.http;
+
+    state
+        .users
+        .entry(user)
+        .or_default()
+        .setup(bot.clone(), user, setup.clone())
+        .await;
+
+    state.save();
+
+    channel
+        .send_message(http, |m| m.embed(|e| wizard::summary(e, &setup)))
+        .await?;
+
+    Ok(())
+}
+
+/// DM a user a welcome message with buttons offering to walk them through
+/// setup. If the user came from a guild, its defaults are suggested.
+pub async fn welcome(ctx: &Context, user: UserId, guild: Option<GuildId>) -> Result<()> {
+    println!("Onboarding user '{}'", user);
+
+    let dm = user.create_dm_channel(ctx).await?;
+
+    let mut prompt = dm
+        .send_message(&ctx.http, |m| {
+            m.content(
+                "Hi, I'm a bot that reminds you to go to bed! 🌙 \
+                 Want me to walk you through setting up sleep reminders?",
+            )
+            .components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id(START_ID)
+                            .label("Set me up")
+                            .style(ButtonStyle::Primary)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(DISMISS_ID)
+                            .label("Not now")
+                            .style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = prompt
+        .await_component_interaction(ctx)
+        .author_id(user)
+        .timeout(TIMEOUT)
+        .await;
+
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => return prompt.edit(ctx, |m| m.components(|c| c)).await,
+    };
+
+    let start = interaction.data.custom_id == START_ID;
+
+    let resp = if start {
+        "Let's get started!"
+    } else {
+        "No problem. You can run the `setup` command whenever you're ready."
+    };
+
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(resp).components(|c| c))
+        })
+        .await?;
+
+    if start {
+        // Button presses carry the user's client locale, which is a better
+        // guess than the guild's
+        let locale = locale::time_zone(&interaction.locale);
+        setup(ctx, dm.id, user, guild, locale).await?;
+    }
+
+    Ok(())
+}
+
+/// Send a user the welcome message in the background, so the caller doesn't
+/// wait for them to respond
+pub fn spawn_welcome(ctx: &Context, user: UserId, guild: Option<GuildId>) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Err(err) = welcome(&ctx, user, guild).await {
+            println!("Error onboarding user '{}': {}", user, err);
+        }
+    });
+}
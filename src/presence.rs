@@ -0,0 +1,249 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::model::gateway::{ClientStatus, Presence};
+use serenity::model::user::OnlineStatus;
+
+/// Number of minutes since a user's last message for them to count as
+/// recently active
+pub const RECENT_ACTIVITY_MINUTES: i64 = 15;
+
+/// Whether a user who's idle counts as awake. Many people go idle while
+/// watching videos, so it's up to them.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum IdlePolicy {
+    /// Idle counts as awake
+    #[default]
+    Awake,
+
+    /// Idle counts as asleep
+    Asleep,
+
+    /// Idle counts as awake only if the user was recently active
+    RecentActivity,
+}
+
+impl IdlePolicy {
+    /// All idle policies
+    pub const ALL: [IdlePolicy; 3] = [
+        IdlePolicy::Awake,
+        IdlePolicy::Asleep,
+        IdlePolicy::RecentActivity,
+    ];
+
+    /// Name used to select this policy in commands
+    pub fn name(self) -> &'static str {
+        match self {
+            IdlePolicy::Awake => "awake",
+            IdlePolicy::Asleep => "asleep",
+            IdlePolicy::RecentActivity => "activity",
+        }
+    }
+}
+
+impl fmt::Display for IdlePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdlePolicy::Awake => write!(f, "awake"),
+            IdlePolicy::Asleep => write!(f, "asleep"),
+            IdlePolicy::RecentActivity => write!(f, "awake if recently active"),
+        }
+    }
+}
+
+impl FromStr for IdlePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        IdlePolicy::ALL
+            .iter()
+            .copied()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("Unknown idle policy '{}'", s))
+    }
+}
+
+/// Kind of Discord client a user can be online from
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Client {
+    Desktop,
+    Mobile,
+    Web,
+}
+
+impl Client {
+    /// All kinds of clients
+    pub const ALL: [Client; 3] = [Client::Desktop, Client::Mobile, Client::Web];
+
+    /// Name used to select this client in commands
+    pub fn name(self) -> &'static str {
+        match self {
+            Client::Desktop => "desktop",
+            Client::Mobile => "mobile",
+            Client::Web => "web",
+        }
+    }
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Client {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Client::ALL
+            .iter()
+            .copied()
+            .find(|client| client.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("Unknown client '{}'", s))
+    }
+}
+
+/// A user's online status, overall and on each of their clients
+#[derive(Clone, Debug)]
+pub struct Status {
+    /// Status across all clients
+    pub overall: OnlineStatus,
+
+    /// Status on each client, if Discord sent it
+    pub clients: Option<ClientStatus>,
+}
+
+impl Status {
+    /// Status of a user who isn't online anywhere
+    pub fn offline() -> Self {
+        Status {
+            overall: OnlineStatus::Offline,
+            clients: None,
+        }
+    }
+
+    /// Get the status counting only some clients, or the overall status if
+    /// it's not known per client
+    fn on(&self, clients: &BTreeSet<Client>) -> OnlineStatus {
+        let per_client = match &self.clients {
+            Some(per_client) => per_client,
+            None => return self.overall,
+        };
+        clients
+            .iter()
+            .filter_map(|client| match client {
+                Client::Desktop => per_client.desktop,
+                Client::Mobile => per_client.mobile,
+                Client::Web => per_client.web,
+            })
+            .max_by_key(|&status| rank(status))
+            .unwrap_or(OnlineStatus::Offline)
+    }
+}
+
+impl From<&Presence> for Status {
+    fn from(presence: &Presence) -> Self {
+        Status {
+            overall: presence.status,
+            clients: presence.client_status.clone(),
+        }
+    }
+}
+
+/// Rank a status by how awake it suggests the user is
+fn rank(status: OnlineStatus) -> u8 {
+    match status {
+        OnlineStatus::Online | OnlineStatus::DoNotDisturb => 2,
+        OnlineStatus::Idle => 1,
+        _ => 0,
+    }
+}
+
+/// Whether a user counts as awake, given their status, how they want idle
+/// treated, which clients they want counted, if not all, and when they were
+/// last active
+pub fn is_awake(
+    status: &Status,
+    policy: IdlePolicy,
+    clients: Option<&BTreeSet<Client>>,
+    last_active: Option<DateTime<Utc>>,
+) -> bool {
+    let status = match clients {
+        Some(clients) => status.on(clients),
+        None => status.overall,
+    };
+    match status {
+        OnlineStatus::Offline | OnlineStatus::Invisible => false,
+        OnlineStatus::Idle => match policy {
+            IdlePolicy::Awake => true,
+            IdlePolicy::Asleep => false,
+            IdlePolicy::RecentActivity => last_active.is_some_and(|active| {
+                Utc::now() - active < Duration::minutes(RECENT_ACTIVITY_MINUTES)
+            }),
+        },
+        _ => true,
+    }
+}
+
+/// How long a user has to show signs of being awake before sleep reminders go
+/// out, so a brief blip online doesn't set them off
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct AwakeThreshold {
+    /// Minutes the user has to be online in a row
+    pub online_minutes: u32,
+
+    /// Minutes since the user's last message, reaction, or typing that still
+    /// count as them being up, even if they weren't online for long
+    pub active_minutes: u32,
+}
+
+/// When a user started counting as awake and was last active
+#[derive(Default)]
+struct SignalTimes {
+    online_since: Option<DateTime<Utc>>,
+    last_active: Option<DateTime<Utc>>,
+}
+
+/// Signs of a user being awake, since the bot started. This is shared between
+/// the user's state and their nag loop.
+#[derive(Clone, Default)]
+pub struct Signals(Arc<Mutex<SignalTimes>>);
+
+impl Signals {
+    /// Record whether the user counts as awake now, remembering since when
+    pub fn set_online(&self, online: bool) {
+        let mut times = self.0.lock().expect("Signals lock poisoned");
+        if !online {
+            times.online_since = None;
+        } else if times.online_since.is_none() {
+            times.online_since = Some(Utc::now());
+        }
+    }
+
+    /// Record that the user did something, like sending a message
+    pub fn active(&self) {
+        self.0.lock().expect("Signals lock poisoned").last_active = Some(Utc::now());
+    }
+
+    /// Get when the user was last active
+    pub fn last_active(&self) -> Option<DateTime<Utc>> {
+        self.0.lock().expect("Signals lock poisoned").last_active
+    }
+
+    /// Whether the user showed signs of being awake for long enough
+    pub fn sustained(&self, threshold: AwakeThreshold) -> bool {
+        let times = self.0.lock().expect("Signals lock poisoned");
+        let now = Utc::now();
+        let online = times.online_since.is_some_and(|since| {
+            now - since >= Duration::minutes(i64::from(threshold.online_minutes))
+        });
+        let active = times.last_active.is_some_and(|active| {
+            now - active < Duration::minutes(i64::from(threshold.active_minutes))
+        });
+        online || active
+    }
+}
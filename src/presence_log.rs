@@ -0,0 +1,53 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of awake and asleep transitions remembered per user
+const MAX_TRANSITIONS: usize = 1000;
+
+/// Moment a user started or stopped counting as awake, from their online
+/// status
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Transition {
+    /// When it happened
+    pub at: DateTime<Utc>,
+
+    /// Whether the user counts as awake from then on
+    pub awake: bool,
+}
+
+/// Log of a user's most recent transitions between awake and asleep, the raw
+/// data behind when they actually went to bed
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PresenceLog(VecDeque<Transition>);
+
+impl PresenceLog {
+    /// Record whether the user counts as awake, if it changed since the last
+    /// transition, forgetting the oldest one if the log is full. Returns
+    /// whether it changed.
+    pub fn record(&mut self, awake: bool) -> bool {
+        if self.0.back().is_some_and(|last| last.awake == awake) {
+            return false;
+        }
+        if self.0.len() == MAX_TRANSITIONS {
+            self.0.pop_front();
+        }
+        self.0.push_back(Transition {
+            at: Utc::now(),
+            awake,
+        });
+        true
+    }
+
+    /// Get the most recent transition, if any
+    pub fn last(&self) -> Option<Transition> {
+        self.0.back().copied()
+    }
+
+    /// Get all remembered transitions, oldest first
+    pub fn all(&self) -> Vec<Transition> {
+        self.0.iter().copied().collect()
+    }
+}
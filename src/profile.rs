@@ -0,0 +1,104 @@
+use crate::intensity::Intensity;
+use crate::time::Time;
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of settings profiles per user
+pub const MAX_PROFILES: usize = 10;
+
+/// Maximum length of a settings profile name
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Largest settings export that can be imported from a URL, in bytes
+const MAX_IMPORT_BYTES: usize = 64 * 1024;
+
+/// How long to wait for a settings export to download
+const IMPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Named bundle of a user's schedule settings, like one for work and one for
+/// vacation, that they can switch between at once
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// Time zone
+    pub time_zone: Option<Tz>,
+
+    /// Bedtime
+    pub bedtime: Option<Time>,
+
+    /// Sleep goal, in minutes
+    pub sleep_goal: Option<u32>,
+
+    /// How insistent sleep reminders are
+    pub intensity: Intensity,
+
+    /// Bluntest tone sleep reminders can escalate to
+    pub max_tone: Option<u8>,
+
+    /// Number of minutes around bedtime sleep reminders may randomly start
+    pub fuzz: Option<u32>,
+
+    /// Local dates of nights to skip sleep reminders for
+    pub skipped: BTreeSet<NaiveDate>,
+
+    /// Wind-down routine
+    pub routine: Vec<String>,
+}
+
+/// Normalize a profile name, so lookups ignore case and surrounding whitespace
+pub fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Download settings exported with `export url`. Only links to this bot's
+/// dashboard are fetched, without following redirects. This only parses the
+/// settings, so they still need checking before they're used. `dashboard` is
+/// the dashboard's URL, if it's set up.
+pub async fn fetch(url: &str, dashboard: Option<&str>) -> Result<Profile, String> {
+    let url = reqwest::Url::parse(url.trim().trim_matches(|c| c == '<' || c == '>'))
+        .map_err(|_| "That isn't a valid link")?;
+    let dashboard = dashboard
+        .and_then(|dashboard| reqwest::Url::parse(dashboard).ok())
+        .ok_or("Links aren't set up on this bot")?;
+    if !matches!(url.scheme(), "http" | "https") || url.origin() != dashboard.origin() {
+        return Err("Use a link made with `export url`".to_string());
+    }
+
+    let failed = "Couldn't download your settings".to_string();
+    let client = reqwest::Client::builder()
+        .timeout(IMPORT_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|_| failed.clone())?;
+    let mut resp = client
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|_| failed.clone())?;
+
+    let too_big = format!(
+        "That's too big to be a settings export. The limit is {} KB.",
+        MAX_IMPORT_BYTES / 1024
+    );
+    if resp
+        .content_length()
+        .is_some_and(|len| len > MAX_IMPORT_BYTES as u64)
+    {
+        return Err(too_big);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await.map_err(|_| failed.clone())? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_IMPORT_BYTES {
+            return Err(too_big);
+        }
+    }
+
+    serde_json::from_slice(&body)
+        .map_err(|err| format!("That doesn't look like a settings export: {}", err))
+}
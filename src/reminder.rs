@@ -0,0 +1,68 @@
+use crate::bot::Bot;
+use crate::events::{self, Event};
+use crate::send_queue;
+use crate::supervisor;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+
+/// Maximum number of pending reminders per user
+pub const MAX_REMINDERS: usize = 10;
+
+/// Maximum length of a reminder's text
+pub const MAX_TEXT_LEN: usize = 200;
+
+/// One-off reminder a user asked for
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    /// When to send the reminder
+    pub at: DateTime<Utc>,
+
+    /// What to remind the user of
+    pub text: String,
+}
+
+/// DM a user one-off reminder
+async fn send(bot: &Bot, id: UserId, reminder: &Reminder) -> serenity::Result<()> {
+    println!("Sending user '{}' a reminder", id);
+    send_queue::wait_turn().await;
+    let dm = id.create_dm_channel(&bot.http).await?;
+    dm.say(&bot.http, format!("⏰ {}", reminder.text)).await?;
+    Ok(())
+}
+
+/// Get a user's pending one-off reminders, soonest first
+async fn pending(bot: &Bot, id: UserId) -> Vec<Reminder> {
+    let state = bot.read().await;
+    let mut reminders = state
+        .users
+        .get(&id)
+        .map_or_else(Vec::new, |user_info| user_info.reminders().to_vec());
+    reminders.sort_by_key(|reminder| reminder.at);
+    reminders
+}
+
+/// Send a user their one-off reminders as they come due, forgetting each one
+/// once it's sent. Restarts from the reminders still pending if it crashes.
+pub fn sched_reminders(bot: Bot, id: UserId) -> tokio::task::JoinHandle<()> {
+    supervisor::supervise(id, "reminder schedule", move || {
+        let bot = bot.clone();
+        async move {
+            for reminder in pending(&bot, id).await {
+                if let Ok(wait) = (reminder.at - Utc::now()).to_std() {
+                    tokio::time::sleep(wait).await;
+                }
+
+                if let Err(err) = send(&bot, id, &reminder).await {
+                    println!("Error sending user reminder: {}", err);
+                }
+
+                events::send(Event::RemindersSent {
+                    user: id,
+                    until: reminder.at,
+                });
+            }
+        }
+    })
+}
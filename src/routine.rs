@@ -0,0 +1,148 @@
+use crate::bot::Bot;
+
+use std::sync::{Arc, Mutex};
+
+use serenity::{
+    builder::CreateComponents,
+    model::interactions::message_component::{ButtonStyle, MessageComponentInteraction},
+    model::prelude::*,
+    prelude::*,
+    Result,
+};
+
+/// Maximum number of items in a wind-down routine
+pub const MAX_ITEMS: usize = 10;
+
+/// Maximum length of a wind-down routine item, which is the longest button
+/// label Discord allows
+pub const MAX_ITEM_LEN: usize = 80;
+
+/// Number of checklist buttons per row
+const BUTTONS_PER_ROW: usize = 5;
+
+/// Prefix of the custom IDs of the checklist buttons. The full ID is
+/// `<prefix>:<item index>`.
+const ITEM_ID_PREFIX: &str = "routine";
+
+/// Which items of a user's wind-down routine they checked off tonight. This
+/// is shared between the user's state and their nag loop.
+#[derive(Clone, Default)]
+pub struct Progress(Arc<Mutex<Vec<bool>>>);
+
+impl Progress {
+    /// Uncheck every item, for a routine with `len` items
+    pub fn reset(&self, len: usize) {
+        *self.0.lock().expect("Routine lock poisoned") = vec![false; len];
+    }
+
+    /// Check or uncheck an item. Returns `false` if there's no such item.
+    pub fn toggle(&self, i: usize) -> bool {
+        let mut checked = self.0.lock().expect("Routine lock poisoned");
+        match checked.get_mut(i) {
+            Some(item) => {
+                *item = !*item;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get which items are checked
+    pub fn checked(&self) -> Vec<bool> {
+        self.0.lock().expect("Routine lock poisoned").clone()
+    }
+
+    /// Whether there's a routine tonight and every item in it is checked
+    pub fn is_done(&self) -> bool {
+        let checked = self.0.lock().expect("Routine lock poisoned");
+        !checked.is_empty() && checked.iter().all(|&item| item)
+    }
+}
+
+/// Format a wind-down checklist as message content
+fn content(items: &[String], checked: &[bool]) -> String {
+    let lines: Vec<String> = items
+        .iter()
+        .zip(checked)
+        .map(|(item, &checked)| format!("{} {}", if checked { "✅" } else { "⬜" }, item))
+        .collect();
+    let footer = if checked.iter().all(|&checked| checked) {
+        "All done. Sleep well! 🌙"
+    } else {
+        "Check everything off and I'll stop reminding you."
+    };
+    format!("Time to wind down 🛁\n{}\n\n{}", lines.join("\n"), footer)
+}
+
+/// Build checklist buttons, one per item
+fn components<'a>(
+    c: &'a mut CreateComponents,
+    items: &[String],
+    checked: &[bool],
+) -> &'a mut CreateComponents {
+    let buttons: Vec<_> = items.iter().zip(checked).enumerate().collect();
+    for row in buttons.chunks(BUTTONS_PER_ROW) {
+        c.create_action_row(|r| {
+            for &(i, (item, &checked)) in row {
+                r.create_button(|b| {
+                    b.custom_id(format!("{}:{}", ITEM_ID_PREFIX, i))
+                        .label(item)
+                        .style(if checked {
+                            ButtonStyle::Success
+                        } else {
+                            ButtonStyle::Secondary
+                        })
+                });
+            }
+            r
+        });
+    }
+    c
+}
+
+/// DM a user their wind-down routine as a checklist, with nothing checked
+pub async fn send_checklist(bot: &Bot, user: UserId, items: &[String]) -> Result<()> {
+    let checked = vec![false; items.len()];
+    let dm = user.create_dm_channel(&bot.http).await?;
+    dm.send_message(&bot.http, |m| {
+        m.content(content(items, &checked))
+            .components(|c| components(c, items, &checked))
+    })
+    .await?;
+    Ok(())
+}
+
+/// Check or uncheck the item of a checklist button, ignoring interactions
+/// with other buttons
+pub async fn handle(ctx: &Context, interaction: &MessageComponentInteraction) -> Result<()> {
+    let bot = Bot::of(ctx).await?;
+    let i = match interaction.data.custom_id.split_once(':') {
+        Some((ITEM_ID_PREFIX, i)) => match i.parse::<usize>() {
+            Ok(i) => i,
+            Err(_) => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+
+    let checklist = {
+        let state = bot.read().await;
+        state
+            .users
+            .get(&interaction.user.id)
+            .and_then(|user_info| user_info.toggle_routine_item(i))
+    };
+
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| match &checklist {
+                    Some((items, checked)) => d
+                        .content(content(items, checked))
+                        .components(|c| components(c, items, checked)),
+                    None => d.content("This checklist is out of date").components(|c| c),
+                })
+        })
+        .await?;
+
+    Ok(())
+}
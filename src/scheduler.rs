@@ -0,0 +1,131 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serenity::model::id::UserId;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::oneshot;
+
+/// Longest the scheduler sleeps at once, so it notices when the system clock
+/// is changed
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Fewest deadlines kept before the scheduler bothers dropping those of jobs
+/// that were stopped
+const MIN_COMPACT_LEN: usize = 64;
+
+/// User's job waiting for a deadline
+struct Entry {
+    at: DateTime<Utc>,
+    user: UserId,
+    wake: oneshot::Sender<()>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.user).cmp(&(other.at, other.user))
+    }
+}
+
+/// Handle to the scheduler, which users' jobs wait on for their deadlines
+pub struct Scheduler {
+    /// Queue of deadlines waiting to be added to the heap
+    queue: UnboundedSender<Entry>,
+
+    /// Deadlines of waiting jobs, earliest first
+    heap: Arc<Mutex<BinaryHeap<Reverse<Entry>>>>,
+}
+
+impl Scheduler {
+    /// Run the scheduler, which keeps every waiting job's deadline in a heap
+    /// and sleeps until the earliest one. Deadlines of jobs that were stopped
+    /// are dropped whenever the heap doubles in size, so rescheduling users
+    /// over and over doesn't pile them up.
+    pub fn spawn() -> Self {
+        let (queue, mut rx) = mpsc::unbounded_channel();
+        let heap: Arc<Mutex<BinaryHeap<Reverse<Entry>>>> = Arc::default();
+
+        let shared = Arc::clone(&heap);
+        tokio::spawn(async move {
+            let heap = shared;
+            // Size of the heap right after stopped jobs were last dropped
+            let mut compacted_len = 0;
+            loop {
+                let wait = {
+                    let mut heap = heap.lock().expect("Scheduler heap lock poisoned");
+                    let now = Utc::now();
+                    while heap.peek().is_some_and(|Reverse(entry)| entry.at <= now) {
+                        let Reverse(entry) = heap.pop().expect("Scheduler heap empty");
+                        // Fails if the job stopped waiting, which is fine
+                        let _ = entry.wake.send(());
+                    }
+
+                    heap.peek().map(|Reverse(entry)| {
+                        (entry.at - now).to_std().unwrap_or_default().min(MAX_SLEEP)
+                    })
+                };
+                let entry = match wait {
+                    Some(wait) => match tokio::time::timeout(wait, rx.recv()).await {
+                        Ok(entry) => entry,
+                        Err(_) => continue,
+                    },
+                    None => rx.recv().await,
+                };
+                let entry = match entry {
+                    Some(entry) => entry,
+                    None => return,
+                };
+
+                let mut heap = heap.lock().expect("Scheduler heap lock poisoned");
+                heap.push(Reverse(entry));
+                if heap.len() > 2 * compacted_len.max(MIN_COMPACT_LEN) {
+                    // A closed sender means the job stopped waiting
+                    heap.retain(|Reverse(entry)| !entry.wake.is_closed());
+                    compacted_len = heap.len();
+                }
+            }
+        });
+
+        Scheduler { queue, heap }
+    }
+
+    /// Wait until `at`. Users' jobs wait here instead of polling, so however
+    /// many users there are, only the scheduler wakes up, once per deadline.
+    pub async fn wait_until(&self, user: UserId, at: DateTime<Utc>) {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.queue.send(Entry { at, user, wake: tx });
+        if rx.await.is_err() {
+            // The scheduler is gone, so wait alone rather than not at all
+            tokio::time::sleep((at - Utc::now()).to_std().unwrap_or_default()).await;
+        }
+    }
+
+    /// Get the deadlines jobs are waiting for, earliest first, with the users
+    /// they're for
+    pub fn upcoming(&self) -> Vec<(DateTime<Utc>, UserId)> {
+        let heap = self.heap.lock().expect("Scheduler heap lock poisoned");
+        let mut upcoming: Vec<_> = heap
+            .iter()
+            .filter(|Reverse(entry)| !entry.wake.is_closed())
+            .map(|Reverse(entry)| (entry.at, entry.user))
+            .collect();
+        upcoming.sort();
+        upcoming
+    }
+}
@@ -0,0 +1,24 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minimum time between scheduled DMs, keeping well under Discord's global rate
+/// limit, since each one takes a request to open the DM channel and another to
+/// send the message
+const SEND_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    /// Earliest time the next scheduled DM can go out
+    static ref NEXT_SLOT: Mutex<Instant> = Mutex::new(Instant::now());
+}
+
+/// Wait for a turn to send a scheduled DM. When many users share a bedtime,
+/// this spreads their reminders out instead of sending them all at once.
+pub async fn wait_turn() {
+    let slot = {
+        let mut next = NEXT_SLOT.lock().expect("Send queue lock poisoned");
+        let slot = (*next).max(Instant::now());
+        *next = slot + SEND_INTERVAL;
+        slot
+    };
+    tokio::time::sleep_until(slot.into()).await;
+}
@@ -0,0 +1,46 @@
+use crate::history::Night;
+
+use chrono::{Duration, NaiveDate};
+
+/// How long users are assumed to want to sleep until they set a goal, in
+/// minutes
+pub const DEFAULT_GOAL_MINUTES: u32 = 8 * 60;
+
+/// Number of nights sleep debt is added up over
+pub const DEBT_NIGHTS: i64 = 7;
+
+/// Weekly sleep debt above which bedtime suggestions move earlier, in minutes
+const HIGH_DEBT_MINUTES: i64 = 3 * 60;
+
+/// How much earlier bedtime suggestions move at most to pay off sleep debt, in
+/// minutes
+const MAX_CATCH_UP_MINUTES: i64 = 30;
+
+/// Get how much less than their goal the user slept on a night, or more if
+/// it's negative. Returns `None` if it's not known how long they slept.
+pub fn night_debt(night: &Night, goal: Duration) -> Option<Duration> {
+    Some(goal - night.slept()?)
+}
+
+/// Add up the sleep debt of the nights on or after a date. Nights where it's
+/// not known how long the user slept don't count. Returns `None` if no nights
+/// count.
+pub fn debt_since(nights: &[Night], goal: Duration, date: NaiveDate) -> Option<Duration> {
+    nights
+        .iter()
+        .filter(|night| night.date >= date)
+        .filter_map(|night| night_debt(night, goal))
+        .fold(None, |total, debt| {
+            Some(total.unwrap_or_else(Duration::zero) + debt)
+        })
+}
+
+/// Get how much earlier to go to bed each night to start paying off a week's
+/// sleep debt, or `None` if the debt isn't high enough to bother
+pub fn catch_up(debt: Duration) -> Option<Duration> {
+    if debt.num_minutes() < HIGH_DEBT_MINUTES {
+        return None;
+    }
+    let per_night = (debt.num_minutes() / DEBT_NIGHTS).min(MAX_CATCH_UP_MINUTES);
+    Some(Duration::minutes(per_night))
+}
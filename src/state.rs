@@ -1,12 +1,34 @@
+use crate::alert;
+use crate::app::AppState;
+use crate::bot::Bot;
+use crate::group::{Group, GroupBedtime};
+use crate::guild_config::GuildConfig;
 use crate::user_info::UserInfo;
 
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
-use serenity::{model::id::UserId, prelude::*};
+use serenity::model::id::{GuildId, MessageId, UserId};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Number of days without activity after which users with no settings are
+/// forgotten
+const PRUNE_AFTER_DAYS: i64 = 90;
+
+/// Most opt-in messages remembered at once. Past this, the oldest ones stop
+/// working.
+const MAX_OPT_IN_MESSAGES: usize = 100;
+
+/// State serialized for saving
+type Snapshot = Vec<u8>;
 
 lazy_static! {
     /// Path to the state save file
@@ -15,44 +37,431 @@ lazy_static! {
         let path = PathBuf::from(path);
         path.join("state.json")
     };
+
+    /// Path to the file locked while an instance of the bot uses the state
+    /// file
+    static ref LOCK_PATH: PathBuf = STATE_PATH.with_extension("json.lock");
+
+    /// Whether to keep users' sleep history out of the state file, set with
+    /// the `OMIT_HISTORY` environment variable
+    static ref OMIT_HISTORY: bool = env::var("OMIT_HISTORY").is_ok_and(|v| v == "1");
+
+    /// Serialized states waiting to be written by the save task
+    static ref SAVES: (UnboundedSender<Snapshot>, Mutex<Option<UnboundedReceiver<Snapshot>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, Mutex::new(Some(rx)))
+    };
+}
+
+/// Whether users' sleep history should be left out when saving state, so a
+/// leaked state file reveals less. It's then lost on restart.
+pub fn omit_history<T>(_: &T) -> bool {
+    *OMIT_HISTORY
+}
+
+/// Exclusive lock on the state file, released when dropped or when the process
+/// exits
+pub struct StateLock {
+    _file: File,
 }
 
 /// Data containing the bot's state. This is serialized to a file as it's
 /// updated.
 #[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct State {
     /// Map of user IDs to per-user state
     pub users: HashMap<UserId, UserInfo>,
+
+    /// Map of guild IDs to per-guild settings
+    pub guilds: HashMap<GuildId, GuildConfig>,
+
+    /// Map of normalized group names to groups of users sharing a bedtime
+    pub groups: HashMap<String, Group>,
+
+    /// Users who have interacted with the bot
+    pub contacted: HashSet<UserId>,
+
+    /// Guild messages that users can react to for an onboarding DM
+    pub opt_in_messages: HashSet<MessageId>,
+
+    /// Number of users forgotten by maintenance over the bot's lifetime
+    pub pruned: u64,
+
+    /// Base64 key that link tokens are signed with, or empty if none were
+    /// minted yet
+    pub link_key: String,
+
+    /// Whether the operator paused sleep reminders for everyone, for
+    /// maintenance
+    pub paused: bool,
+
+    /// Users the operator paused sleep reminders for, for maintenance
+    pub paused_users: HashSet<UserId>,
 }
 
 impl State {
-    /// Serialize state to a file. This should be called whenever `State` is
-    /// updated.
+    /// Get the settings for a guild, or the defaults if there's no guild or it
+    /// has no settings
+    pub fn guild_config(&self, guild: Option<GuildId>) -> GuildConfig {
+        guild
+            .and_then(|guild| self.guilds.get(&guild))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Record that a user interacted with the bot. Returns whether this is
+    /// their first contact and they haven't configured anything yet, meaning
+    /// they should be onboarded.
+    pub fn first_contact(&mut self, id: UserId) -> bool {
+        let first = self.contacted.insert(id);
+        let configured = self.users.get(&id).is_some_and(UserInfo::is_configured);
+        first && !configured
+    }
+
+    /// Remember an opt-in message, forgetting the oldest ones if there are
+    /// too many
+    pub fn add_opt_in_message(&mut self, id: MessageId) {
+        self.opt_in_messages.insert(id);
+        while self.opt_in_messages.len() > MAX_OPT_IN_MESSAGES {
+            // Message IDs increase over time, so the smallest is the oldest
+            let oldest = *self
+                .opt_in_messages
+                .iter()
+                .min()
+                .expect("Opt-in messages empty");
+            self.opt_in_messages.remove(&oldest);
+        }
+    }
+
+    /// Whether sleep reminders to a user are paused for maintenance. Their
+    /// nights still run as usual, but nothing is delivered.
+    pub fn is_paused(&self, id: UserId) -> bool {
+        self.paused || self.paused_users.contains(&id)
+    }
+
+    /// Record that a user interacted with the bot
+    pub fn touch(&mut self, id: UserId) {
+        if let Some(user_info) = self.users.get_mut(&id) {
+            user_info.seen();
+        }
+    }
+
+    /// Forget users with no settings and no recent activity. Returns how many
+    /// were forgotten.
+    pub fn prune(&mut self) -> usize {
+        let since = Utc::now().naive_utc().date() - Duration::days(PRUNE_AFTER_DAYS);
+        let before = self.users.len();
+        self.users.retain(|_, user_info| !user_info.is_idle(since));
+        let pruned = before - self.users.len();
+        self.pruned += pruned as u64;
+        pruned
+    }
+
+    /// Forget some users, taking them out of their groups first and dropping
+    /// them as anyone's manager or buddy. Returns how many were forgotten.
+    pub async fn forget(&mut self, bot: Bot, ids: &[UserId]) -> usize {
+        let mut forgotten = 0;
+        for &id in ids {
+            self.leave_group(bot.clone(), id).await;
+            if let Some(mut user_info) = self.users.remove(&id) {
+                user_info.unschedule();
+                forgotten += 1;
+            }
+            self.move_references(bot.clone(), id, None).await;
+        }
+        self.pruned += forgotten as u64;
+        forgotten
+    }
+
+    /// Take a user out of their group, if they're in one, deleting the group
+    /// if it's left empty. Returns the name of the group they left.
+    pub async fn leave_group(&mut self, bot: Bot, id: UserId) -> Option<String> {
+        let user_info = self.users.get_mut(&id)?;
+        let name = user_info.group()?.to_string();
+        user_info.set_group(bot, id, None, None).await;
+
+        if let Some(group) = self.groups.get_mut(&name) {
+            group.members.remove(&id);
+            if group.members.is_empty() {
+                self.groups.remove(&name);
+            } else if group.owner == Some(id) {
+                // Hand the group to someone still in it
+                group.owner = group.members.iter().next().copied();
+            }
+        }
+
+        Some(name)
+    }
+
+    /// Put a user in a group, creating it with them as its owner if it doesn't
+    /// exist, and taking them out of any group they were already in
+    pub async fn join_group(&mut self, bot: Bot, id: UserId, name: String) {
+        self.leave_group(bot.clone(), id).await;
+
+        let group = self.groups.entry(name.clone()).or_insert_with(|| Group {
+            owner: Some(id),
+            ..Group::default()
+        });
+        group.members.insert(id);
+        group.invited.remove(&id);
+        let bedtime = group.bedtime;
+
+        self.users
+            .entry(id)
+            .or_default()
+            .set_group(bot, id, Some(name), bedtime)
+            .await;
+    }
+
+    /// Move a user's settings, history, and streaks to another user ID, like
+    /// when they switch Discord accounts, replacing anything saved for the
+    /// other ID. Anyone they manage, buddy for, or share a group or challenge
+    /// with sees the new ID too. Returns whether there was anything to move.
+    pub async fn transfer(&mut self, bot: Bot, from: UserId, to: UserId) -> bool {
+        let mut user_info = match self.users.remove(&from) {
+            Some(user_info) => user_info,
+            None => return false,
+        };
+        user_info.unschedule();
+
+        self.leave_group(bot.clone(), to).await;
+        if let Some(mut replaced) = self.users.remove(&to) {
+            replaced.unschedule();
+        }
+
+        self.move_references(bot.clone(), from, Some(to)).await;
+
+        user_info.update_sched(bot, to).await;
+        self.users.insert(to, user_info);
+        true
+    }
+
+    /// Point everything else in the state that refers to a user at another
+    /// user ID, or drop those references if `to` is `None`. Returns the users
+    /// whose buddy changed, who need rescheduling.
+    fn replace_references(&mut self, from: UserId, to: Option<UserId>) -> Vec<UserId> {
+        if self.contacted.remove(&from) {
+            self.contacted.extend(to);
+        }
+        if self.paused_users.remove(&from) {
+            self.paused_users.extend(to);
+        }
+
+        for group in self.groups.values_mut() {
+            if group.members.remove(&from) {
+                group.members.extend(to);
+            }
+            if group.invited.remove(&from) {
+                group.invited.extend(to);
+            }
+            if group.owner == Some(from) {
+                group.owner = to;
+            }
+        }
+
+        let challenges = self
+            .guilds
+            .values_mut()
+            .filter_map(|config| config.challenge.as_mut());
+        for challenge in challenges {
+            if challenge.participants.remove(&from) {
+                challenge.participants.extend(to);
+            }
+        }
+
+        let mut changed = Vec::new();
+        for (&id, user_info) in &mut self.users {
+            if user_info.replace_contact(from, to) {
+                changed.push(id);
+            }
+        }
+        changed
+    }
+
+    /// Point everything else in the state that refers to a user at another
+    /// user ID, or drop those references if `to` is `None`, rescheduling
+    /// users whose buddy changed
+    async fn move_references(&mut self, bot: Bot, from: UserId, to: Option<UserId>) {
+        for id in self.replace_references(from, to) {
+            if let Some(user_info) = self.users.get_mut(&id) {
+                user_info.update_sched(bot.clone(), id).await;
+            }
+        }
+    }
+
+    /// Set a group's shared bedtime, rescheduling all of its members
+    pub async fn set_group_bedtime(&mut self, bot: Bot, name: &str, bedtime: GroupBedtime) {
+        let group = match self.groups.get_mut(name) {
+            Some(group) => group,
+            None => return,
+        };
+        group.bedtime = Some(bedtime);
+
+        for &member in &group.members {
+            self.users
+                .entry(member)
+                .or_default()
+                .set_group(bot.clone(), member, Some(name.to_string()), Some(bedtime))
+                .await;
+        }
+    }
+
+    /// Serialize state and queue it to be written to a file. This should be
+    /// called whenever `State` is updated.
     pub fn save(&self) {
-        let f = File::create(&*STATE_PATH);
-        let f = f.expect("Failed to create state file");
-        let f = BufWriter::new(f);
-        let v = serde_json::to_writer(f, self);
-        v.expect("Failed to write state");
+        let _span = tracing::info_span!("save_state").entered();
+        match serde_json::to_vec(self) {
+            // Sending only fails if the save task is gone, when the bot is
+            // shutting down anyway
+            Ok(bytes) => drop(SAVES.0.send(bytes)),
+            Err(err) => alert::report(format!("Failed to save state: {}", err)),
+        }
+    }
+
+    /// Lock the state file, so that a second instance of the bot can't
+    /// overwrite this one's saves and send everyone duplicate reminders.
+    /// Fails if another instance holds the lock.
+    pub fn lock() -> io::Result<StateLock> {
+        let f = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&*LOCK_PATH)?;
+        // SAFETY: the file descriptor is valid for as long as `f` is open
+        let res = unsafe { libc::flock(f.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(StateLock { _file: f })
     }
 
     /// Try to load state from a file, and use the default if the file does not
     /// exist.
-    pub fn load() -> Self {
-        let f = File::open(&*STATE_PATH);
-        match f {
-            Ok(f) => {
-                let f = BufReader::new(f);
-                let v = serde_json::from_reader(f);
-                v.expect("Failed to read state")
-            }
+    pub async fn load() -> Self {
+        match tokio::fs::read(&*STATE_PATH).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).expect("Failed to read state"),
             Err(_) => Self::default(),
         }
     }
 }
 
-/// Field of `serenity::prelude::Context::data` used to store the state in the
-/// context.
-impl TypeMapKey for State {
-    type Value = State;
+/// Write serialized state to the state file. It's written to a temporary file
+/// first, so a crash partway through can't leave the state file truncated.
+async fn write(bytes: &[u8]) -> io::Result<()> {
+    let tmp = STATE_PATH.with_extension("json.tmp");
+    tokio::fs::write(&tmp, bytes).await?;
+    tokio::fs::rename(&tmp, &*STATE_PATH).await
+}
+
+/// Spawn the task that writes saved state to disk, so file writes don't block
+/// the executor while the state lock is held
+pub fn spawn_saver(app: Arc<AppState>) {
+    let mut rx = SAVES
+        .1
+        .lock()
+        .expect("Save queue lock poisoned")
+        .take()
+        .expect("Save task already spawned");
+
+    tokio::spawn(async move {
+        while let Some(mut bytes) = rx.recv().await {
+            // Only the newest state needs writing
+            while let Ok(newer) = rx.try_recv() {
+                bytes = newer;
+            }
+            let started = Instant::now();
+            let res = write(&bytes).await;
+            app.metrics.state_save_latency.observe(started.elapsed());
+            if let Err(err) = res {
+                alert::report(format!("Failed to save state: {}", err));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::challenge::Challenge;
+
+    use std::collections::BTreeSet;
+
+    use serenity::model::id::ChannelId;
+
+    /// Set up a state where user 1 manages user 2, is user 3's buddy, takes
+    /// part in a challenge, and is invited to a group
+    fn connected_state() -> State {
+        let old = UserId(1);
+        let mut state = State::default();
+
+        let mut managed = UserInfo::default();
+        managed.set_manager(Some(old));
+        managed.answer_manager(old, true);
+        state.users.insert(UserId(2), managed);
+
+        let befriended: UserInfo =
+            serde_json::from_str(r#"{"buddy": {"id": 1, "after_minutes": 30, "accepted": true}}"#)
+                .expect("Invalid user info");
+        state.users.insert(UserId(3), befriended);
+
+        let mut group = Group::default();
+        group.members.insert(UserId(2));
+        group.owner = Some(UserId(2));
+        group.invited.insert(old);
+        state.groups.insert("house".to_string(), group);
+
+        let config = GuildConfig {
+            challenge: Some(Challenge {
+                channel: ChannelId(10),
+                start: Utc::now(),
+                end: Utc::now() + Duration::days(7),
+                participants: BTreeSet::from([old, UserId(2)]),
+            }),
+            ..GuildConfig::default()
+        };
+        state.guilds.insert(GuildId(20), config);
+
+        state.contacted.insert(old);
+        state.paused_users.insert(old);
+        state
+    }
+
+    #[test]
+    fn transfer_moves_references() {
+        let (old, new) = (UserId(1), UserId(4));
+        let mut state = connected_state();
+
+        let changed = state.replace_references(old, Some(new));
+
+        assert_eq!(changed, vec![UserId(3)]);
+        let manager = state.users[&UserId(2)].manager().expect("Manager dropped");
+        assert_eq!(manager.id, new);
+        assert!(manager.accepted);
+        let buddy = state.users[&UserId(3)].buddy().expect("Buddy dropped");
+        assert_eq!(buddy.id, new);
+        assert!(buddy.accepted);
+        assert_eq!(state.groups["house"].invited, BTreeSet::from([new]));
+        let challenge = state.guilds[&GuildId(20)].challenge.as_ref().unwrap();
+        assert_eq!(challenge.participants, BTreeSet::from([UserId(2), new]));
+        assert!(state.contacted.contains(&new) && !state.contacted.contains(&old));
+        assert!(state.paused_users.contains(&new) && !state.paused_users.contains(&old));
+    }
+
+    #[test]
+    fn forget_drops_references() {
+        let old = UserId(1);
+        let mut state = connected_state();
+
+        let changed = state.replace_references(old, None);
+
+        assert_eq!(changed, vec![UserId(3)]);
+        assert!(state.users[&UserId(2)].manager().is_none());
+        assert!(state.users[&UserId(3)].buddy().is_none());
+        assert!(state.groups["house"].invited.is_empty());
+        assert_eq!(state.groups["house"].owner, Some(UserId(2)));
+        let challenge = state.guilds[&GuildId(20)].challenge.as_ref().unwrap();
+        assert_eq!(challenge.participants, BTreeSet::from([UserId(2)]));
+        assert!(state.contacted.is_empty() && state.paused_users.is_empty());
+    }
 }
@@ -0,0 +1,71 @@
+use crate::history::Night;
+use crate::sleep_debt;
+use crate::time::Time;
+
+use chrono::{Duration, NaiveTime, Timelike};
+use chrono_tz::Tz;
+
+/// Number of recent nights looked at for suggestions
+const RECENT_NIGHTS: usize = 30;
+
+/// Minimum number of nights with a detected sleep time needed for a
+/// suggestion
+const MIN_NIGHTS: usize = 5;
+
+/// How long before the usual time the user falls asleep to suggest a bedtime,
+/// to leave time to wind down
+const WIND_DOWN_MINUTES: i64 = 25;
+
+/// Granularity suggested bedtimes are rounded to, in minutes
+const ROUND_MINUTES: i64 = 15;
+
+/// Bedtime suggested from a user's observed sleep patterns
+pub struct Suggestion {
+    /// Time the user usually falls asleep
+    pub usual: Time,
+
+    /// Suggested bedtime
+    pub bedtime: Time,
+
+    /// How much earlier the suggestion is to pay off a week of sleep debt, if
+    /// it's moved at all
+    pub catch_up: Option<Duration>,
+}
+
+/// Get the number of minutes after noon a time is, so times around midnight
+/// sort in order
+fn minutes_after_noon(time: NaiveTime) -> i64 {
+    let minutes = i64::from(time.hour() * 60 + time.minute());
+    (minutes + 12 * 60) % (24 * 60)
+}
+
+/// Suggest a bedtime from when a user was detected going to sleep on recent
+/// nights, in their time zone. After a week of high sleep debt, it's moved
+/// earlier. Returns `None` if there isn't enough data.
+pub fn suggest(nights: &[Night], tz: Tz, debt: Option<Duration>) -> Option<Suggestion> {
+    let mut asleep: Vec<i64> = nights
+        .iter()
+        .rev()
+        .take(RECENT_NIGHTS)
+        .filter_map(|night| night.fell_asleep)
+        .map(|at| minutes_after_noon(at.with_timezone(&tz).time()))
+        .collect();
+
+    if asleep.len() < MIN_NIGHTS {
+        return None;
+    }
+
+    asleep.sort_unstable();
+    let median = asleep[asleep.len() / 2];
+    let catch_up = debt.and_then(sleep_debt::catch_up);
+    let earlier = catch_up.map_or(0, |catch_up| catch_up.num_minutes());
+    let suggested =
+        (median - WIND_DOWN_MINUTES - earlier).div_euclid(ROUND_MINUTES) * ROUND_MINUTES;
+
+    let noon = Time(NaiveTime::from_hms(12, 0, 0));
+    Some(Suggestion {
+        usual: noon.shift(Duration::minutes(median)),
+        bedtime: noon.shift(Duration::minutes(suggested)),
+        catch_up,
+    })
+}
@@ -0,0 +1,59 @@
+use crate::alert;
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use serenity::model::id::UserId;
+use tokio::task::JoinHandle;
+
+/// How long to wait before restarting a task the first time it crashes
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Longest wait before restarting a task that keeps crashing. A task that ran
+/// this long before crashing is restarted with the initial wait again.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Task handle that aborts the task when dropped, so aborting a supervisor
+/// also stops the task it's supervising
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run a user's task made by `make`, restarting it with a growing delay if it
+/// panics, until it finishes normally. `what` describes the task in logs.
+pub fn supervise<F, Fut>(id: UserId, what: &'static str, make: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let mut task = AbortOnDrop(tokio::spawn(make()));
+            let err = match (&mut task.0).await {
+                Ok(()) => return,
+                Err(err) if err.is_panic() => err,
+                // Cancelled by something other than this supervisor
+                Err(_) => return,
+            };
+
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = INITIAL_BACKOFF;
+            }
+            alert::report(format!(
+                "The {} for user '{}' crashed ({}), restarting it in {}s",
+                what,
+                id,
+                err,
+                backoff.as_secs()
+            ));
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    })
+}
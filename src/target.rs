@@ -0,0 +1,79 @@
+use crate::bot::Bot;
+use crate::time::Time;
+
+use chrono::{Duration, NaiveDate, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// How often to check for scheduled changes to users' settings
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Most nights a user can take to reach their goal bedtime
+pub const MAX_NIGHTS: i64 = 365;
+
+/// Goal bedtime that a user's bedtime moves toward a little each night
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Target {
+    /// Bedtime to end up at
+    pub goal: Time,
+
+    /// Bedtime before moving toward the goal
+    pub from: Time,
+
+    /// Local date moving toward the goal started
+    pub start: NaiveDate,
+
+    /// Number of nights to take to reach the goal
+    pub nights: i64,
+}
+
+/// Get the number of minutes between two times of day, going whichever way
+/// around midnight is shorter
+fn minutes_between(from: Time, to: Time) -> i64 {
+    let minutes = |time: Time| i64::from(time.0.hour() * 60 + time.0.minute());
+    let diff = (minutes(to) - minutes(from)).rem_euclid(24 * 60);
+    if diff > 12 * 60 {
+        diff - 24 * 60
+    } else {
+        diff
+    }
+}
+
+impl Target {
+    /// Get the bedtime for the night of a local date
+    pub fn bedtime_on(&self, date: NaiveDate) -> Time {
+        let elapsed = (date - self.start).num_days().clamp(0, self.nights);
+        let total = minutes_between(self.from, self.goal);
+        self.from
+            .shift(Duration::minutes(total * elapsed / self.nights))
+    }
+
+    /// Whether the goal is reached by the night of a local date
+    pub fn is_reached(&self, date: NaiveDate) -> bool {
+        (date - self.start).num_days() >= self.nights
+    }
+
+    /// Get the local date the goal will be reached
+    pub fn end(&self) -> NaiveDate {
+        self.start + Duration::days(self.nights)
+    }
+}
+
+/// Apply scheduled changes to users' settings as days pass, like moving their
+/// bedtimes toward their targets, forever
+pub fn spawn_hourly(bot: Bot) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let mut state = bot.write().await;
+            let mut changed = false;
+            for (&id, user_info) in state.users.iter_mut() {
+                changed |= user_info.apply_scheduled(bot.clone(), id).await;
+            }
+            if changed {
+                state.save();
+            }
+        }
+    });
+}
@@ -0,0 +1,273 @@
+use crate::intensity::{self, Intensity};
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+/// Map of message names to templates
+type TemplateSet = HashMap<String, String>;
+
+/// Operator's replacements for the bot's messages, plus the message packs
+/// users can pick from
+#[derive(Default)]
+struct Templates {
+    /// Templates read from the JSON or YAML file named by the
+    /// `TEMPLATES_FILE` environment variable
+    base: TemplateSet,
+
+    /// Message packs read from the JSON and YAML files in the directory named
+    /// by the `MESSAGE_PACKS_DIR` environment variable, by lowercase file name
+    packs: BTreeMap<String, TemplateSet>,
+}
+
+lazy_static! {
+    /// Templates currently in use, replaced when they're reloaded
+    static ref TEMPLATES: RwLock<Templates> = RwLock::default();
+}
+
+/// Placeholders that nag templates can use
+const NAG_PLACEHOLDERS: &[&str] = &["user", "name", "bedtime", "minutes_late", "streak"];
+
+/// Key of the nag template for an intensity and tone, counting from 1
+pub fn nag_key(intensity: Intensity, tone: u8) -> String {
+    format!("nag.{}.{}", intensity.name(), tone)
+}
+
+/// Get the placeholders a template can use, or `None` if there's no template
+/// with that key
+fn placeholders(key: &str) -> Option<&'static [&'static str]> {
+    match key {
+        "good_night" | "good_morning" | "reminders_on" | "reminders_off" => Some(&["user"]),
+        "bedtime_set" | "bedtime_moved" => Some(&["user", "bedtime"]),
+        "bedtime_tonight" => Some(&["user", "in"]),
+        "time_zone_set" => Some(&["user", "time_zone"]),
+        "skipped_tonight" => Some(&["user", "night"]),
+        "morning.late" => Some(&["bedtime", "minutes_late", "late", "nags"]),
+        "morning.on_time" => Some(&["bedtime"]),
+        _ => {
+            let is_nag = Intensity::ALL.iter().any(|&intensity| {
+                (1..=intensity::TONES).any(|tone| nag_key(intensity, tone) == key)
+            });
+            is_nag.then_some(NAG_PLACEHOLDERS)
+        }
+    }
+}
+
+/// Get the names of the placeholders in a template, like `bedtime` in
+/// `Your bedtime is {bedtime}`
+fn placeholders_in(template: &str) -> Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed `{{` in '{}'", template))?;
+        names.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+    Ok(names)
+}
+
+/// Check that every template in a set has a known name and only uses the
+/// placeholders allowed for it
+fn check(templates: &TemplateSet) -> Result<(), String> {
+    for (key, template) in templates {
+        let allowed = placeholders(key).ok_or_else(|| format!("unknown template '{}'", key))?;
+        for name in placeholders_in(template)? {
+            if !allowed.contains(&name) {
+                return Err(format!(
+                    "template '{}' can't use placeholder {{{}}}. It can use: {}",
+                    key,
+                    name,
+                    allowed.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file is a templates file, by its extension
+fn is_templates_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("json" | "yaml" | "yml")
+    )
+}
+
+/// Read and check a JSON or YAML file of templates, going by its extension
+fn load_file(path: &Path) -> Result<TemplateSet, String> {
+    let err = |err: String| format!("{}: {}", path.display(), err);
+    let text = fs::read_to_string(path).map_err(|e| err(e.to_string()))?;
+    let templates: TemplateSet = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml" | "yml") => serde_yaml::from_str(&text).map_err(|e| err(e.to_string()))?,
+        _ => serde_json::from_str(&text).map_err(|e| err(e.to_string()))?,
+    };
+    check(&templates).map_err(err)?;
+    Ok(templates)
+}
+
+/// Read and check the templates file and message packs, if they're set
+fn load() -> Result<Templates, String> {
+    let base = match env::var("TEMPLATES_FILE") {
+        Ok(path) => load_file(Path::new(&path))?,
+        Err(_) => TemplateSet::new(),
+    };
+
+    let mut packs = BTreeMap::new();
+    if let Ok(dir) = env::var("MESSAGE_PACKS_DIR") {
+        let entries = fs::read_dir(&dir).map_err(|err| format!("{}: {}", dir, err))?;
+        for entry in entries {
+            let path = entry.map_err(|err| format!("{}: {}", dir, err))?.path();
+            if !is_templates_file(&path) {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_lowercase(),
+                None => continue,
+            };
+            if packs.insert(name.clone(), load_file(&path)?).is_some() {
+                return Err(format!(
+                    "{}: more than one message pack named '{}'",
+                    dir, name
+                ));
+            }
+        }
+    }
+
+    Ok(Templates { base, packs })
+}
+
+/// Load and check the templates file and message packs, so mistakes in them
+/// stop the bot at startup instead of the first time a message is sent
+pub fn init() {
+    let templates = load().unwrap_or_else(|err| panic!("Invalid templates: {}", err));
+    if !templates.base.is_empty() {
+        println!("Loaded {} message templates", templates.base.len());
+    }
+    if !templates.packs.is_empty() {
+        println!("Loaded {} message packs", templates.packs.len());
+    }
+    *TEMPLATES.write().expect("Templates lock poisoned") = templates;
+}
+
+/// Load the templates file and message packs again, so they can be changed
+/// without restarting the bot. If there's a mistake in them, the old ones are
+/// kept. Returns the names of the loaded message packs.
+pub fn reload() -> Result<Vec<String>, String> {
+    let templates = load()?;
+    let names = templates.packs.keys().cloned().collect();
+    *TEMPLATES.write().expect("Templates lock poisoned") = templates;
+    Ok(names)
+}
+
+/// Get the names of the message packs users can pick from
+pub fn pack_names() -> Vec<String> {
+    let templates = TEMPLATES.read().expect("Templates lock poisoned");
+    templates.packs.keys().cloned().collect()
+}
+
+/// Whether there's a message pack with a name
+pub fn has_pack(name: &str) -> bool {
+    let templates = TEMPLATES.read().expect("Templates lock poisoned");
+    templates.packs.contains_key(name)
+}
+
+/// Render a message from the user's message pack, if they picked one that
+/// has the message, then from the operator's template for it, or from
+/// `default` if neither has it, filling in placeholders with `vars`
+pub fn render(
+    pack: Option<&str>,
+    key: &str,
+    default: &str,
+    vars: &[(&str, &dyn Display)],
+) -> String {
+    let templates = TEMPLATES.read().expect("Templates lock poisoned");
+    let message = pack
+        .and_then(|pack| templates.packs.get(pack))
+        .and_then(|pack| pack.get(key))
+        .or_else(|| templates.base.get(key))
+        .map_or(default, String::as_str)
+        .to_string();
+    drop(templates);
+    fill(&message, vars)
+}
+
+/// Fill in the placeholders in a template with `vars` in one pass, so braces
+/// in the values are left alone. Unknown placeholders are kept as they are.
+fn fill(template: &str, vars: &[(&str, &dyn Display)]) -> String {
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let name = &rest[1..end];
+        match vars.iter().find(|(var, _)| *var == name) {
+            Some((_, value)) => message.push_str(&value.to_string()),
+            None => message.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    message.push_str(rest);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_placeholders() {
+        let vars: &[(&str, &dyn Display)] = &[("user", &"Sam"), ("minutes_late", &15)];
+        assert_eq!(
+            fill("{user}, you're {minutes_late} minutes late", vars),
+            "Sam, you're 15 minutes late"
+        );
+        assert_eq!(fill("{user} {user}", vars), "Sam Sam");
+        assert_eq!(fill("no placeholders", vars), "no placeholders");
+    }
+
+    #[test]
+    fn fill_leaves_values_alone() {
+        let vars: &[(&str, &dyn Display)] = &[("name", &"{bedtime}"), ("bedtime", &"10:30 PM")];
+        assert_eq!(
+            fill("{name} goes to bed at {bedtime}", vars),
+            "{bedtime} goes to bed at 10:30 PM"
+        );
+    }
+
+    #[test]
+    fn fill_keeps_unknown_and_unclosed() {
+        let vars: &[(&str, &dyn Display)] = &[("user", &"Sam")];
+        assert_eq!(fill("{other} {user}", vars), "{other} Sam");
+        assert_eq!(fill("{user} {unclosed", vars), "Sam {unclosed");
+        assert_eq!(fill("}{user}", vars), "}Sam");
+    }
+
+    #[test]
+    fn load_yaml_and_json() {
+        let dir = env::temp_dir().join(format!("bedtime-templates-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let yaml = dir.join("pirate.yaml");
+        fs::write(&yaml, "good_night: \"Sleep well, {user} 🏴‍☠️\"\n").unwrap();
+        let json = dir.join("pirate.json");
+        fs::write(&json, r#"{"good_night": "Sleep well, {user} 🏴‍☠️"}"#).unwrap();
+
+        let from_yaml = load_file(&yaml);
+        let from_json = load_file(&json);
+        let bad = dir.join("bad.yml");
+        fs::write(&bad, "good_night: \"{bedtime}\"\n").unwrap();
+        let from_bad = load_file(&bad);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(from_yaml.unwrap(), from_json.unwrap());
+        assert!(from_bad.is_err());
+    }
+}
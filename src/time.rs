@@ -1,7 +1,8 @@
 use std::fmt;
 use std::str::FromStr;
 
-use chrono::NaiveTime;
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
 /// Customized version of [`NaiveTime`]
@@ -11,6 +12,39 @@ pub struct Time(pub NaiveTime);
 impl Time {
     /// Format string to use on the inner [`NaiveTime`]
     const FMT: &'static str = "%I:%M %p";
+
+    /// Move the time forward (or backward, if negative) by some amount,
+    /// wrapping around midnight
+    pub fn shift(self, by: chrono::Duration) -> Self {
+        Time(self.0.overflowing_add_signed(by).0)
+    }
+
+    /// Get the next instant after `now` when it's this time of day in a time
+    /// zone
+    pub fn next_after(self, tz: Tz, now: DateTime<Utc>) -> DateTime<Utc> {
+        let mut date = now.with_timezone(&tz).naive_local().date();
+        loop {
+            if let Some(at) = self.on(date, tz) {
+                if at > now {
+                    return at;
+                }
+            }
+            date = date.succ_opt().expect("Date out of range");
+        }
+    }
+
+    /// Get the instant it's this time of day on a date in a time zone. Times
+    /// skipped by a daylight saving transition don't exist, so they fall back
+    /// to an hour later, and times repeated by one are the earlier instant.
+    fn on(self, date: NaiveDate, tz: Tz) -> Option<DateTime<Utc>> {
+        tz.from_local_datetime(&date.and_time(self.0))
+            .earliest()
+            .or_else(|| {
+                let later = self.0 + chrono::Duration::hours(1);
+                tz.from_local_datetime(&date.and_time(later)).earliest()
+            })
+            .map(|at| at.with_timezone(&Utc))
+    }
 }
 
 impl fmt::Display for Time {
@@ -22,7 +56,368 @@ impl fmt::Display for Time {
 impl FromStr for Time {
     type Err = chrono::format::ParseError;
 
+    /// Parse a time the way people tend to type it, like `10:30 PM`, `10pm`,
+    /// `22:30`, or `22`. Minutes are optional, and without AM or PM the hour
+    /// is on a 24-hour clock.
     fn from_str(s: &str) -> chrono::format::ParseResult<Self> {
-        NaiveTime::parse_from_str(s, Self::FMT).map(Time)
+        let s = s.trim().to_ascii_uppercase().replace(' ', "");
+        let (clock, period) = match s.strip_suffix("AM").or_else(|| s.strip_suffix("PM")) {
+            Some(clock) => (clock, &s[clock.len()..]),
+            None => (s.as_str(), ""),
+        };
+        let clock = if clock.contains(':') {
+            clock.to_string()
+        } else {
+            format!("{}:00", clock)
+        };
+        if period.is_empty() {
+            NaiveTime::parse_from_str(&clock, "%H:%M").map(Time)
+        } else {
+            NaiveTime::parse_from_str(&format!("{}{}", clock, period), "%I:%M%p").map(Time)
+        }
+    }
+}
+
+/// Hour of the day before which times count as part of the night that
+/// started the evening before
+const NIGHT_ROLLOVER_HOUR: u32 = 12;
+
+/// Get the night a local time falls in, named for the date of the evening the
+/// night starts on. Both 11:00 PM on the 5th and 12:30 AM on the 6th are in
+/// the night of the 5th.
+pub fn night_of<T: TimeZone>(at: &DateTime<T>) -> NaiveDate {
+    let at = at.naive_local();
+    if at.hour() < NIGHT_ROLLOVER_HOUR {
+        at.date().pred_opt().expect("Date out of range")
+    } else {
+        at.date()
+    }
+}
+
+/// Get when a bedtime is on a night, which is the next calendar day for
+/// bedtimes after midnight. A bedtime skipped by a daylight saving transition
+/// is an hour later that night, the same as for [`Time::next_after`].
+/// Returns `None` if it can't be placed on that night at all.
+pub fn bedtime_on(night: NaiveDate, bedtime: Time, tz: Tz) -> Option<DateTime<Utc>> {
+    let date = if bedtime.0.hour() < NIGHT_ROLLOVER_HOUR {
+        night.succ_opt().expect("Date out of range")
+    } else {
+        night
+    };
+    bedtime.on(date, tz)
+}
+
+/// Span of time a user is meant to be asleep on one night, from their bedtime
+/// to when they're expected to wake up. It may cross midnight.
+#[derive(Clone, Copy)]
+pub struct SleepWindow {
+    /// When the user should go to bed
+    pub start: DateTime<Utc>,
+
+    /// When the user is expected to wake up, after which they're no longer
+    /// reminded to sleep
+    pub end: DateTime<Utc>,
+}
+
+impl SleepWindow {
+    /// Get the sleep window of a night with a bedtime, lasting for the user's
+    /// sleep goal. Returns `None` if the bedtime can't be placed on that night
+    /// at all.
+    pub fn of(night: NaiveDate, bedtime: Time, tz: Tz, length: chrono::Duration) -> Option<Self> {
+        let start = bedtime_on(night, bedtime, tz)?;
+        Some(SleepWindow::starting_at(start, length))
+    }
+
+    /// Get the sleep window that starts at a time, lasting for the user's
+    /// sleep goal
+    pub fn starting_at(start: DateTime<Utc>, length: chrono::Duration) -> Self {
+        SleepWindow {
+            start,
+            end: start + length,
+        }
+    }
+}
+
+/// Error from parsing a time zone name
+#[derive(Debug)]
+pub struct ParseTimeZoneError(String);
+
+impl fmt::Display for ParseTimeZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "I don't know the time zone '{}'. Try one like `America/Chicago`, \
+             or set it from your city with `location`.",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseTimeZoneError {}
+
+/// Parse a time zone name, like `America/Chicago`
+pub fn parse_time_zone(s: &str) -> Result<Tz, ParseTimeZoneError> {
+    let s = s.trim();
+    s.parse().map_err(|_| ParseTimeZoneError(s.to_string()))
+}
+
+/// Split a time zone name off the start or end of some text, like
+/// `Europe/Paris` in `11:00 PM Europe/Paris`. Time zone names have no spaces,
+/// so it can only be the first or last word. Returns the time zone, if there
+/// is one, and the rest of the text.
+pub fn split_time_zone(text: &str) -> (Option<Tz>, &str) {
+    let text = text.trim();
+    if let Some((first, rest)) = text.split_once(' ') {
+        if let Ok(tz) = first.parse() {
+            return (Some(tz), rest.trim());
+        }
+    }
+    if let Some((init, last)) = text.rsplit_once(' ') {
+        if let Ok(tz) = last.parse() {
+            return (Some(tz), init.trim());
+        }
+    }
+    (None, text)
+}
+
+/// Longest duration accepted, so that adding one to a date can't overflow
+pub const MAX_DURATION_DAYS: i64 = 366;
+
+/// Error from parsing a duration like `1h30m`
+#[derive(Debug)]
+pub enum ParseDurationError {
+    /// The duration isn't written in a recognized format
+    Invalid(String),
+
+    /// The duration is longer than [`MAX_DURATION_DAYS`]
+    TooLong(String),
+}
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseDurationError::Invalid(s) => write!(
+                f,
+                "Couldn't read duration '{}'. Try something like `2h` or `1h30m`.",
+                s
+            ),
+            ParseDurationError::TooLong(s) => write!(
+                f,
+                "Duration '{}' is too long. It can be at most {} days.",
+                s, MAX_DURATION_DAYS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+/// Parse a duration made of weeks, days, hours, minutes, and seconds, like
+/// `3w`, `7d`, `2h`, `45m`, `30s`, or `1h30m`. Durations longer than
+/// [`MAX_DURATION_DAYS`] are rejected.
+pub fn parse_duration(s: &str) -> Result<chrono::Duration, ParseDurationError> {
+    let invalid = || ParseDurationError::Invalid(s.to_string());
+    let too_long = || ParseDurationError::TooLong(s.to_string());
+    let max_secs = MAX_DURATION_DAYS * 24 * 60 * 60;
+
+    let mut total: i64 = 0;
+    let mut num = String::new();
+    for c in s.trim().chars() {
+        match c {
+            '0'..='9' => num.push(c),
+            'w' | 'd' | 'h' | 'm' | 's' => {
+                if num.is_empty() {
+                    return Err(invalid());
+                }
+                // Only digits were collected, so this fails only on overflow
+                let n: i64 = num.parse().map_err(|_| too_long())?;
+                let unit = match c {
+                    'w' => 7 * 24 * 60 * 60,
+                    'd' => 24 * 60 * 60,
+                    'h' => 60 * 60,
+                    'm' => 60,
+                    _ => 1,
+                };
+                total = n
+                    .checked_mul(unit)
+                    .and_then(|secs| total.checked_add(secs))
+                    .filter(|&total| total <= max_secs)
+                    .ok_or_else(too_long)?;
+                num.clear();
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    if !num.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+
+    Ok(chrono::Duration::seconds(total))
+}
+
+/// Argument to the bedtime command
+pub enum BedtimeArg {
+    /// Set the bedtime to a time of day, like `10:30 PM`
+    At(Time),
+
+    /// Go to bed some amount of time from now, tonight only, like `in 2h`
+    In(chrono::Duration),
+
+    /// Move the bedtime later or earlier, like `+30m` or `-15m`
+    Shift(chrono::Duration),
+}
+
+impl FromStr for BedtimeArg {
+    type Err = Box<dyn std::error::Error + Send + Sync>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(d) = s.strip_prefix("in ") {
+            Ok(BedtimeArg::In(parse_duration(d)?))
+        } else if let Some(d) = s.strip_prefix('+') {
+            Ok(BedtimeArg::Shift(parse_duration(d)?))
+        } else if let Some(d) = s.strip_prefix('-') {
+            Ok(BedtimeArg::Shift(-parse_duration(d)?))
+        } else {
+            Ok(BedtimeArg::At(s.parse()?))
+        }
+    }
+}
+
+/// Wrapper for displaying a duration in hours and minutes, like `1h30m`
+pub struct HumanDuration(pub chrono::Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.0.num_minutes();
+        let sign = if minutes < 0 { "-" } else { "" };
+        let (hours, minutes) = (minutes.abs() / 60, minutes.abs() % 60);
+        match (hours, minutes) {
+            (0, m) => write!(f, "{}{}m", sign, m),
+            (h, 0) => write!(f, "{}{}h", sign, h),
+            (h, m) => write!(f, "{}{}h{}m", sign, h, m),
+        }
+    }
+}
+
+/// Wrapper for displaying a UTC offset, like `UTC-6` or `UTC+5:30`
+pub struct UtcOffset(pub chrono::FixedOffset);
+
+impl fmt::Display for UtcOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.0.local_minus_utc() / 60;
+        let sign = if minutes < 0 { '-' } else { '+' };
+        let (hours, minutes) = (minutes.abs() / 60, minutes.abs() % 60);
+        match minutes {
+            0 => write!(f, "UTC{}{}", sign, hours),
+            m => write!(f, "UTC{}{}:{:02}", sign, hours, m),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        let parse = |s| parse_duration(s).unwrap();
+        assert_eq!(parse("30s"), chrono::Duration::seconds(30));
+        assert_eq!(parse("45m"), chrono::Duration::minutes(45));
+        assert_eq!(parse("2h"), chrono::Duration::hours(2));
+        assert_eq!(parse("7d"), chrono::Duration::days(7));
+        assert_eq!(parse("3w"), chrono::Duration::weeks(3));
+        assert_eq!(parse(" 1h30m "), chrono::Duration::minutes(90));
+        assert_eq!(parse("1d1h1m1s"), chrono::Duration::seconds(90061));
+    }
+
+    #[test]
+    fn parse_duration_zero() {
+        assert!(matches!(
+            parse_duration("0m"),
+            Err(ParseDurationError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse_duration("0h0m"),
+            Err(ParseDurationError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn parse_duration_too_long() {
+        let max = format!("{}d", MAX_DURATION_DAYS);
+        assert_eq!(
+            parse_duration(&max).unwrap(),
+            chrono::Duration::days(MAX_DURATION_DAYS)
+        );
+        let too_long = format!("{}d1s", MAX_DURATION_DAYS);
+        assert!(matches!(
+            parse_duration(&too_long),
+            Err(ParseDurationError::TooLong(_))
+        ));
+        assert!(matches!(
+            parse_duration("99999999999999999999w"),
+            Err(ParseDurationError::TooLong(_))
+        ));
+        assert!(matches!(
+            parse_duration("9223372036854775807w"),
+            Err(ParseDurationError::TooLong(_))
+        ));
+    }
+
+    #[test]
+    fn parse_duration_garbage() {
+        for s in [
+            "", "h", "10", "1h30", "2x", "-1h", "1.5h", "one hour", "1 h",
+        ] {
+            assert!(
+                matches!(parse_duration(s), Err(ParseDurationError::Invalid(_))),
+                "'{}' parsed",
+                s
+            );
+        }
+    }
+
+    /// Parse a time, getting the hour and minute
+    fn hm(s: &str) -> (u32, u32) {
+        let tm: Time = s.parse().unwrap_or_else(|_| panic!("'{}' didn't parse", s));
+        (tm.0.hour(), tm.0.minute())
+    }
+
+    #[test]
+    fn parse_time_12_hour() {
+        assert_eq!(hm("10:30 PM"), (22, 30));
+        assert_eq!(hm("10:30pm"), (22, 30));
+        assert_eq!(hm("10pm"), (22, 0));
+        assert_eq!(hm(" 7 am "), (7, 0));
+        assert_eq!(hm("1:05 AM"), (1, 5));
+    }
+
+    #[test]
+    fn parse_time_24_hour() {
+        assert_eq!(hm("22:30"), (22, 30));
+        assert_eq!(hm("22"), (22, 0));
+        assert_eq!(hm("0:15"), (0, 15));
+        assert_eq!(hm("07:00"), (7, 0));
+    }
+
+    #[test]
+    fn parse_time_noon_and_midnight() {
+        assert_eq!(hm("12pm"), (12, 0));
+        assert_eq!(hm("12:30 PM"), (12, 30));
+        assert_eq!(hm("12am"), (0, 0));
+        assert_eq!(hm("12:30 AM"), (0, 30));
+        assert_eq!(hm("0"), (0, 0));
+        assert_eq!(hm("12"), (12, 0));
+    }
+
+    #[test]
+    fn parse_time_out_of_range() {
+        for s in [
+            "24", "24:00", "13pm", "0am", "10:60", "25:00", "-1", "", "pm", "ten",
+        ] {
+            assert!(s.parse::<Time>().is_err(), "'{}' parsed", s);
+        }
     }
 }
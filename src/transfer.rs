@@ -0,0 +1,106 @@
+use crate::bot::Bot;
+
+use std::time::Duration;
+
+use serenity::{
+    model::interactions::message_component::ButtonStyle, model::prelude::*, prelude::*, Result,
+};
+
+/// How long the new account has to confirm a transfer
+const TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Custom ID of the button confirming a transfer
+const ACCEPT_ID: &str = "transfer_accept";
+
+/// Custom ID of the button declining a transfer
+const DECLINE_ID: &str = "transfer_decline";
+
+/// Ask the new account in a DM to confirm that a user's settings should move
+/// to it, and move them if it does
+async fn request_consent(ctx: &Context, from: UserId, to: UserId) -> Result<()> {
+    let bot = Bot::of(ctx).await?;
+    let dm = to.create_dm_channel(ctx).await?;
+
+    let mut prompt = dm
+        .send_message(&ctx.http, |m| {
+            m.content(format!(
+                "{} wants to move their bedtime settings, sleep history, and \
+                 streaks to this account. Anything I have saved for this \
+                 account will be replaced. Is this your account?",
+                from.mention()
+            ))
+            .components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id(ACCEPT_ID)
+                            .label("Move them here")
+                            .style(ButtonStyle::Success)
+                    })
+                    .create_button(|b| {
+                        b.custom_id(DECLINE_ID)
+                            .label("No thanks")
+                            .style(ButtonStyle::Secondary)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = prompt
+        .await_component_interaction(ctx)
+        .author_id(to)
+        .timeout(TIMEOUT)
+        .await;
+
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => return prompt.edit(ctx, |m| m.components(|c| c)).await,
+    };
+
+    let moved = interaction.data.custom_id == ACCEPT_ID && {
+        let mut state = bot.write().await;
+        let moved = state.transfer(bot.clone(), from, to).await;
+        state.save();
+        moved
+    };
+
+    let resp = match (interaction.data.custom_id == ACCEPT_ID, moved) {
+        (true, true) => "Done! Your settings and history are on this account now. 🌙",
+        (true, false) => "There was nothing left to move",
+        (false, _) => "Okay, I didn't move anything",
+    };
+
+    interaction
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.content(resp).components(|c| c))
+        })
+        .await?;
+
+    let resp = if moved {
+        format!(
+            "Your settings and history moved to {}, and I deleted them from \
+             this account",
+            to.mention()
+        )
+    } else {
+        format!("{} didn't take your settings", to.mention())
+    };
+    from.create_dm_channel(ctx)
+        .await?
+        .say(&ctx.http, resp)
+        .await?;
+
+    Ok(())
+}
+
+/// Ask the new account for confirmation in the background, so the caller
+/// doesn't wait for it to respond
+pub fn spawn_request_consent(ctx: &Context, from: UserId, to: UserId) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Err(err) = request_consent(&ctx, from, to).await {
+            println!("Error requesting transfer confirmation: {}", err);
+        }
+    });
+}
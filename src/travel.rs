@@ -0,0 +1,47 @@
+use crate::target::Target;
+use crate::time::Time;
+
+use chrono::{Duration, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// Number of nights before a trip to spend gradually moving the bedtime
+pub const GRADUAL_NIGHTS: i64 = 3;
+
+/// Upcoming trip to another time zone
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Trip {
+    /// Time zone to switch to
+    pub time_zone: Tz,
+
+    /// Local date to switch time zones on
+    pub date: NaiveDate,
+
+    /// Bedtime to keep in the new time zone
+    pub bedtime: Time,
+
+    /// Whether the bedtime moves toward the new time zone's before the trip
+    pub gradual: bool,
+}
+
+impl Trip {
+    /// Get a target that moves a bedtime in the `from` time zone to the
+    /// trip's bedtime in its time zone over the nights before the trip, so
+    /// the switch doesn't come all at once
+    pub fn gradual_target(&self, from: Tz) -> Target {
+        let arrival = self
+            .time_zone
+            .from_local_datetime(&self.date.and_time(self.bedtime.0))
+            .earliest()
+            .unwrap_or_else(|| {
+                self.time_zone
+                    .from_utc_datetime(&self.date.and_time(self.bedtime.0))
+            });
+        Target {
+            goal: Time(arrival.with_timezone(&from).time()),
+            from: self.bedtime,
+            start: self.date - Duration::days(GRADUAL_NIGHTS),
+            nights: GRADUAL_NIGHTS,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use chrono_tz::{Tz, TZ_VARIANTS};
+use serenity::{builder::CreateComponents, model::prelude::*, prelude::*, Result};
+
+/// Maximum number of options Discord allows in a single select menu
+const MAX_OPTIONS: usize = 25;
+
+/// How long to wait for the user to make each selection
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Custom ID of the time zone select menu
+const MENU_ID: &str = "time_zone_picker";
+
+/// Region name used for time zones that don't have one, like `UTC`
+const OTHER_REGION: &str = "Other";
+
+/// Description of the select menu option containing the suggested time zone
+const SUGGESTED: &str = "Suggested from your language settings";
+
+/// Option in a select menu, narrowing the remaining time zone candidates down
+/// to `members`
+struct Group {
+    label: String,
+    members: Vec<&'static str>,
+}
+
+impl Group {
+    /// Whether this option leads to a time zone
+    fn contains(&self, tz: Option<Tz>) -> bool {
+        tz.is_some_and(|tz| self.members.contains(&tz.name()))
+    }
+}
+
+/// Get the region part of a time zone name, like `America` in
+/// `America/New_York`
+fn region(name: &str) -> &str {
+    match name.split_once('/') {
+        Some((region, _)) => region,
+        None => OTHER_REGION,
+    }
+}
+
+/// Get a human-readable city part of a time zone name, like `New York` in
+/// `America/New_York`
+fn city(name: &str) -> String {
+    let city = match name.split_once('/') {
+        Some((_, city)) => city,
+        None => name,
+    };
+    city.replace('_', " ")
+}
+
+/// Group all time zones by region
+fn region_groups() -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+
+    for tz in TZ_VARIANTS.iter() {
+        let name = tz.name();
+        let region = region(name);
+        match groups.iter_mut().find(|group| group.label == region) {
+            Some(group) => group.members.push(name),
+            None => groups.push(Group {
+                label: region.to_string(),
+                members: vec![name],
+            }),
+        }
+    }
+
+    groups.sort_by(|a, b| a.label.cmp(&b.label));
+    groups
+}
+
+/// Split time zone candidates into at most [`MAX_OPTIONS`] groups. If there
+/// are few enough candidates, each one gets its own group. Otherwise, the
+/// candidates are split into alphabetical ranges.
+fn city_groups(mut candidates: Vec<&'static str>) -> Vec<Group> {
+    candidates.sort_unstable();
+
+    if candidates.len() <= MAX_OPTIONS {
+        return candidates
+            .into_iter()
+            .map(|name| Group {
+                label: city(name),
+                members: vec![name],
+            })
+            .collect();
+    }
+
+    let chunk_size = candidates.len().div_ceil(MAX_OPTIONS);
+    candidates
+        .chunks(chunk_size)
+        .map(|chunk| Group {
+            label: format!("{} – {}", city(chunk[0]), city(chunk[chunk.len() - 1])),
+            members: chunk.to_vec(),
+        })
+        .collect()
+}
+
+/// Build the components for a select menu choosing between `groups`, marking
+/// the one leading to `suggested`, if given
+fn menu<'a>(
+    c: &'a mut CreateComponents,
+    placeholder: &str,
+    groups: &[Group],
+    suggested: Option<Tz>,
+) -> &'a mut CreateComponents {
+    c.create_action_row(|r| {
+        r.create_select_menu(|m| {
+            m.custom_id(MENU_ID).placeholder(placeholder).options(|o| {
+                for (i, group) in groups.iter().enumerate() {
+                    o.create_option(|opt| {
+                        opt.label(&group.label).value(i);
+                        if group.contains(suggested) {
+                            opt.description(SUGGESTED);
+                        }
+                        opt
+                    });
+                }
+                o
+            })
+        })
+    })
+}
+
+/// Interactively ask a user in a channel to pick a time zone, first by region
+/// and then by city. If there's a `suggested` time zone, its region is listed
+/// first and it's marked in both menus. Returns `None` if the user doesn't
+/// respond in time.
+pub async fn pick_time_zone(
+    ctx: &Context,
+    channel: ChannelId,
+    user: UserId,
+    suggested: Option<Tz>,
+) -> Result<Option<Tz>> {
+    let mut groups = region_groups();
+    if let Some(i) = groups.iter().position(|group| group.contains(suggested)) {
+        let group = groups.remove(i);
+        groups.insert(0, group);
+    }
+
+    let mut prompt = channel
+        .send_message(&ctx.http, |m| {
+            m.content("Pick your region")
+                .components(|c| menu(c, "Region", &groups, suggested))
+        })
+        .await?;
+
+    loop {
+        let interaction = prompt
+            .await_component_interaction(ctx)
+            .author_id(user)
+            .timeout(TIMEOUT)
+            .await;
+
+        let interaction = match interaction {
+            Some(interaction) => interaction,
+            None => {
+                prompt
+                    .edit(ctx, |m| {
+                        m.content("Time zone selection timed out").components(|c| c)
+                    })
+                    .await?;
+                return Ok(None);
+            }
+        };
+
+        let chosen = interaction
+            .data
+            .values
+            .first()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&i| i < groups.len());
+        let chosen = match chosen {
+            Some(i) => groups.swap_remove(i),
+            None => {
+                interaction
+                    .create_interaction_response(&ctx.http, |r| {
+                        r.kind(InteractionResponseType::ChannelMessageWithSource)
+                            .interaction_response_data(|d| {
+                                d.content("That isn't one of the options, please pick again")
+                                    .ephemeral(true)
+                            })
+                    })
+                    .await?;
+                continue;
+            }
+        };
+
+        if let [name] = chosen.members[..] {
+            let tz: Tz = name.parse().expect("Time zone from list failed to parse");
+            interaction
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|d| {
+                            d.content(format!("Picked {}", tz.name())).components(|c| c)
+                        })
+                })
+                .await?;
+            return Ok(Some(tz));
+        }
+
+        let label = chosen.label;
+        groups = city_groups(chosen.members);
+
+        interaction
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.content(format!("Pick your city in {}", label))
+                            .components(|c| menu(c, "City", &groups, suggested))
+                    })
+            })
+            .await?;
+    }
+}
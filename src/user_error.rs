@@ -0,0 +1,45 @@
+use std::error::Error;
+use std::num::ParseIntError;
+
+use serenity::framework::standard::ArgError;
+
+/// Reply for a time that couldn't be read
+const BAD_TIME: &str =
+    "I couldn't read that time. Try something like `10:30 PM`, `10pm`, or `22:30`.";
+
+/// Reply for a number that couldn't be read
+const BAD_NUMBER: &str = "I couldn't read that number. Try something like `10`.";
+
+/// Reply for a command run without the argument it needs
+const MISSING_ARG: &str = "This command needs more to go on. Try `help` with the \
+                           command's name to see how to use it.";
+
+/// Reply for errors on the bot's end, like Discord being unreachable
+const INTERNAL: &str = "Something went wrong on my end. Please try again in a bit.";
+
+/// Reply for an argument error, given the reply for its parse error
+fn describe_arg<E>(err: &ArgError<E>, parse: &str) -> String {
+    match err {
+        ArgError::Eos => MISSING_ARG.to_string(),
+        _ => parse.to_string(),
+    }
+}
+
+/// Explain why a command failed in a way that makes sense to users. Errors
+/// from parsing arguments get a hint with an example, errors on the bot's end
+/// get a generic apology, and errors made for users are shown as they are.
+pub fn describe(err: &(dyn Error + Send + Sync + 'static)) -> String {
+    if err.is::<chrono::format::ParseError>() {
+        BAD_TIME.to_string()
+    } else if let Some(err) = err.downcast_ref::<ArgError<chrono::format::ParseError>>() {
+        describe_arg(err, BAD_TIME)
+    } else if err.is::<ParseIntError>() {
+        BAD_NUMBER.to_string()
+    } else if let Some(err) = err.downcast_ref::<ArgError<ParseIntError>>() {
+        describe_arg(err, BAD_NUMBER)
+    } else if err.is::<serenity::Error>() {
+        INTERNAL.to_string()
+    } else {
+        err.to_string()
+    }
+}
@@ -1,23 +1,74 @@
-use crate::time::Time;
+use crate::achievements::Badge;
+use crate::actor::{self, Flags, Msg, Start, UserActor};
+use crate::alert;
+use crate::audit::{self, AuditEvent};
+use crate::bot::Bot;
+use crate::buddy::{self, Buddy};
+use crate::change_log::{Change, ChangeLog, Setting};
+use crate::check_in;
+use crate::events::{self, Event};
+use crate::group::GroupBedtime;
+use crate::history::{History, Night};
+use crate::holidays::{self, Country};
+use crate::intensity::{self, Intensity, NagContext};
+use crate::manager::Manager;
+use crate::morning;
+use crate::nag_log::{NagLog, NagRecord};
+use crate::presence::{self, AwakeThreshold, Client, IdlePolicy, Signals, Status};
+use crate::presence_log::{PresenceLog, Transition};
+use crate::profile::{self, Profile};
+use crate::reminder::{self, Reminder};
+use crate::routine::{self, Progress};
+use crate::send_queue;
+use crate::sleep_debt;
+use crate::state;
+use crate::supervisor;
+use crate::target::{self, Target};
+use crate::templates;
+use crate::time::{self, HumanDuration, SleepWindow, Time};
+use crate::travel::Trip;
+use crate::winddown;
+use crate::wizard::Setup;
+use crate::xp;
 
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::sync::atomic;
 use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
-use std::thread::sleep;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use chrono::Timelike;
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike, Utc};
 use chrono_tz::Tz;
-use clokwerk::{AsyncScheduler, Job, TimeUnits};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serenity::{
-    http::{CacheHttp, Http},
-    model::{channel::PrivateChannel, id::UserId},
+    http::{Http, HttpError},
+    model::{
+        channel::{Message, PrivateChannel},
+        id::{ChannelId, GuildId, MessageId, UserId},
+        mention::Mentionable,
+        user::OnlineStatus,
+    },
 };
 
+/// How many hours away from a one-night bedtime override still count as the
+/// same night
+const OVERRIDE_WINDOW_HOURS: i64 = 12;
+
+/// Number of recent nights the average sleep rating in user info covers
+const RATING_NIGHTS: i64 = 7;
+
+/// Minutes a bedtime lockout keeps going after the user cancels it, so it
+/// can't be lifted on a whim
+const LOCKOUT_COOLDOWN_MINUTES: i64 = 30;
+
+/// Night sleep reminders are skipped on, and why
+pub type SkippedNight = (NaiveDate, String);
+
 /// User-specific state
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct UserInfo {
     /// Whether the user has bedtime alerts enabled
     on: bool,
@@ -28,6 +79,156 @@ pub struct UserInfo {
     /// The user's bedtime, if one is set
     bedtime: Option<Time>,
 
+    /// Goal the user's bedtime is gradually moving toward, if any
+    target: Option<Target>,
+
+    /// How long the user wants to sleep each night, in minutes, if they set a
+    /// goal
+    sleep_goal: Option<u32>,
+
+    /// Upcoming trip to another time zone, if any
+    trip: Option<Trip>,
+
+    /// When the user should go to bed tonight, overriding their bedtime for
+    /// one night only
+    tonight: Option<DateTime<Utc>>,
+
+    /// Local dates of nights the user chose to skip sleep reminders for
+    skipped: BTreeSet<NaiveDate>,
+
+    /// Country whose public holidays the user observes, if they set one
+    country: Option<Country>,
+
+    /// Whether to skip sleep reminders on the eves of the user's country's
+    /// public holidays
+    holiday_skip: bool,
+
+    /// How insistent the user's sleep reminders are
+    intensity: Intensity,
+
+    /// Bluntest tone the user's sleep reminders can escalate to, counting
+    /// from 1, if they set one
+    max_tone: Option<u8>,
+
+    /// Name of the message pack the user picked for their reminders, if any
+    theme: Option<String>,
+
+    /// Number of minutes before or after their bedtime that the user's sleep
+    /// reminders may randomly start, if they want them less predictable
+    fuzz: Option<u32>,
+
+    /// Whether the user consents to having their online status tracked
+    #[serde(default = "tracked_before_opt_in")]
+    track_presence: bool,
+
+    /// Whether the user counts as awake while idle
+    idle_policy: IdlePolicy,
+
+    /// Clients the user counts as awake while online on, if not all of them
+    awake_clients: Option<BTreeSet<Client>>,
+
+    /// How long the user has to show signs of being awake before sleep
+    /// reminders go out, if they set a threshold
+    awake_threshold: Option<AwakeThreshold>,
+
+    /// Whether the user wants one sleep reminder message a night, edited as
+    /// they stay up, instead of a new message for each reminder
+    single_nag: bool,
+
+    /// Whether the user wants their sleep reminder DMs deleted once they wake
+    /// up, leaving a summary of the night
+    clean_up: bool,
+
+    /// Whether the user wants a DM the morning after each night saying how
+    /// late they went to sleep
+    morning_summary: bool,
+
+    /// Whether to reply for the user when they're mentioned while asleep past
+    /// their bedtime, in guilds that allow it
+    away_reply: bool,
+
+    /// Phrase that acts like the `wake` command when DMed to the bot,
+    /// normalized for matching
+    wake_phrase: Option<String>,
+
+    /// Local date of the last night the user got a morning summary for
+    summarized: Option<NaiveDate>,
+
+    /// Name of the group the user is in, if any
+    group: Option<String>,
+
+    /// Shared bedtime of the user's group, which replaces their own
+    group_bedtime: Option<GroupBedtime>,
+
+    /// Whether the user's group can see if they're asleep. Off until they
+    /// turn it on.
+    share_status: bool,
+
+    /// Friend to tell when the user stays up too late
+    buddy: Option<Buddy>,
+
+    /// Someone the user lets change their bedtime settings, if any
+    manager: Option<Manager>,
+
+    /// Guilds the user wants good night announcements in
+    announce_in: HashSet<GuildId>,
+
+    /// Guilds the user wants to be timed out in from their bedtime until
+    /// they're expected to wake up
+    lockout_in: HashSet<GuildId>,
+
+    /// When the user's current bedtime lockout ends, if they're locked out
+    locked_until: Option<DateTime<Utc>>,
+
+    /// Recent changes to the user's settings, for undoing them
+    changes: ChangeLog,
+
+    /// Sleep reminders recently sent to the user
+    #[serde(skip_serializing_if = "state::omit_history")]
+    nag_log: NagLog,
+
+    /// How the user's recent nights went
+    #[serde(skip_serializing_if = "state::omit_history")]
+    history: History,
+
+    /// When the user recently started and stopped counting as awake
+    #[serde(skip_serializing_if = "state::omit_history")]
+    presence_log: PresenceLog,
+
+    /// Lifetime experience points earned by going to bed on time
+    xp: u64,
+
+    /// Badges the user unlocked, in the order they were unlocked
+    badges: Vec<Badge>,
+
+    /// Checklist the user goes through before bed
+    routine: Vec<String>,
+
+    /// Map of normalized names to the user's saved settings profiles
+    profiles: BTreeMap<String, Profile>,
+
+    /// Which routine items the user checked off tonight
+    #[serde(skip)]
+    routine_progress: Progress,
+
+    /// One-off reminders the user asked for that haven't been sent yet
+    reminders: Vec<Reminder>,
+
+    /// When the user last interacted with the bot
+    last_seen: Option<DateTime<Utc>>,
+
+    /// Whether the bot can no longer reach the user, so they get no sleep
+    /// reminders until they interact with it again
+    dormant: bool,
+
+    /// When the user was warned that they'll be forgotten for not interacting
+    /// with the bot, if they were
+    prune_warned: Option<DateTime<Utc>>,
+
+    /// Number of times the user revoked their link tokens. Tokens minted
+    /// before the latest revocation are invalid.
+    link_generation: u32,
+
     /// Whether the user is detected to be awake
     #[serde(skip)]
     awake: Arc<AtomicBool>,
@@ -36,9 +237,29 @@ pub struct UserInfo {
     #[serde(skip)]
     allowed_awake: Arc<AtomicBool>,
 
-    /// Handle used to manage bedtime alert scheduling
+    /// The user's latest online status, if it's been seen since the bot
+    /// started
     #[serde(skip)]
-    sched: Option<tokio::task::JoinHandle<()>>,
+    status: Option<Status>,
+
+    /// Signs of the user being awake, since the bot started
+    #[serde(skip)]
+    signals: Signals,
+
+    /// When the user's sleep reminders last started, since the bot started
+    #[serde(skip)]
+    last_fired: Arc<Mutex<Option<DateTime<Utc>>>>,
+
+    /// Task owning the user's scheduled jobs and awake flags, started the
+    /// first time it's needed
+    #[serde(skip)]
+    actor: Option<UserActor>,
+}
+
+/// Presence tracking assumed for users saved before it became opt-in, who
+/// were tracked all along
+fn tracked_before_opt_in() -> bool {
+    true
 }
 
 impl Default for UserInfo {
@@ -47,152 +268,1823 @@ impl Default for UserInfo {
             on: true,
             time_zone: None,
             bedtime: None,
+            target: None,
+            sleep_goal: None,
+            trip: None,
+            tonight: None,
+            skipped: BTreeSet::new(),
+            country: None,
+            holiday_skip: false,
+            intensity: Intensity::default(),
+            max_tone: None,
+            theme: None,
+            fuzz: None,
+            track_presence: false,
+            idle_policy: IdlePolicy::default(),
+            awake_clients: None,
+            awake_threshold: None,
+            single_nag: false,
+            clean_up: false,
+            morning_summary: false,
+            away_reply: false,
+            wake_phrase: None,
+            summarized: None,
+            group: None,
+            group_bedtime: None,
+            share_status: false,
+            buddy: None,
+            manager: None,
+            announce_in: HashSet::new(),
+            lockout_in: HashSet::new(),
+            locked_until: None,
+            changes: ChangeLog::default(),
+            nag_log: NagLog::default(),
+            presence_log: PresenceLog::default(),
+            history: History::default(),
+            xp: 0,
+            badges: Vec::new(),
+            routine: Vec::new(),
+            profiles: BTreeMap::new(),
+            routine_progress: Progress::default(),
             awake: Arc::new(AtomicBool::new(true)),
             allowed_awake: Arc::new(AtomicBool::new(true)),
-            sched: None,
+            status: None,
+            signals: Signals::default(),
+            last_fired: Arc::default(),
+            reminders: Vec::new(),
+            last_seen: None,
+            dormant: false,
+            prune_warned: None,
+            link_generation: 0,
+            actor: None,
         }
     }
 }
 
 /// In the specified private channel, send a sleep reminder
-async fn send_nag_msg_in_dm(http: impl AsRef<Http>, chan: PrivateChannel) {
-    let res = chan.say(&http, "Go to bed. 😴 🛏  💤").await;
-    if let Err(err) = res {
-        println!("Error sending user sleep reminder: {}", err);
-    }
+async fn send_nag_msg_in_dm(
+    http: impl AsRef<Http>,
+    chan: PrivateChannel,
+    message: &str,
+) -> serenity::Result<Message> {
+    chan.say(&http, message).await
 }
 
-/// Send a sleep reminder direct message to a user
-async fn send_nag_msg(cache_http: impl CacheHttp, id: UserId) {
+/// Maximum number of tries to deliver a sleep reminder
+const MAX_SEND_ATTEMPTS: u32 = 3;
+
+/// How long to wait before the first retry of a sleep reminder, doubling for
+/// each retry after that
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Send a sleep reminder direct message to a user, retrying with a growing
+/// delay if it fails for a reason that might go away. Returns how many tries
+/// it took, along with the result of the last one.
+#[tracing::instrument(skip(bot, message))]
+async fn send_nag_msg(bot: &Bot, id: UserId, message: &str) -> (u32, serenity::Result<Message>) {
     println!("Nagging user '{}'", id);
-    let res = id.create_dm_channel(&cache_http).await;
-    match res {
-        Ok(dm) => send_nag_msg_in_dm(cache_http.http(), dm).await,
-        Err(err) => println!("Error creating DM channel: {}", err),
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        send_queue::wait_turn().await;
+        let started = std::time::Instant::now();
+        let res = match id.create_dm_channel(&bot.http).await {
+            Ok(dm) => send_nag_msg_in_dm(&bot.http, dm, message).await,
+            Err(err) => Err(err),
+        };
+        bot.app.metrics.nag_dm_latency.observe(started.elapsed());
+        match res {
+            Err(err) if is_transient(&err) && attempts < MAX_SEND_ATTEMPTS => {
+                println!(
+                    "Error sending user '{}' sleep reminder, retrying in {}s: {}",
+                    id,
+                    delay.as_secs(),
+                    err
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            res => return (attempts, res),
+        }
     }
 }
 
-/// Send a sleep reminder direct message to a user if the awake flag is set
-async fn maybe_nag(cache_http: impl CacheHttp, id: UserId, awake: Arc<AtomicBool>) {
-    let awake = awake.load(atomic::Ordering::Relaxed);
+/// Whether an error might go away if the request is retried, like a server
+/// error, rate limit, or network problem
+fn is_transient(err: &serenity::Error) -> bool {
+    match err {
+        serenity::Error::Http(err) => match &**err {
+            HttpError::UnsuccessfulRequest(resp) => {
+                resp.status_code.is_server_error() || resp.status_code.as_u16() == 429
+            }
+            HttpError::Request(_) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Discord error code for a user that can't be sent messages
+const CANNOT_MESSAGE_USER: isize = 50007;
+
+/// Discord error code for a user that doesn't exist, like a deleted account
+const UNKNOWN_USER: isize = 10013;
+
+/// Whether an error means a user can't be sent direct messages, because they
+/// blocked the bot, left every guild it's in, or deleted their account
+fn is_unreachable(err: &serenity::Error) -> bool {
+    match err {
+        serenity::Error::Http(err) => match &**err {
+            HttpError::UnsuccessfulRequest(resp) => {
+                [CANNOT_MESSAGE_USER, UNKNOWN_USER].contains(&resp.error.code)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Number of sleep reminders in a row that can fail to send before the bot's
+/// operator is alerted
+const ALERT_AFTER_FAILURES: usize = 3;
+
+/// Send a user a sleep reminder. If they want a single reminder message, the
+/// one sent earlier tonight is edited with how long they've been up instead,
+/// falling back to a new message if that fails. Returns how many tries it
+/// took, along with the result of the last one, which has the message the
+/// reminder ended up in.
+async fn deliver_nag(
+    nag: &Nag,
+    message: &str,
+    sent: u32,
+) -> (u32, serenity::Result<(ChannelId, MessageId)>) {
+    let earlier = *nag.nag_msg.lock().expect("Nag message lock poisoned");
+    if let (true, Some((channel, msg))) = (nag.single_nag, earlier) {
+        let started = *nag.last_fired.lock().expect("Last fired lock poisoned");
+        let elapsed = started.map_or_else(chrono::Duration::zero, |start| Utc::now() - start);
+        let content = format!(
+            "{}\nStill awake — {} past bedtime ⏰ (reminder {})",
+            message,
+            HumanDuration(elapsed),
+            sent + 1
+        );
+        send_queue::wait_turn().await;
+        match channel
+            .edit_message(&nag.bot.http, msg, |m| m.content(content))
+            .await
+        {
+            Ok(_) => return (1, Ok((channel, msg))),
+            Err(err) => println!(
+                "Error editing user sleep reminder, sending a new one: {}",
+                err
+            ),
+        }
+    }
+
+    let (attempts, res) = send_nag_msg(&nag.bot, nag.id, message).await;
+    let res = res.map(|msg| {
+        let ids = (msg.channel_id, msg.id);
+        *nag.nag_msg.lock().expect("Nag message lock poisoned") = Some(ids);
+        ids
+    });
+    (attempts, res)
+}
+
+/// Send a sleep reminder direct message to a user if the awake flag is set,
+/// unless they finished their wind-down routine. Returns `false` if the user
+/// can't be reached.
+async fn maybe_nag(nag: &Nag) -> bool {
+    // Only count the user as awake once they've shown it for long enough,
+    // if they want that
+    let awake = nag.awake.load(atomic::Ordering::Relaxed)
+        && nag
+            .awake_threshold
+            .is_none_or(|threshold| nag.signals.sustained(threshold));
 
-    println!("User '{}' awake status: '{}'", id, awake);
+    println!("User '{}' awake status: '{}'", nag.id, awake);
 
-    if awake {
-        send_nag_msg(cache_http, id).await;
-        sleep(Duration::from_secs(5));
+    if awake && !nag.routine_progress.is_done() {
+        let started = *nag.last_fired.lock().expect("Last fired lock poisoned");
+        let elapsed = started.map_or_else(chrono::Duration::zero, |start| Utc::now() - start);
+        let night = nag.history.last();
+        let sent = night.as_ref().map_or(0, |night| night.nags);
+        let bedtime = night.map_or_else(String::new, |night| night.bedtime.to_string());
+        let name = nag
+            .bot
+            .cache
+            .user(nag.id)
+            .map_or_else(|| nag.id.mention().to_string(), |user| user.name);
+        let context = NagContext {
+            user: nag.id,
+            name: &name,
+            bedtime: &bedtime,
+            streak: nag.history.streak_at_risk(),
+            pack: nag.theme.as_deref(),
+        };
+        let message = nag.intensity.nag_message(elapsed, nag.max_tone, &context);
+        let (attempts, res) = deliver_nag(nag, &message, sent).await;
+        if let Err(err) = &res {
+            println!("Error sending user sleep reminder: {}", err);
+        }
+        let reachable = !res.as_ref().is_err_and(is_unreachable);
+        match res {
+            Ok(ids) => {
+                nag.log.record(attempts, None, Some(ids));
+                audit::record(AuditEvent::NagSent { user: nag.id });
+                events::send(Event::NagDelivered(nag.id));
+            }
+            Err(err) => nag.log.record(attempts, Some(err.to_string()), None),
+        }
+        if reachable && nag.log.failure_streak() == ALERT_AFTER_FAILURES {
+            alert::report(format!(
+                "The last {} sleep reminders to user '{}' failed to send",
+                ALERT_AFTER_FAILURES, nag.id
+            ));
+        }
+        nag.history.count_nag();
+        if !reachable {
+            return false;
+        }
     }
+
+    true
+}
+
+/// Whether a user is marked as unreachable
+async fn is_dormant(bot: &Bot, id: UserId) -> bool {
+    let state = bot.read().await;
+    state
+        .users
+        .get(&id)
+        .is_some_and(|user_info| user_info.dormant)
 }
 
-async fn nag_loop(
-    http: Arc<Http>,
+/// Everything needed to nag a user, shared between their scheduled jobs
+#[derive(Clone)]
+struct Nag {
+    bot: Bot,
     id: UserId,
+    time_zone: Tz,
+    intensity: Intensity,
     awake: Arc<AtomicBool>,
     allowed_awake: Arc<AtomicBool>,
-) {
-    println!("Reached nag loop for user '{}'", id);
-    allowed_awake.store(false, atomic::Ordering::Relaxed);
+    last_fired: Arc<Mutex<Option<DateTime<Utc>>>>,
+    log: NagLog,
+    history: History,
+    buddy: Option<Buddy>,
+    routine: Vec<String>,
+    routine_progress: Progress,
+    single_nag: bool,
+    clean_up: bool,
+    sleep_goal: chrono::Duration,
+    max_tone: Option<u8>,
+    theme: Option<String>,
+    fuzz: u32,
+    signals: Signals,
+    awake_threshold: Option<AwakeThreshold>,
+
+    /// Tonight's sleep reminder message, for editing in single-message mode
+    nag_msg: Arc<Mutex<Option<(ChannelId, MessageId)>>>,
+}
+
+/// Wish a user good night in the announcement channels of the guilds they
+/// opted in to announcements for
+async fn announce_good_night(nag: &Nag) {
+    let channels: Vec<ChannelId> = {
+        let state = nag.bot.read().await;
+        match state.users.get(&nag.id) {
+            Some(user_info) => user_info
+                .announce_in
+                .iter()
+                .filter_map(|&guild| state.guild_config(Some(guild)).announce_channel)
+                .collect(),
+            None => Vec::new(),
+        }
+    };
+
+    for channel in channels {
+        let resp = templates::render(
+            nag.theme.as_deref(),
+            "good_night",
+            "Good night, {user}! 🌙",
+            &[("user", &nag.id.mention())],
+        );
+        if let Err(err) = channel.say(&nag.bot.http, resp).await {
+            println!("Error announcing good night: {}", err);
+        }
+    }
+}
+
+/// Normalize a wake phrase for matching, ignoring case and spacing
+fn normalize_phrase(phrase: &str) -> String {
+    phrase
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Time a user out until `until` in the guilds they opted in to a bedtime
+/// lockout in
+async fn lock_out(nag: &Nag, until: DateTime<Utc>) {
+    let guilds: Vec<GuildId> = {
+        let mut state = nag.bot.write().await;
+        let guilds = match state.users.get_mut(&nag.id) {
+            Some(user_info) if !user_info.lockout_in.is_empty() => {
+                user_info.locked_until = Some(until);
+                user_info.lockout_in.iter().copied().collect()
+            }
+            _ => return,
+        };
+        state.save();
+        guilds
+    };
+
+    for guild in guilds {
+        let res = guild
+            .edit_member(&nag.bot.http, nag.id, |member| {
+                member.disable_communication_until_datetime(until.into())
+            })
+            .await;
+        if let Err(err) = res {
+            println!(
+                "Error locking out user '{}' in guild '{}': {}",
+                nag.id, guild, err
+            );
+        }
+    }
+}
+
+#[tracing::instrument(skip(nag), fields(id = %nag.id))]
+async fn nag_loop(nag: Nag) {
+    println!("Reached nag loop for user '{}'", nag.id);
+    if is_dormant(&nag.bot, nag.id).await {
+        println!("User '{}' is dormant, not nagging", nag.id);
+        return;
+    }
+    let paused = nag.bot.read().await.is_paused(nag.id);
+    if !paused {
+        announce_good_night(&nag).await;
+    }
+    nag.routine_progress.reset(nag.routine.len());
+    if !nag.routine.is_empty() && !paused {
+        if let Err(err) = routine::send_checklist(&nag.bot, nag.id, &nag.routine).await {
+            println!("Error sending user routine checklist: {}", err);
+        }
+    }
+    nag.allowed_awake.store(false, atomic::Ordering::Relaxed);
+    *nag.nag_msg.lock().expect("Nag message lock poisoned") = None;
+    let start = Utc::now();
+    *nag.last_fired.lock().expect("Last fired lock poisoned") = Some(start);
+    let local = start.with_timezone(&nag.time_zone);
+    let bedtime = Time(NaiveTime::from_hms(local.hour(), local.minute(), 0));
+    nag.history
+        .start_night(time::night_of(&local), bedtime, false);
+    let window = SleepWindow::starting_at(start, nag.sleep_goal);
+    if !paused {
+        lock_out(&nag, window.end).await;
+    }
+    let timeout = nag.bot.app.config.nag_timeout;
+    let mut buddy = nag.buddy.filter(|buddy| buddy.accepted);
     loop {
-        if allowed_awake.load(atomic::Ordering::Relaxed) {
+        if nag.allowed_awake.load(atomic::Ordering::Relaxed) {
+            break;
+        }
+
+        // Stop at the end of the sleep window, when the user is expected to
+        // be up anyway
+        if Utc::now() >= window.end {
+            println!("User '{}' reached the end of their sleep window", nag.id);
+            nag.allowed_awake.store(true, atomic::Ordering::Relaxed);
             break;
         }
 
-        let awake = Arc::clone(&awake);
+        // Give up if the user stays up too long, rather than nagging them
+        // all night
+        if Utc::now() - start >= timeout {
+            println!("Giving up on sleep reminders for user '{}' tonight", nag.id);
+            nag.history.give_up();
+            nag.allowed_awake.store(true, atomic::Ordering::Relaxed);
+            break;
+        }
+
+        // Keep the night going, but deliver nothing while the operator has
+        // sleep reminders paused
+        if nag.bot.read().await.is_paused(nag.id) {
+            tokio::time::sleep(nag.intensity.nag_interval(0)).await;
+            continue;
+        }
+
+        if !maybe_nag(&nag).await {
+            events::send(Event::Unreachable(nag.id));
+            return;
+        }
+
+        // Tell the user's buddy once if they stay up too late
+        if let Some(b) = buddy {
+            let late = (Utc::now() - start).num_minutes() >= b.after_minutes;
+            if late && nag.awake.load(atomic::Ordering::Relaxed) {
+                buddy::notify(&nag.bot, nag.id, b).await;
+                buddy = None;
+            }
+        }
+
+        // Wait between reminders even when none was sent, like when the user
+        // finished their routine, so the loop doesn't spin
+        let sent = nag.history.last().map_or(0, |night| night.nags);
+        tokio::time::sleep(nag.intensity.nag_interval(sent)).await;
+    }
+    finish_night(&nag).await;
+}
+
+/// Delete the sleep reminder DMs sent to a user since their night started,
+/// leaving a summary of the night in their place
+async fn clean_up_nags(nag: &Nag) {
+    let start = match *nag.last_fired.lock().expect("Last fired lock poisoned") {
+        Some(start) => start,
+        None => return,
+    };
+    let mut deleted = 0;
+    for (channel, msg) in nag.log.messages_since(start) {
+        send_queue::wait_turn().await;
+        match channel.delete_message(&nag.bot.http, msg).await {
+            Ok(()) => deleted += 1,
+            Err(err) => println!("Error deleting user sleep reminder: {}", err),
+        }
+    }
+    if deleted == 0 {
+        return;
+    }
+
+    println!("Deleted {} sleep reminders for user '{}'", deleted, nag.id);
+    let mut resp = match deleted {
+        1 => "🧹 Cleared 1 sleep reminder from last night.".to_string(),
+        n => format!("🧹 Cleared {} sleep reminders from last night.", n),
+    };
+    if let Some(summary) = nag
+        .history
+        .last()
+        .and_then(|night| morning::summary(&night, nag.time_zone, nag.theme.as_deref()))
+    {
+        resp = format!("{} {}", resp, summary);
+    }
+    let res = async {
+        let dm = nag.id.create_dm_channel(&nag.bot.http).await?;
+        dm.say(&nag.bot.http, resp).await
+    }
+    .await;
+    if let Err(err) = res {
+        println!("Error sending user cleanup summary: {}", err);
+    }
+}
+
+/// Once a user's night is over, give them experience points if they went to
+/// bed on time, tell them about any badges they unlocked, clean up their
+/// sleep reminders if they want, and ask them how they slept
+async fn finish_night(nag: &Nag) {
+    nag.history.wake();
+
+    if nag.clean_up {
+        clean_up_nags(nag).await;
+    }
+
+    let unlocked = {
+        let mut state = nag.bot.write().await;
+        let user_info = match state.users.get_mut(&nag.id) {
+            Some(user_info) => user_info,
+            None => return,
+        };
+
+        let streak = nag.history.streak();
+        if streak > 0 {
+            let earned = xp::award(streak);
+            println!("User '{}' earned {} XP", nag.id, earned);
+            user_info.xp += earned;
+        }
+
+        let unlocked = Badge::newly_earned(&nag.history, &user_info.badges);
+        user_info.badges.extend(&unlocked);
+
+        state.save();
+        unlocked
+    };
+
+    for badge in unlocked {
+        println!("User '{}' unlocked badge '{}'", nag.id, badge.name());
+        let res = async {
+            let dm = nag.id.create_dm_channel(&nag.bot.http).await?;
+            dm.say(&nag.bot.http, format!("🏅 You unlocked a badge: {}", badge))
+                .await
+        }
+        .await;
+        if let Err(err) = res {
+            println!("Error sending user badge message: {}", err);
+        }
+    }
 
-        maybe_nag(&http, id, awake).await;
+    if let Some(night) = nag.history.last() {
+        if let Err(err) = check_in::send(&nag.bot, nag.id, night.date).await {
+            println!("Error sending user morning check-in: {}", err);
+        }
     }
 }
 
-/// Schedule bedtime alerts for a user
-async fn sched_bedtime(
-    http: Arc<Http>,
+/// Whether a one-night bedtime override is recent enough that its night may
+/// not be over yet at `now`
+fn overrides_tonight(tonight: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    tonight.is_some_and(|tonight| (now - tonight).num_hours().abs() < OVERRIDE_WINDOW_HOURS)
+}
+
+/// Settings that decide what happens to a user's sleep reminders each night
+#[derive(Clone)]
+struct BedtimeRules {
     time_zone: Tz,
     bedtime: Time,
-    id: UserId,
-    awake: Arc<AtomicBool>,
-    allowed_awake: Arc<AtomicBool>,
-) -> tokio::task::JoinHandle<()> {
-    let mut sched = AsyncScheduler::with_tz(time_zone);
-    let http = Arc::clone(&http);
-    println!("Scheduling bedtime for user '{}'", id);
-    sched
-        .every(1.day())
-        .plus(bedtime.0.hour().hours())
-        .plus(bedtime.0.minute().minutes())
-        .run(move || nag_loop(http.clone(), id, awake.clone(), allowed_awake.clone()));
-    tokio::spawn(async move {
-        loop {
-            sched.run_pending().await;
-            tokio::time::sleep(Duration::from_secs(1)).await;
+
+    /// One-night bedtime override, which replaces the regular bedtime on the
+    /// night it falls in
+    tonight: Option<DateTime<Utc>>,
+
+    /// Nights the user skipped
+    skipped: BTreeSet<NaiveDate>,
+
+    /// Country whose public holidays' eves are skipped, if the user wants
+    holiday_country: Option<Country>,
+}
+
+/// What happens to a user's sleep reminders on a night
+#[derive(Debug, PartialEq, Eq)]
+enum NightPlan {
+    /// No reminders, for the reason given
+    Skipped(String),
+
+    /// Reminders start at a one-night bedtime override
+    Overridden(DateTime<Utc>),
+
+    /// Reminders start at the regular bedtime, give or take any fuzz
+    Regular(DateTime<Utc>),
+}
+
+impl BedtimeRules {
+    /// Get why sleep reminders are skipped on a night, if they are
+    fn skip_reason(&self, night: NaiveDate) -> Option<String> {
+        if self.skipped.contains(&night) {
+            return Some("you skipped it".to_string());
+        }
+        holidays::eve_of(self.holiday_country?, night)
+            .map(|holiday| format!("it's the eve of {}", holiday))
+    }
+
+    /// Decide what happens on a night. Skipping a night wins over overriding
+    /// its bedtime.
+    fn plan_night(&self, night: NaiveDate) -> NightPlan {
+        if let Some(reason) = self.skip_reason(night) {
+            return NightPlan::Skipped(reason);
+        }
+        let tz = self.time_zone;
+        if let Some(tonight) = self
+            .tonight
+            .filter(|tonight| time::night_of(&tonight.with_timezone(&tz)) == night)
+        {
+            return NightPlan::Overridden(tonight);
+        }
+        match time::bedtime_on(night, self.bedtime, tz) {
+            Some(start) => NightPlan::Regular(start),
+            None => NightPlan::Skipped("its bedtime doesn't exist".to_string()),
+        }
+    }
+
+    /// Decide what happens on the night `now` falls in, returning the night
+    /// along with it
+    fn plan(&self, now: DateTime<Utc>) -> (NaiveDate, NightPlan) {
+        let night = time::night_of(&now.with_timezone(&self.time_zone));
+        (night, self.plan_night(night))
+    }
+}
+
+/// Run bedtime alerts for a user, following `rules` each night. If the user
+/// wants fuzzing, each night's alerts start at a random time around their
+/// bedtime.
+async fn run_bedtime(nag: Nag, rules: BedtimeRules) {
+    let id = nag.id;
+    // Wake up at the earliest time reminders can start, then wait a random
+    // amount of time picked anew each night
+    let earliest = Time(rules.bedtime.0 - chrono::Duration::minutes(i64::from(nag.fuzz)));
+    let mut pending = rules.tonight.filter(|&tonight| tonight > Utc::now());
+    loop {
+        let regular = earliest.next_after(rules.time_zone, Utc::now());
+        let at = pending.map_or(regular, |tonight| tonight.min(regular));
+        nag.bot.app.scheduler.wait_until(id, at).await;
+
+        if pending.is_some_and(|tonight| tonight <= Utc::now()) {
+            pending = None;
+            // Skipped nights are recorded when their regular bedtime comes
+            if let (_, NightPlan::Overridden(_)) = rules.plan(Utc::now()) {
+                nag_loop(nag.clone()).await;
+            }
+            continue;
+        }
+
+        let jitter = rand::thread_rng().gen_range(0..=2 * u64::from(nag.fuzz));
+        tokio::time::sleep(Duration::from_secs(jitter * 60)).await;
+        match rules.plan(Utc::now()) {
+            (night, NightPlan::Skipped(reason)) => {
+                println!("Skipping tonight for user '{}' because {}", id, reason);
+                nag.history.start_night(night, rules.bedtime, true);
+            }
+            (_, NightPlan::Overridden(_)) => {
+                println!("Bedtime for user '{}' is overridden tonight", id);
+            }
+            (_, NightPlan::Regular(_)) => nag_loop(nag.clone()).await,
         }
+    }
+}
+
+/// Schedule bedtime alerts for a user, restarting them if they crash
+fn sched_bedtime(nag: Nag, rules: BedtimeRules) -> tokio::task::JoinHandle<()> {
+    println!("Scheduling bedtime for user '{}'", nag.id);
+    supervisor::supervise(nag.id, "bedtime schedule", move || {
+        run_bedtime(nag.clone(), rules.clone())
     })
 }
 
 impl UserInfo {
     /// Update user's bedtime alert schedule based on their settings
-    pub async fn update_sched(&mut self, http: Arc<Http>, id: UserId) {
-        if let Some(sched) = &self.sched {
-            sched.abort()
-        }
-        match self {
-            UserInfo {
-                on,
-                time_zone: Some(time_zone),
-                bedtime: Some(bedtime),
-                awake,
-                allowed_awake,
-                ..
-            } if *on => {
-                let awake = Arc::clone(awake);
-                let allowed_awake = Arc::clone(allowed_awake);
+    pub async fn update_sched(&mut self, bot: Bot, id: UserId) {
+        let reminders: Option<Start> = if self.reminders.is_empty() {
+            None
+        } else {
+            let bot = bot.clone();
+            Some(Box::new(move || reminder::sched_reminders(bot, id)))
+        };
+        self.actor()
+            .send(Msg::SetJob(actor::Job::Reminders, reminders));
+        // Forget one-night overrides for nights that are over
+        let now = Utc::now();
+        self.tonight = self
+            .tonight
+            .filter(|&tonight| tonight > now || overrides_tonight(Some(tonight), now));
+        match self.bedtime_rules() {
+            Some(rules) if self.on => {
+                let nag = Nag {
+                    bot,
+                    id,
+                    time_zone: rules.time_zone,
+                    intensity: self.intensity,
+                    awake: Arc::clone(&self.awake),
+                    allowed_awake: Arc::clone(&self.allowed_awake),
+                    last_fired: Arc::clone(&self.last_fired),
+                    log: self.nag_log.clone(),
+                    history: self.history.clone(),
+                    buddy: self.buddy,
+                    routine: self.routine.clone(),
+                    routine_progress: self.routine_progress.clone(),
+                    single_nag: self.single_nag,
+                    clean_up: self.clean_up,
+                    sleep_goal: self.sleep_goal(),
+                    max_tone: self.max_tone,
+                    theme: self.theme.clone(),
+                    fuzz: self.fuzz.unwrap_or(0),
+                    signals: self.signals.clone(),
+                    awake_threshold: self.awake_threshold,
+                    nag_msg: Arc::default(),
+                };
 
-                let sched =
-                    sched_bedtime(http, *time_zone, *bedtime, id, awake, allowed_awake).await;
-                self.sched = Some(sched);
-            }
-            _ => {
-                self.sched = None;
+                let start: Start = Box::new(move || sched_bedtime(nag, rules));
+                self.actor()
+                    .send(Msg::SetJob(actor::Job::Bedtime, Some(start)));
             }
+            _ => self.actor().send(Msg::SetJob(actor::Job::Bedtime, None)),
+        }
+    }
+
+    /// Whether the user's jobs have been scheduled and are still being managed
+    pub fn is_scheduled(&self) -> bool {
+        self.actor.as_ref().is_some_and(UserActor::is_running)
+    }
+
+    /// Get the user's actor, spawning it if it isn't running yet
+    fn actor(&mut self) -> &UserActor {
+        let flags = Flags {
+            awake: Arc::clone(&self.awake),
+            allowed_awake: Arc::clone(&self.allowed_awake),
+            history: self.history.clone(),
+        };
+        self.actor.get_or_insert_with(|| UserActor::spawn(flags))
+    }
+
+    /// Get the time zone and bedtime user's sleep reminders follow. A group's
+    /// shared bedtime takes priority over the user's own.
+    fn schedule(&self) -> Option<(Tz, Time)> {
+        match self.group_bedtime {
+            Some(group) => Some((group.time_zone, group.bedtime)),
+            None => self.time_zone.zip(self.bedtime),
         }
     }
 
+    /// Get the settings that decide what happens to the user's sleep
+    /// reminders each night, if they have a bedtime schedule
+    fn bedtime_rules(&self) -> Option<BedtimeRules> {
+        let (time_zone, bedtime) = self.schedule()?;
+        Some(BedtimeRules {
+            time_zone,
+            bedtime,
+            tonight: self.tonight,
+            skipped: self.skipped.clone(),
+            holiday_country: self.country.filter(|_| self.holiday_skip),
+        })
+    }
+
     /// Set user's time zone
-    pub async fn set_time_zone(&mut self, http: Arc<Http>, id: UserId, time_zone: Tz) {
+    pub async fn set_time_zone(&mut self, bot: Bot, id: UserId, time_zone: Tz) {
+        self.changes.record(vec![Setting::TimeZone(self.time_zone)]);
         self.time_zone = Some(time_zone);
-        self.update_sched(http, id).await;
+        self.update_sched(bot, id).await;
     }
 
     /// Set user's bedtime
-    pub async fn set_bedtime(&mut self, http: Arc<Http>, id: UserId, bedtime: Time) {
+    pub async fn set_bedtime(&mut self, bot: Bot, id: UserId, bedtime: Time) {
+        self.changes.record(vec![Setting::Bedtime(self.bedtime)]);
+        self.bedtime = Some(bedtime);
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set user's time zone and bedtime together, as one change, and enable
+    /// sleep alerts
+    pub async fn set_time_zone_and_bedtime(
+        &mut self,
+        bot: Bot,
+        id: UserId,
+        time_zone: Tz,
+        bedtime: Time,
+    ) {
+        self.changes.record(vec![
+            Setting::TimeZone(self.time_zone),
+            Setting::Bedtime(self.bedtime),
+        ]);
+        self.on = true;
+        self.time_zone = Some(time_zone);
+        self.bedtime = Some(bedtime);
+        self.update_sched(bot, id).await;
+    }
+
+    /// Shift user's bedtime by some amount. Returns the new bedtime, or `None`
+    /// if the user has no bedtime to shift.
+    pub async fn shift_bedtime(
+        &mut self,
+        bot: Bot,
+        id: UserId,
+        by: chrono::Duration,
+    ) -> Option<Time> {
+        let bedtime = self.bedtime?.shift(by);
+        self.set_bedtime(bot, id, bedtime).await;
+        Some(bedtime)
+    }
+
+    /// Start moving the user's bedtime toward a goal a little each night,
+    /// reaching it after some time. Returns the target, or `None` if the user
+    /// has no time zone and bedtime to move from.
+    pub async fn set_target(
+        &mut self,
+        bot: Bot,
+        id: UserId,
+        goal: Time,
+        over: chrono::Duration,
+    ) -> Option<Target> {
+        let tz = self.time_zone?;
+        let target = Target {
+            goal,
+            from: self.bedtime?,
+            start: Utc::now().with_timezone(&tz).date().naive_local(),
+            nights: over.num_days().clamp(1, target::MAX_NIGHTS),
+        };
+        self.target = Some(target);
+        self.advance_target(bot, id).await;
+        Some(target)
+    }
+
+    /// Stop moving the user's bedtime toward a goal, keeping it where it is.
+    /// Returns whether there was a target.
+    pub fn clear_target(&mut self) -> bool {
+        self.target.take().is_some()
+    }
+
+    /// Move the user's bedtime to where it should be tonight on the way to
+    /// their target, forgetting the target once it's reached. Returns whether
+    /// anything changed.
+    pub async fn advance_target(&mut self, bot: Bot, id: UserId) -> bool {
+        let (target, tz) = match self.target.zip(self.time_zone) {
+            Some(target) => target,
+            None => return false,
+        };
+
+        let today = Utc::now().with_timezone(&tz).date().naive_local();
+        let reached = target.is_reached(today);
+        if reached {
+            self.target = None;
+        }
+
+        let bedtime = target.bedtime_on(today);
+        if self.bedtime == Some(bedtime) {
+            return reached;
+        }
+
+        println!("Moving bedtime for user '{}' to {}", id, bedtime);
         self.bedtime = Some(bedtime);
-        self.update_sched(http, id).await;
+        self.update_sched(bot, id).await;
+        true
+    }
+
+    /// Plan a switch to another time zone on a local date, keeping the same
+    /// bedtime there. If `gradual`, the bedtime moves toward the new time
+    /// zone's over the nights before. Returns the trip, or `None` if the user
+    /// has no time zone and bedtime.
+    pub async fn set_trip(
+        &mut self,
+        bot: Bot,
+        id: UserId,
+        time_zone: Tz,
+        date: NaiveDate,
+        gradual: bool,
+    ) -> Option<Trip> {
+        let from = self.time_zone?;
+        let trip = Trip {
+            time_zone,
+            date,
+            bedtime: self.bedtime?,
+            gradual,
+        };
+        self.trip = Some(trip);
+        if gradual {
+            self.target = Some(trip.gradual_target(from));
+        }
+        self.apply_scheduled(bot, id).await;
+        Some(trip)
+    }
+
+    /// Cancel the user's upcoming trip, putting their bedtime back if it was
+    /// moving toward the trip. Returns whether there was a trip.
+    pub async fn clear_trip(&mut self, bot: Bot, id: UserId) -> bool {
+        let trip = match self.trip.take() {
+            Some(trip) => trip,
+            None => return false,
+        };
+        if trip.gradual {
+            self.target = None;
+            self.bedtime = Some(trip.bedtime);
+            self.update_sched(bot, id).await;
+        }
+        true
+    }
+
+    /// Switch the user's time zone if their trip started, and move their
+    /// bedtime toward its target. Returns whether anything changed.
+    pub async fn apply_scheduled(&mut self, bot: Bot, id: UserId) -> bool {
+        let arrived = match self.trip.zip(self.time_zone) {
+            Some((trip, tz)) => Utc::now().with_timezone(&tz).date().naive_local() >= trip.date,
+            None => false,
+        };
+        if let Some(trip) = self.trip.filter(|_| arrived) {
+            println!("User '{}' traveled to {}", id, trip.time_zone.name());
+            self.trip = None;
+            if trip.gradual {
+                self.target = None;
+            }
+            self.time_zone = Some(trip.time_zone);
+            self.bedtime = Some(trip.bedtime);
+            self.update_sched(bot, id).await;
+            return true;
+        }
+        self.advance_target(bot, id).await
+    }
+
+    /// Remind the user of something once at the next occurrence of a time
+    /// of day. Returns when the reminder will be sent, or `None` if the user
+    /// has no time zone.
+    pub async fn add_reminder(
+        &mut self,
+        bot: Bot,
+        id: UserId,
+        time: Time,
+        text: String,
+    ) -> Option<DateTime<Utc>> {
+        let at = time.next_after(self.time_zone?, Utc::now());
+        self.reminders.push(Reminder { at, text });
+        self.update_sched(bot, id).await;
+        Some(at)
+    }
+
+    /// Get the user's pending one-off reminders
+    pub fn reminders(&self) -> &[Reminder] {
+        &self.reminders
+    }
+
+    /// Forget one-off reminders due at or before a time, once they're sent
+    pub fn forget_reminders(&mut self, until: DateTime<Utc>) {
+        self.reminders.retain(|reminder| reminder.at > until);
+    }
+
+    /// Override user's bedtime for one night only
+    pub async fn set_tonight(&mut self, bot: Bot, id: UserId, tonight: DateTime<Utc>) {
+        self.tonight = Some(tonight);
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set how many minutes around their bedtime the user's sleep reminders
+    /// may randomly start, or `None` to start them right at bedtime
+    pub async fn set_fuzz(&mut self, bot: Bot, id: UserId, fuzz: Option<u32>) {
+        self.fuzz = fuzz;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set the bluntest tone the user's sleep reminders can escalate to, or
+    /// `None` to let them escalate all the way
+    pub async fn set_max_tone(&mut self, bot: Bot, id: UserId, max_tone: Option<u8>) {
+        self.max_tone = max_tone;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set the message pack the user's reminders use, or `None` for the
+    /// default messages
+    pub async fn set_theme(&mut self, bot: Bot, id: UserId, theme: Option<String>) {
+        self.theme = theme;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Get the name of the message pack the user picked, if any
+    pub fn theme(&self) -> Option<&str> {
+        self.theme.as_deref()
+    }
+
+    /// Set whether the user wants one sleep reminder message a night, edited
+    /// as they stay up
+    pub async fn set_single_nag(&mut self, bot: Bot, id: UserId, single_nag: bool) {
+        self.single_nag = single_nag;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set whether the user wants their sleep reminder DMs deleted once they
+    /// wake up
+    pub async fn set_clean_up(&mut self, bot: Bot, id: UserId, clean_up: bool) {
+        self.clean_up = clean_up;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set whether the user wants a summary of how late they went to sleep the
+    /// morning after each night
+    pub fn set_morning_summary(&mut self, morning_summary: bool) {
+        self.morning_summary = morning_summary;
+    }
+
+    /// Set whether to reply for the user when they're mentioned while asleep
+    /// past their bedtime
+    pub fn set_away_reply(&mut self, away_reply: bool) {
+        self.away_reply = away_reply;
+    }
+
+    /// Set the phrase that acts like the `wake` command when DMed to the bot,
+    /// or remove it
+    pub fn set_wake_phrase(&mut self, phrase: Option<&str>) {
+        self.wake_phrase = phrase.map(normalize_phrase);
+    }
+
+    /// Whether a DM to the bot is the user's wake phrase
+    pub fn is_wake_phrase(&self, text: &str) -> bool {
+        self.wake_phrase.as_deref() == Some(normalize_phrase(text).as_str())
+    }
+
+    /// If the user wants mentions answered for them and is asleep past their
+    /// bedtime right now, get the bedtime they went to sleep after
+    pub fn away_reply_bedtime(&self) -> Option<Time> {
+        if !self.away_reply || !self.is_nagging() || self.is_awake() {
+            return None;
+        }
+        self.schedule().map(|(_, bedtime)| bedtime)
+    }
+
+    /// If the user wants morning summaries and it's the morning after their
+    /// last night, get a summary of the night, unless they already got one
+    pub fn take_morning_summary(&mut self, now: DateTime<Utc>) -> Option<String> {
+        if !self.morning_summary {
+            return None;
+        }
+        let tz = self.time_zone?;
+        let night = self.history.last()?;
+        if self.summarized == Some(night.date)
+            || !morning::is_morning_after(&night, tz, self.sleep_goal(), now)
+        {
+            return None;
+        }
+        self.summarized = Some(night.date);
+        morning::summary(&night, tz, self.theme.as_deref())
+    }
+
+    /// Set how insistent the user's sleep reminders are
+    pub async fn set_intensity(&mut self, bot: Bot, id: UserId, intensity: Intensity) {
+        self.intensity = intensity;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Start winding down, going to bed after some time tonight only, with
+    /// check-ins along the way. Returns `false` if the user has no bedtime
+    /// schedule for the override to apply to, or the wind-down is longer than
+    /// [`winddown::MAX_HOURS`].
+    pub async fn start_winddown(&mut self, bot: Bot, id: UserId, length: chrono::Duration) -> bool {
+        if !self.on || self.schedule().is_none() {
+            return false;
+        }
+        if length > chrono::Duration::hours(winddown::MAX_HOURS) {
+            return false;
+        }
+        let bedtime = match Utc::now().checked_add_signed(length) {
+            Some(bedtime) => bedtime,
+            None => return false,
+        };
+        self.set_tonight(bot.clone(), id, bedtime).await;
+        let start: Start = Box::new(move || winddown::spawn(bot, id, bedtime));
+        self.actor()
+            .send(Msg::SetJob(actor::Job::WindDown, Some(start)));
+        true
+    }
+
+    /// Skip sleep reminders for the next night, without affecting the nights
+    /// after. Returns the skipped night's date, or `None` if the user has no
+    /// bedtime scheduled.
+    pub async fn skip_tonight(&mut self, bot: Bot, id: UserId) -> Option<NaiveDate> {
+        let (tz, bedtime) = self.schedule()?;
+        let next = bedtime.next_after(tz, Utc::now());
+        let night = time::night_of(&next.with_timezone(&tz));
+        self.skipped.insert(night);
+        self.tonight = None;
+        self.update_sched(bot, id).await;
+        Some(night)
+    }
+
+    /// Get the country whose public holidays the user observes, if they set one
+    pub fn country(&self) -> Option<Country> {
+        self.country
+    }
+
+    /// Set the country whose public holidays the user observes, or `None` for
+    /// none
+    pub async fn set_country(&mut self, bot: Bot, id: UserId, country: Option<Country>) {
+        self.country = country;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set whether to skip sleep reminders on the eves of public holidays
+    pub async fn set_holiday_skip(&mut self, bot: Bot, id: UserId, holiday_skip: bool) {
+        self.holiday_skip = holiday_skip;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Get when the user's sleep reminders next start after `now`, along with
+    /// the nights skipped before then and why. Returns `None` if they have no
+    /// bedtime schedule.
+    pub fn next_bedtime(&self, now: DateTime<Utc>) -> Option<(DateTime<Utc>, Vec<SkippedNight>)> {
+        let rules = self.bedtime_rules()?;
+        let mut skipped = Vec::new();
+        let mut night = time::night_of(&now.with_timezone(&rules.time_zone));
+        // Give up looking after a year of skipped nights
+        for _ in 0..366 {
+            match rules.plan_night(night) {
+                NightPlan::Skipped(reason) => {
+                    // Only count skipped nights that haven't started yet
+                    let start = time::bedtime_on(night, rules.bedtime, rules.time_zone);
+                    if start.is_none_or(|start| start > now) {
+                        skipped.push((night, reason));
+                    }
+                }
+                NightPlan::Overridden(start) | NightPlan::Regular(start) if start > now => {
+                    return Some((start, skipped))
+                }
+                NightPlan::Overridden(_) | NightPlan::Regular(_) => {}
+            }
+            night = night.succ_opt().expect("Date out of range");
+        }
+        None
+    }
+
+    /// Apply all settings collected by the setup wizard, and enable sleep
+    /// alerts
+    pub async fn setup(&mut self, bot: Bot, id: UserId, setup: Setup) {
+        self.changes.record(vec![
+            Setting::TimeZone(self.time_zone),
+            Setting::Bedtime(self.bedtime),
+        ]);
+        self.on = true;
+        self.time_zone = Some(setup.time_zone);
+        self.bedtime = Some(setup.bedtime);
+        self.intensity = setup.intensity;
+        self.set_track_presence(setup.track_presence);
+        self.update_sched(bot, id).await;
+    }
+
+    /// Revert user's most recent time zone or bedtime change. Returns the
+    /// change that was reverted, or `None` if there's nothing to undo.
+    pub async fn undo(&mut self, bot: Bot, id: UserId) -> Option<Change> {
+        let change = self.changes.pop()?;
+        for setting in &change.previous {
+            match *setting {
+                Setting::TimeZone(time_zone) => self.time_zone = time_zone,
+                Setting::Bedtime(bedtime) => self.bedtime = bedtime,
+            }
+        }
+        self.update_sched(bot, id).await;
+        Some(change)
     }
 
     /// Enable sleep alerts for user
-    pub async fn on(&mut self, http: Arc<Http>, id: UserId) {
+    pub async fn on(&mut self, bot: Bot, id: UserId) {
         self.on = true;
-        self.update_sched(http, id).await;
+        self.update_sched(bot, id).await;
     }
 
     /// Disable sleep alerts for user
-    pub async fn off(&mut self, http: Arc<Http>, id: UserId) {
+    pub async fn off(&mut self, bot: Bot, id: UserId) {
         self.on = false;
-        self.update_sched(http, id).await;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Stop all of the user's scheduled jobs, before forgetting them
+    pub fn unschedule(&mut self) {
+        if let Some(actor) = self.actor.take() {
+            actor.send(Msg::Shutdown);
+        }
+    }
+
+    /// Get user's time zone, if one is set
+    pub fn time_zone(&self) -> Option<Tz> {
+        self.time_zone
+    }
+
+    /// Get up to `n` of the most recent sleep reminders sent to the user,
+    /// oldest first
+    pub fn recent_nags(&self, n: usize) -> Vec<NagRecord> {
+        self.nag_log.recent(n)
+    }
+
+    /// Get how many times the user revoked their link tokens
+    pub fn link_generation(&self) -> u32 {
+        self.link_generation
+    }
+
+    /// Invalidate all of the user's link tokens
+    pub fn revoke_links(&mut self) {
+        self.link_generation += 1;
+    }
+
+    /// Count the sleep reminders delivered to the user since a time
+    pub fn nags_delivered_since(&self, since: DateTime<Utc>) -> usize {
+        self.nag_log.delivered_since(since)
+    }
+
+    /// Get the time zone and bedtime the user's sleep reminders go out at, if
+    /// they're getting any
+    pub fn active_schedule(&self) -> Option<(Tz, Time)> {
+        if self.on && !self.dormant {
+            self.schedule()
+        } else {
+            None
+        }
+    }
+
+    /// Get warnings about anything stopping the user's sleep reminders from
+    /// going out, with what to do about it
+    pub fn setup_warnings(&self) -> Vec<&'static str> {
+        let mut warnings = Vec::new();
+        if self.group_bedtime.is_none() {
+            if self.time_zone.is_none() {
+                warnings.push(
+                    "⚠️ You haven't set a time zone yet, so reminders won't fire. \
+                     Pick one with `tz`, or set it from your city with `location`.",
+                );
+            }
+            if self.bedtime.is_none() {
+                warnings.push(
+                    "⚠️ You haven't set a bedtime yet, so reminders won't fire. \
+                     Set one with `bedtime`, like `bedtime 10:30 PM`.",
+                );
+            }
+        }
+        if !self.on {
+            warnings.push("⚠️ Sleep reminders are off. Turn them back on with `on`.");
+        }
+        warnings
+    }
+
+    /// Get the friend told when the user stays up too late, if anyone
+    pub fn buddy(&self) -> Option<Buddy> {
+        self.buddy
+    }
+
+    /// Ask a friend to be told when user stays up too late, or stop telling
+    /// anyone if `buddy` is `None`. The friend has to accept before they're
+    /// told anything.
+    pub async fn set_buddy(&mut self, bot: Bot, id: UserId, buddy: Option<Buddy>) {
+        self.buddy = buddy;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Record a friend's answer to being user's buddy
+    pub async fn answer_buddy(&mut self, bot: Bot, id: UserId, buddy: UserId, accepted: bool) {
+        match &mut self.buddy {
+            Some(b) if b.id == buddy && accepted => b.accepted = true,
+            Some(b) if b.id == buddy => self.buddy = None,
+            _ => return,
+        }
+        self.update_sched(bot, id).await;
+    }
+
+    /// Get who the user lets change their bedtime settings, if anyone
+    pub fn manager(&self) -> Option<Manager> {
+        self.manager
+    }
+
+    /// Ask someone to manage the user's bedtime settings, or stop letting
+    /// anyone if `manager` is `None`. The manager has to accept before they
+    /// can change anything.
+    pub fn set_manager(&mut self, manager: Option<UserId>) {
+        self.manager = manager.map(|id| Manager {
+            id,
+            accepted: false,
+        });
+    }
+
+    /// Record someone's answer to managing the user's settings
+    pub fn answer_manager(&mut self, manager: UserId, accepted: bool) {
+        match &mut self.manager {
+            Some(m) if m.id == manager && accepted => m.accepted = true,
+            Some(m) if m.id == manager => self.manager = None,
+            _ => {}
+        }
+    }
+
+    /// Point the user's manager and buddy at another user ID if they're
+    /// `from`, like when that person switches accounts, or drop them if `to`
+    /// is `None`. Returns whether the buddy changed, meaning the user needs
+    /// rescheduling.
+    pub fn replace_contact(&mut self, from: UserId, to: Option<UserId>) -> bool {
+        if let Some(m) = self.manager.filter(|m| m.id == from) {
+            self.manager = to.map(|id| Manager { id, ..m });
+        }
+        match self.buddy.filter(|b| b.id == from) {
+            Some(b) => {
+                self.buddy = to.map(|id| Buddy { id, ..b });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the name of user's group, if they're in one
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Whether the user lets their group see if they're asleep
+    pub fn shares_status(&self) -> bool {
+        self.share_status
+    }
+
+    /// Set whether the user lets their group see if they're asleep
+    pub fn set_share_status(&mut self, share_status: bool) {
+        self.share_status = share_status;
+    }
+
+    /// Put user in a group, or take them out of their group if `group` is
+    /// `None`, following its shared bedtime
+    pub async fn set_group(
+        &mut self,
+        bot: Bot,
+        id: UserId,
+        group: Option<String>,
+        group_bedtime: Option<GroupBedtime>,
+    ) {
+        self.group = group;
+        self.group_bedtime = group_bedtime;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Whether user is detected to be awake
+    pub fn is_awake(&self) -> bool {
+        self.awake.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Opt in or out of good night announcements in a guild
+    pub fn set_announce(&mut self, guild: GuildId, announce: bool) {
+        if announce {
+            self.announce_in.insert(guild);
+        } else {
+            self.announce_in.remove(&guild);
+        }
+    }
+
+    /// Opt in to a bedtime lockout in a guild
+    pub fn lock_out_in(&mut self, guild: GuildId) {
+        self.lockout_in.insert(guild);
+    }
+
+    /// Opt out of the bedtime lockout in a guild, or in every guild if none is
+    /// given. Lockouts going on right now only end after the cooldown, so get
+    /// the guilds the user is locked out in with when their lockout should now
+    /// end.
+    pub fn cancel_lockout(
+        &mut self,
+        guild: Option<GuildId>,
+        now: DateTime<Utc>,
+    ) -> Vec<(GuildId, DateTime<Utc>)> {
+        let guilds: Vec<GuildId> = match guild {
+            Some(guild) => self.lockout_in.take(&guild).into_iter().collect(),
+            None => self.lockout_in.drain().collect(),
+        };
+        let end = match self.locked_until.filter(|&until| until > now) {
+            Some(until) => until.min(now + chrono::Duration::minutes(LOCKOUT_COOLDOWN_MINUTES)),
+            None => return Vec::new(),
+        };
+        guilds.into_iter().map(|guild| (guild, end)).collect()
+    }
+
+    /// Get all of user's recorded nights on or after a date, oldest first
+    pub fn nights_since(&self, date: NaiveDate) -> Vec<Night> {
+        self.history.since(date)
+    }
+
+    /// Get the user's lifetime experience points
+    pub fn xp(&self) -> u64 {
+        self.xp
+    }
+
+    /// Record how well the user slept the night of a date. Returns `false` if
+    /// the night isn't remembered.
+    pub fn rate_night(&mut self, date: NaiveDate, rating: u8) -> bool {
+        self.history.rate(date, rating)
+    }
+
+    /// Get all of the user's remembered nights, oldest first
+    pub fn nights(&self) -> Vec<Night> {
+        self.history.all()
+    }
+
+    /// Get how long the user wants to sleep each night
+    pub fn sleep_goal(&self) -> chrono::Duration {
+        let minutes = self.sleep_goal.unwrap_or(sleep_debt::DEFAULT_GOAL_MINUTES);
+        chrono::Duration::minutes(i64::from(minutes))
+    }
+
+    /// Set how long the user wants to sleep each night
+    pub fn set_sleep_goal(&mut self, goal: chrono::Duration) {
+        self.sleep_goal = Some(goal.num_minutes() as u32);
+    }
+
+    /// Get the user's sleep debt over the last week, or `None` if it's not
+    /// known how long they slept on any of those nights
+    pub fn sleep_debt(&self) -> Option<chrono::Duration> {
+        let since = Utc::now().naive_utc().date() - chrono::Duration::days(sleep_debt::DEBT_NIGHTS);
+        sleep_debt::debt_since(&self.history.since(since), self.sleep_goal(), since)
+    }
+
+    /// Attach a note to the user's most recent night. Returns the night's
+    /// date, or `None` if they have no nights recorded.
+    pub fn note_last_night(&mut self, note: String) -> Option<NaiveDate> {
+        self.history.note_last(note)
+    }
+
+    /// Get the names of the user's saved settings profiles, in order
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.profiles.keys().map(String::as_str).collect()
+    }
+
+    /// Save the user's current schedule settings as a profile, replacing any
+    /// profile with the same name. Returns `false` if they already have as
+    /// many profiles as they can.
+    pub fn save_profile(&mut self, name: String) -> bool {
+        if !self.profiles.contains_key(&name) && self.profiles.len() >= profile::MAX_PROFILES {
+            return false;
+        }
+        let profile = self.current_profile();
+        self.profiles.insert(name, profile);
+        true
+    }
+
+    /// Switch the user's schedule settings to a saved profile. The time zone
+    /// and bedtime it replaces can be undone. Returns `false` if there's no
+    /// profile with that name.
+    pub async fn use_profile(&mut self, bot: Bot, id: UserId, name: &str) -> bool {
+        let profile = match self.profiles.get(name) {
+            Some(profile) => profile.clone(),
+            None => return false,
+        };
+        self.apply_profile(bot, id, profile).await;
+        true
+    }
+
+    /// Get the user's current schedule settings as a profile
+    pub fn current_profile(&self) -> Profile {
+        Profile {
+            time_zone: self.time_zone,
+            bedtime: self.bedtime,
+            sleep_goal: self.sleep_goal,
+            intensity: self.intensity,
+            max_tone: self.max_tone,
+            fuzz: self.fuzz,
+            skipped: self.skipped.clone(),
+            routine: self.routine.clone(),
+        }
+    }
+
+    /// Switch the user's schedule settings to a profile. The time zone and
+    /// bedtime they had can be undone.
+    pub async fn apply_profile(&mut self, bot: Bot, id: UserId, profile: Profile) {
+        self.changes.record(vec![
+            Setting::TimeZone(self.time_zone),
+            Setting::Bedtime(self.bedtime),
+        ]);
+        self.time_zone = profile.time_zone;
+        self.bedtime = profile.bedtime;
+        self.sleep_goal = profile.sleep_goal;
+        self.intensity = profile.intensity;
+        self.max_tone = profile.max_tone;
+        self.fuzz = profile.fuzz;
+        self.skipped = profile.skipped;
+        self.routine = profile.routine;
+        self.routine_progress.reset(self.routine.len());
+        self.update_sched(bot, id).await;
+    }
+
+    /// Delete one of the user's saved profiles. Returns `false` if there's no
+    /// profile with that name.
+    pub fn delete_profile(&mut self, name: &str) -> bool {
+        self.profiles.remove(name).is_some()
+    }
+
+    /// Get the user's wind-down routine
+    pub fn routine(&self) -> &[String] {
+        &self.routine
+    }
+
+    /// Set the user's wind-down routine
+    pub async fn set_routine(&mut self, bot: Bot, id: UserId, routine: Vec<String>) {
+        self.routine = routine;
+        self.routine_progress.reset(self.routine.len());
+        self.update_sched(bot, id).await;
+    }
+
+    /// Check or uncheck an item of tonight's wind-down routine. Returns the
+    /// routine and which of its items are checked, or `None` if there's no
+    /// such item.
+    pub fn toggle_routine_item(&self, i: usize) -> Option<(Vec<String>, Vec<bool>)> {
+        if !self.routine_progress.toggle(i) {
+            return None;
+        }
+        Some((self.routine.clone(), self.routine_progress.checked()))
+    }
+
+    /// Get the badges the user unlocked, in the order they were unlocked
+    pub fn badges(&self) -> &[Badge] {
+        &self.badges
+    }
+
+    /// Whether the user has set anything needed for sleep reminders
+    pub fn is_configured(&self) -> bool {
+        self.time_zone.is_some() || self.bedtime.is_some()
+    }
+
+    /// Record that the user interacted with the bot just now
+    pub fn seen(&mut self) {
+        self.noticed();
+        self.dormant = false;
+    }
+
+    /// Record that the user was around just now, so they aren't forgotten as
+    /// stale. Unlike `seen`, this doesn't make a dormant user reachable again.
+    pub fn noticed(&mut self) {
+        self.last_seen = Some(Utc::now());
+        self.prune_warned = None;
+    }
+
+    /// Whether the user interacted with the bot since a time and can still be
+    /// reached
+    pub fn active_since(&self, since: DateTime<Utc>) -> bool {
+        !self.dormant && self.last_seen.is_some_and(|seen| seen >= since)
+    }
+
+    /// Whether the bot can no longer reach the user
+    pub fn is_dormant(&self) -> bool {
+        self.dormant
+    }
+
+    /// Mark the user as unreachable, so they get no sleep reminders until
+    /// they interact with the bot again
+    pub fn set_dormant(&mut self) {
+        self.dormant = true;
+    }
+
+    /// Get when the user last interacted with the bot. Users from before this
+    /// was tracked count as seen now.
+    pub fn last_seen(&mut self) -> DateTime<Utc> {
+        *self.last_seen.get_or_insert_with(Utc::now)
+    }
+
+    /// Get when the user was warned that they'll be forgotten, if they were
+    pub fn prune_warned(&self) -> Option<DateTime<Utc>> {
+        self.prune_warned
+    }
+
+    /// Record that the user was just warned that they'll be forgotten
+    pub fn warn_prune(&mut self) {
+        self.prune_warned = Some(Utc::now());
+    }
+
+    /// Whether the user has no settings worth keeping and no nights recorded
+    /// on or after a date, so their state can be forgotten
+    pub fn is_idle(&self, since: NaiveDate) -> bool {
+        !self.is_configured()
+            && self.group.is_none()
+            && self.reminders.is_empty()
+            && self.history.since(since).is_empty()
+    }
+
+    /// Whether the user's online status should be tracked. This requires
+    /// sleep reminders to be on and the user's explicit consent.
+    pub fn tracks_presence(&self) -> bool {
+        self.on && self.track_presence
+    }
+
+    /// Give or revoke consent to track user's online status
+    pub fn set_track_presence(&mut self, track_presence: bool) {
+        self.track_presence = track_presence;
+        if !track_presence {
+            // Without presence tracking, assume the user is awake
+            self.awake();
+        }
+    }
+
+    /// Set whether the user counts as awake while idle
+    pub fn set_idle_policy(&mut self, idle_policy: IdlePolicy) {
+        self.idle_policy = idle_policy;
+        self.update_awake();
+    }
+
+    /// Set how long the user has to show signs of being awake before sleep
+    /// reminders go out, or `None` to send them on any sign
+    pub async fn set_awake_threshold(
+        &mut self,
+        bot: Bot,
+        id: UserId,
+        awake_threshold: Option<AwakeThreshold>,
+    ) {
+        self.awake_threshold = awake_threshold;
+        self.update_sched(bot, id).await;
+    }
+
+    /// Set which clients the user counts as awake while online on, or `None`
+    /// to count all of them
+    pub fn set_awake_clients(&mut self, awake_clients: Option<BTreeSet<Client>>) {
+        self.awake_clients = awake_clients;
+        self.update_awake();
+    }
+
+    /// Record the user's new online status, flagging them as awake or asleep.
+    /// Returns whether they count as awake.
+    pub fn presence_changed(&mut self, status: Status) -> bool {
+        self.status = Some(status);
+        self.update_awake()
+    }
+
+    /// Record that the user sent a message, reacted, or started typing, which
+    /// counts as them being awake
+    /// while idle if they want it to
+    pub fn active(&mut self, id: UserId) {
+        self.noticed();
+        self.signals.active();
+        let idle = self
+            .status
+            .as_ref()
+            .is_some_and(|status| status.overall == OnlineStatus::Idle);
+        if idle && self.idle_policy == IdlePolicy::RecentActivity {
+            self.update_awake();
+            // Check again once the activity isn't recent anymore
+            tokio::spawn(async move {
+                let recent = chrono::Duration::minutes(presence::RECENT_ACTIVITY_MINUTES);
+                tokio::time::sleep(recent.to_std().unwrap_or_default()).await;
+                events::send(Event::PresenceRecheck(id));
+            });
+        }
+    }
+
+    /// Flag the user as awake or asleep based on their latest online status,
+    /// if it's known. Returns whether they count as awake.
+    pub fn update_awake(&mut self) -> bool {
+        let status = match &self.status {
+            Some(status) => status,
+            None => return self.is_awake(),
+        };
+        let awake = presence::is_awake(
+            status,
+            self.idle_policy,
+            self.awake_clients.as_ref(),
+            self.signals.last_active(),
+        );
+        self.signals.set_online(awake);
+        self.presence_log.record(awake);
+        if awake {
+            self.awake();
+        } else {
+            self.asleep();
+        }
+        awake
     }
 
     /// Set user awake flag
     pub fn awake(&mut self) {
-        self.awake.store(true, atomic::Ordering::Relaxed)
+        self.actor().send(Msg::PresenceChanged(true))
     }
 
-    /// Unset user awake flag
+    /// Unset user awake flag, remembering when they fell asleep if it was past
+    /// their bedtime
     pub fn asleep(&mut self) {
-        self.awake.store(false, atomic::Ordering::Relaxed)
+        self.actor().send(Msg::PresenceChanged(false))
     }
 
-    /// Set user allowed awake flag
+    /// Set user allowed awake flag. Acknowledging reminders counts as
+    /// interacting with the bot.
     pub fn allow_awake(&mut self) {
-        self.allowed_awake.store(true, atomic::Ordering::Relaxed)
+        self.seen();
+        self.actor().send(Msg::Ack)
+    }
+
+    /// Get when the user last started or stopped counting as awake, if they
+    /// have since their presence started being tracked
+    pub fn last_transition(&self) -> Option<DateTime<Utc>> {
+        self.presence_log.last().map(|transition| transition.at)
+    }
+
+    /// Get the user's remembered transitions between awake and asleep, oldest
+    /// first
+    pub fn transitions(&self) -> Vec<Transition> {
+        self.presence_log.all()
+    }
+
+    /// Whether the user's sleep reminders are going out right now
+    pub fn is_nagging(&self) -> bool {
+        !self.allowed_awake.load(atomic::Ordering::Relaxed)
+    }
+
+    /// How long the user has been up past their bedtime, if their sleep
+    /// reminders are going out right now
+    pub fn past_bedtime(&self, now: DateTime<Utc>) -> Option<chrono::Duration> {
+        if !self.is_nagging() {
+            return None;
+        }
+        self.last_fired().map(|fired| now - fired)
+    }
+
+    /// When the user's sleep reminders last started, if they have since the
+    /// bot started
+    pub fn last_fired(&self) -> Option<DateTime<Utc>> {
+        *self.last_fired.lock().expect("Last fired lock poisoned")
+    }
+
+    /// Get when the user's sleep reminders will next start after `now`, if
+    /// they're scheduled
+    pub fn next_fire(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if !self.on || self.dormant {
+            return None;
+        }
+        if let Some(tonight) = self.tonight.filter(|&tonight| tonight > now) {
+            return Some(tonight);
+        }
+        let (time_zone, bedtime) = self.schedule()?;
+        let mut at = bedtime.next_after(time_zone, now);
+        while self
+            .skipped
+            .contains(&time::night_of(&at.with_timezone(&time_zone)))
+        {
+            at = bedtime.next_after(time_zone, at);
+        }
+        Some(at)
+    }
+
+    /// Describe what the user's sleep reminders would be doing at an instant,
+    /// without sending anything, for debugging schedules around daylight
+    /// saving transitions and midnight
+    pub fn simulate(
+        &self,
+        id: UserId,
+        at: DateTime<Utc>,
+        nag_timeout: chrono::Duration,
+    ) -> Vec<String> {
+        let rules = match self.bedtime_rules() {
+            Some(rules) => rules,
+            None => return vec!["No time zone and bedtime, so nothing is scheduled".to_string()],
+        };
+        let (tz, bedtime) = (rules.time_zone, rules.bedtime);
+        let fmt = |at: DateTime<Utc>| {
+            at.with_timezone(&tz)
+                .format("%Y-%m-%d %I:%M %p %Z")
+                .to_string()
+        };
+        let (night, plan) = rules.plan(at);
+
+        let mut lines = vec![
+            format!("Local time: {} in {}", fmt(at), tz.name()),
+            format!("Night of {}, bedtime {}", night, bedtime),
+        ];
+        if self.group_bedtime.is_some() {
+            lines.push("Bedtime comes from the user's group".to_string());
+        }
+        if !self.on {
+            lines.push("Sleep reminders are off, so none would be sent".to_string());
+        }
+        if self.dormant {
+            lines.push("User is dormant, so no reminders would be sent".to_string());
+        }
+
+        // Overrides start exactly on time, without fuzz
+        let start = match plan {
+            NightPlan::Skipped(reason) => {
+                lines.push(format!("Night is skipped because {}", reason));
+                None
+            }
+            NightPlan::Overridden(tonight) => {
+                lines.push(format!("Bedtime is overridden to {} tonight", fmt(tonight)));
+                Some((tonight, 0))
+            }
+            NightPlan::Regular(start) => {
+                if start.with_timezone(&tz).time() != bedtime.0 {
+                    lines.push(
+                        "Bedtime doesn't exist this night because of a daylight saving \
+                         transition, so it's an hour later"
+                            .to_string(),
+                    );
+                }
+                Some((start, self.fuzz.unwrap_or(0)))
+            }
+        };
+
+        if let Some((start, fuzz)) = start {
+            let fuzz = chrono::Duration::minutes(i64::from(fuzz));
+            let window = SleepWindow::starting_at(start, self.sleep_goal());
+            let timeout = nag_timeout;
+            lines.push(format!(
+                "Reminders start at {}, and the sleep window ends at {}",
+                fmt(start),
+                fmt(window.end)
+            ));
+            if at < start - fuzz {
+                lines.push(format!(
+                    "Waiting for bedtime, {} to go",
+                    HumanDuration(start - fuzz - at)
+                ));
+            } else if at < start + fuzz {
+                lines.push(format!(
+                    "Reminders could start any moment, up to {} around bedtime",
+                    HumanDuration(fuzz)
+                ));
+            } else if at >= window.end {
+                lines.push("Sleep window is over, so reminders have stopped".to_string());
+            } else if at - start >= timeout {
+                lines.push(format!(
+                    "Reminders gave up at {}, {} after starting",
+                    fmt(start + timeout),
+                    HumanDuration(timeout)
+                ));
+            } else {
+                let elapsed = at - start;
+                let bedtime = bedtime.to_string();
+                let context = NagContext {
+                    user: id,
+                    name: "the user",
+                    bedtime: &bedtime,
+                    streak: self.history.streak_at_risk(),
+                    pack: self.theme.as_deref(),
+                };
+                lines.push(format!(
+                    "If awake, the user would be nagged {} past bedtime with: {}",
+                    HumanDuration(elapsed),
+                    self.intensity.nag_message(elapsed, self.max_tone, &context)
+                ));
+            }
+        }
+
+        match self.next_bedtime(at) {
+            Some((next, skipped)) => {
+                let mut line = format!("Next reminders start at {}", fmt(next));
+                if !skipped.is_empty() {
+                    let nights: Vec<_> = skipped
+                        .iter()
+                        .map(|(night, reason)| format!("{} ({})", night, reason))
+                        .collect();
+                    line = format!("{}, skipping {}", line, nights.join(", "));
+                }
+                lines.push(line);
+            }
+            None => lines.push("No upcoming reminders".to_string()),
+        }
+
+        lines
     }
 }
 
@@ -209,12 +2101,248 @@ impl fmt::Display for UserInfo {
             None => "none".to_string(),
         };
 
+        let tonight = match (self.tonight, self.time_zone) {
+            (Some(tonight), Some(tz)) if tonight > Utc::now() => {
+                Time(tonight.with_timezone(&tz).time()).to_string()
+            }
+            _ => "none".to_string(),
+        };
+
+        let target = match self.target {
+            Some(target) => format!("{} by {}", target.goal, target.end().format("%B %-d")),
+            None => "none".to_string(),
+        };
+
+        let trip = match self.trip {
+            Some(trip) => format!("{} on {}", trip.time_zone.name(), trip.date),
+            None => "none".to_string(),
+        };
+
+        let group = match (&self.group, self.group_bedtime) {
+            (Some(name), Some(group)) => format!("{} (bedtime {})", name, group.bedtime),
+            (Some(name), None) => name.clone(),
+            (None, _) => "none".to_string(),
+        };
+
+        let skipping = match self.schedule() {
+            Some((tz, bedtime)) => {
+                let next = bedtime.next_after(tz, Utc::now());
+                self.skipped
+                    .contains(&time::night_of(&next.with_timezone(&tz)))
+            }
+            _ => false,
+        };
+
+        let rating = self
+            .history
+            .average_rating(Utc::now().naive_utc().date() - chrono::Duration::days(RATING_NIGHTS));
+        let rating = match rating {
+            Some(rating) => format!("{:.1}/{}", rating, check_in::MAX_RATING),
+            None => "none".to_string(),
+        };
+
+        let debt = match self.sleep_debt() {
+            Some(debt) => HumanDuration(debt).to_string(),
+            None => "unknown".to_string(),
+        };
+
         write!(
             f,
             "**on**: {}\n\
              **time zone**: {}\n\
-             **bedtime**: {}",
-            self.on, time_zone, bedtime
+             **bedtime**: {}\n\
+             **tonight's bedtime**: {}\n\
+             **bedtime target**: {}\n\
+             **trip**: {}\n\
+             **group**: {}\n\
+             **skipping tonight**: {}\n\
+             **holidays**: {}\n\
+             **intensity**: {}\n\
+             **maximum tone**: {}\n\
+             **message pack**: {}\n\
+             **bedtime fuzz**: {}\n\
+             **presence tracking**: {}\n\
+             **idle counts as**: {}\n\
+             **clients counted as awake**: {}\n\
+             **awake threshold**: {}\n\
+             **single reminder message**: {}\n\
+             **morning cleanup**: {}\n\
+             **morning summary**: {}\n\
+             **reply when mentioned asleep**: {}\n\
+             **bedtime lockout**: {}\n\
+             **wake phrase**: {}\n\
+             **level**: {} ({} XP)\n\
+             **sleep rating (last week)**: {}\n\
+             **sleep goal**: {}\n\
+             **sleep debt (last week)**: {}",
+            self.on,
+            time_zone,
+            bedtime,
+            tonight,
+            target,
+            trip,
+            group,
+            skipping,
+            match (self.country, self.holiday_skip) {
+                (Some(country), true) => format!("{}, skipping holiday eves", country),
+                (Some(country), false) => country.to_string(),
+                (None, _) => "none".to_string(),
+            },
+            self.intensity,
+            match self.max_tone {
+                Some(max_tone) => format!("{}/{}", max_tone, intensity::TONES),
+                None => "none".to_string(),
+            },
+            self.theme.as_deref().unwrap_or("default"),
+            match self.fuzz {
+                Some(fuzz) => format!(
+                    "±{}",
+                    HumanDuration(chrono::Duration::minutes(i64::from(fuzz)))
+                ),
+                None => "none".to_string(),
+            },
+            self.track_presence,
+            self.idle_policy,
+            match &self.awake_clients {
+                Some(clients) => clients
+                    .iter()
+                    .map(Client::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                None => "all".to_string(),
+            },
+            match self.awake_threshold {
+                Some(threshold) => format!(
+                    "online for {} or active in the last {}",
+                    HumanDuration(chrono::Duration::minutes(i64::from(
+                        threshold.online_minutes
+                    ))),
+                    HumanDuration(chrono::Duration::minutes(i64::from(
+                        threshold.active_minutes
+                    )))
+                ),
+                None => "none".to_string(),
+            },
+            self.single_nag,
+            self.clean_up,
+            self.morning_summary,
+            self.away_reply,
+            match self.lockout_in.len() {
+                0 => "off".to_string(),
+                1 => "1 server".to_string(),
+                n => format!("{} servers", n),
+            },
+            self.wake_phrase.as_deref().unwrap_or("none"),
+            xp::level(self.xp),
+            self.xp,
+            rating,
+            HumanDuration(self.sleep_goal()),
+            debt
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn rules(time_zone: Tz, bedtime: &str) -> BedtimeRules {
+        BedtimeRules {
+            time_zone,
+            bedtime: bedtime.parse().unwrap(),
+            tonight: None,
+            skipped: BTreeSet::new(),
+            holiday_country: None,
+        }
+    }
+
+    fn local(tz: Tz, year: i32, month: u32, day: u32, hour: u32, min: u32) -> DateTime<Utc> {
+        tz.ymd(year, month, day)
+            .and_hms(hour, min, 0)
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn regular_night() {
+        let tz = chrono_tz::Europe::London;
+        let rules = rules(tz, "11:00 PM");
+        let now = local(tz, 2024, 6, 2, 1, 0);
+        assert_eq!(
+            rules.plan(now),
+            (
+                NaiveDate::from_ymd(2024, 6, 1),
+                NightPlan::Regular(local(tz, 2024, 6, 1, 23, 0))
+            )
+        );
+    }
+
+    #[test]
+    fn bedtime_in_daylight_saving_gap() {
+        // 2:30 AM doesn't exist in New York on 2024-03-10, so it's 3:30 AM
+        let tz = chrono_tz::America::New_York;
+        let rules = rules(tz, "2:30 AM");
+        let start = Utc.ymd(2024, 3, 10).and_hms(7, 30, 0);
+        assert_eq!(
+            rules.plan(start),
+            (NaiveDate::from_ymd(2024, 3, 9), NightPlan::Regular(start))
+        );
+        // The scheduler wakes up at the same instant
+        let before = Utc.ymd(2024, 3, 9).and_hms(12, 0, 0);
+        assert_eq!(rules.bedtime.next_after(tz, before), start);
+    }
+
+    #[test]
+    fn bedtime_in_daylight_saving_overlap() {
+        // 1:30 AM happens twice in New York on 2024-11-03, and the first counts
+        let tz = chrono_tz::America::New_York;
+        let rules = rules(tz, "1:30 AM");
+        let start = Utc.ymd(2024, 11, 3).and_hms(5, 30, 0);
+        assert_eq!(rules.plan(start).1, NightPlan::Regular(start));
+    }
+
+    #[test]
+    fn skip_wins_over_override() {
+        let tz = chrono_tz::Europe::London;
+        let mut rules = rules(tz, "11:00 PM");
+        let tonight = local(tz, 2024, 6, 1, 21, 30);
+        rules.tonight = Some(tonight);
+        assert_eq!(rules.plan(tonight).1, NightPlan::Overridden(tonight));
+
+        rules.skipped.insert(NaiveDate::from_ymd(2024, 6, 1));
+        assert_eq!(
+            rules.plan(tonight).1,
+            NightPlan::Skipped("you skipped it".to_string())
+        );
+        // The override doesn't carry over to the next night
+        assert_eq!(
+            rules.plan(local(tz, 2024, 6, 2, 23, 0)).1,
+            NightPlan::Regular(local(tz, 2024, 6, 2, 23, 0))
+        );
+    }
+
+    #[test]
+    fn override_past_midnight() {
+        let tz = chrono_tz::Europe::London;
+        let mut rules = rules(tz, "11:00 PM");
+        let tonight = local(tz, 2024, 6, 2, 1, 0);
+        rules.tonight = Some(tonight);
+        // The regular bedtime comes first, but the night is overridden
+        assert_eq!(
+            rules.plan(local(tz, 2024, 6, 1, 23, 0)).1,
+            NightPlan::Overridden(tonight)
+        );
+    }
+
+    #[test]
+    fn holiday_eve() {
+        let tz = chrono_tz::Europe::London;
+        let mut rules = rules(tz, "11:00 PM");
+        rules.holiday_country = Some(Country::UnitedKingdom);
+        assert_eq!(
+            rules.plan(local(tz, 2024, 12, 24, 23, 0)).1,
+            NightPlan::Skipped("it's the eve of Christmas Day".to_string())
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use crate::bot::Bot;
+use crate::send_queue;
+use crate::time::HumanDuration;
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serenity::model::id::UserId;
+
+/// Longest wind-down a user can start, in hours
+pub const MAX_HOURS: i64 = 12;
+
+/// How often to check in with a user winding down before bed
+const CHECK_IN_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// DM a user a wind-down check-in
+async fn check_in(bot: &Bot, id: UserId, left: chrono::Duration) -> serenity::Result<()> {
+    send_queue::wait_turn().await;
+    let dm = id.create_dm_channel(&bot.http).await?;
+    dm.say(
+        &bot.http,
+        format!("{} until bedtime... 🕯️", HumanDuration(left)),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Check in with a user periodically until their bedtime, so it doesn't come
+/// as a surprise. Their regular sleep reminders take over at bedtime.
+pub fn spawn(bot: Bot, id: UserId, bedtime: DateTime<Utc>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_IN_INTERVAL);
+        // The first tick completes immediately, and the user was just told
+        // how long they have
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            // Round to the nearest minute, since ticks aren't exact
+            let left = chrono::Duration::minutes(
+                ((bedtime - Utc::now()).num_seconds() as f64 / 60.0).round() as i64,
+            );
+            if left <= chrono::Duration::zero() {
+                break;
+            }
+            if let Err(err) = check_in(&bot, id, left).await {
+                println!("Error sending user wind-down check-in: {}", err);
+            }
+        }
+    })
+}
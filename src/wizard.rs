@@ -0,0 +1,306 @@
+use crate::guild_config::GuildConfig;
+use crate::intensity::Intensity;
+use crate::time::Time;
+use crate::tz_picker;
+
+use std::time::Duration;
+
+use chrono_tz::Tz;
+use serenity::{
+    builder::CreateEmbed,
+    model::interactions::message_component::{ActionRowComponent, InputTextStyle},
+    model::prelude::*,
+    prelude::*,
+    Result,
+};
+
+/// How long to wait for the user to complete each setup step
+const TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Custom ID of the bedtime modal and its text input
+const BEDTIME_ID: &str = "setup_bedtime";
+
+/// Settings collected by the setup wizard
+#[derive(Clone)]
+pub struct Setup {
+    pub time_zone: Tz,
+    pub bedtime: Time,
+    pub intensity: Intensity,
+    pub track_presence: bool,
+}
+
+/// Edit a setup prompt to tell the user that they took too long
+async fn timed_out(ctx: &Context, prompt: &mut Message) -> Result<()> {
+    prompt
+        .edit(ctx, |m| m.content("Setup timed out").components(|c| c))
+        .await
+}
+
+/// Ask a user in a channel to pick one of `options` by pressing a button.
+/// Returns `None` if the user doesn't respond in time.
+async fn choose<T: Copy>(
+    ctx: &Context,
+    channel: ChannelId,
+    user: UserId,
+    question: &str,
+    options: &[(&str, T)],
+) -> Result<Option<T>> {
+    let mut prompt = channel
+        .send_message(&ctx.http, |m| {
+            m.content(question).components(|c| {
+                c.create_action_row(|r| {
+                    for (i, (label, _)) in options.iter().enumerate() {
+                        r.create_button(|b| b.custom_id(i).label(label));
+                    }
+                    r
+                })
+            })
+        })
+        .await?;
+
+    loop {
+        let interaction = prompt
+            .await_component_interaction(ctx)
+            .author_id(user)
+            .timeout(TIMEOUT)
+            .await;
+
+        let interaction = match interaction {
+            Some(interaction) => interaction,
+            None => {
+                timed_out(ctx, &mut prompt).await?;
+                return Ok(None);
+            }
+        };
+
+        let chosen = interaction
+            .data
+            .custom_id
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| options.get(i));
+        let (label, value) = match chosen {
+            Some(&chosen) => chosen,
+            None => continue,
+        };
+
+        interaction
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.content(format!("{} **{}**", question, label))
+                            .components(|c| c)
+                    })
+            })
+            .await?;
+
+        return Ok(Some(value));
+    }
+}
+
+/// Ask a user in a channel to type their bedtime into a modal, repeating until
+/// it parses. The modal is pre-filled with `suggested`, if given. Returns
+/// `None` if the user doesn't respond in time.
+async fn ask_bedtime(
+    ctx: &Context,
+    channel: ChannelId,
+    user: UserId,
+    suggested: Option<Time>,
+) -> Result<Option<Time>> {
+    let question = "When do you want to go to bed?";
+
+    let mut prompt = channel
+        .send_message(&ctx.http, |m| {
+            m.content(question).components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| b.custom_id(BEDTIME_ID).label("Enter bedtime"))
+                })
+            })
+        })
+        .await?;
+
+    loop {
+        let button = prompt
+            .await_component_interaction(ctx)
+            .author_id(user)
+            .timeout(TIMEOUT)
+            .await;
+
+        let button = match button {
+            Some(button) => button,
+            None => {
+                timed_out(ctx, &mut prompt).await?;
+                return Ok(None);
+            }
+        };
+
+        button
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::Modal)
+                    .interaction_response_data(|d| {
+                        d.custom_id(BEDTIME_ID).title("Bedtime").components(|c| {
+                            c.create_action_row(|r| {
+                                r.create_input_text(|t| {
+                                    t.custom_id(BEDTIME_ID)
+                                        .label("Bedtime")
+                                        .placeholder("10:30 PM")
+                                        .style(InputTextStyle::Short)
+                                        .required(true);
+                                    if let Some(suggested) = suggested {
+                                        t.value(suggested);
+                                    }
+                                    t
+                                })
+                            })
+                        })
+                    })
+            })
+            .await?;
+
+        let submit = prompt
+            .await_modal_interaction(ctx)
+            .author_id(user)
+            .timeout(TIMEOUT)
+            .await;
+
+        let submit = match submit {
+            Some(submit) => submit,
+            None => {
+                timed_out(ctx, &mut prompt).await?;
+                return Ok(None);
+            }
+        };
+
+        let text = submit
+            .data
+            .components
+            .iter()
+            .flat_map(|row| &row.components)
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) => Some(input.value.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        match text.parse::<Time>() {
+            Ok(bedtime) => {
+                submit
+                    .create_interaction_response(&ctx.http, |r| {
+                        r.kind(InteractionResponseType::UpdateMessage)
+                            .interaction_response_data(|d| {
+                                d.content(format!("{} **{}**", question, bedtime))
+                                    .components(|c| c)
+                            })
+                    })
+                    .await?;
+                return Ok(Some(bedtime));
+            }
+            Err(_) => {
+                submit
+                    .create_interaction_response(&ctx.http, |r| {
+                        r.kind(InteractionResponseType::UpdateMessage)
+                            .interaction_response_data(|d| {
+                                d.content(format!(
+                                    "{} I couldn't read `{}`. Try something like `10:30 PM`, `10pm`, or `22:30`.",
+                                    question, text
+                                ))
+                            })
+                    })
+                    .await?;
+            }
+        }
+    }
+}
+
+/// Ask a user in a channel to pick their time zone, offering `suggested`
+/// first, if given, and highlighting `locale` in the picker. Returns `None` if
+/// the user doesn't respond in time.
+async fn ask_time_zone(
+    ctx: &Context,
+    channel: ChannelId,
+    user: UserId,
+    suggested: Option<Tz>,
+    locale: Option<Tz>,
+) -> Result<Option<Tz>> {
+    if let Some(suggested) = suggested {
+        let question = format!("Is your time zone {}?", suggested.name());
+        let options = [("Yes", true), ("Pick another", false)];
+        match choose(ctx, channel, user, &question, &options).await? {
+            Some(true) => return Ok(Some(suggested)),
+            Some(false) => {}
+            None => return Ok(None),
+        }
+    }
+
+    tz_picker::pick_time_zone(ctx, channel, user, locale).await
+}
+
+/// Walk a user in a channel through picking their time zone, bedtime, nag
+/// intensity, and privacy settings, suggesting the guild's defaults, or the
+/// time zone guessed from the user's `locale` if the guild has none. Returns
+/// `None` if the user abandons setup.
+pub async fn run(
+    ctx: &Context,
+    channel: ChannelId,
+    user: UserId,
+    defaults: &GuildConfig,
+    locale: Option<Tz>,
+) -> Result<Option<Setup>> {
+    let suggested = defaults.default_time_zone.or(locale);
+    let time_zone = ask_time_zone(ctx, channel, user, suggested, locale).await?;
+    let time_zone = match time_zone {
+        Some(time_zone) => time_zone,
+        None => return Ok(None),
+    };
+
+    let bedtime = match ask_bedtime(ctx, channel, user, defaults.default_bedtime).await? {
+        Some(bedtime) => bedtime,
+        None => return Ok(None),
+    };
+
+    let intensities: Vec<_> = Intensity::ALL
+        .iter()
+        .map(|&intensity| (intensity.name(), intensity))
+        .collect();
+    let intensity = choose(
+        ctx,
+        channel,
+        user,
+        "How insistent should your sleep reminders be?",
+        &intensities,
+    )
+    .await?;
+    let intensity = match intensity {
+        Some(intensity) => intensity,
+        None => return Ok(None),
+    };
+
+    let track_presence = choose(
+        ctx,
+        channel,
+        user,
+        "Can I watch your online status, so I only remind you while you're awake?",
+        &[("Yes", true), ("No", false)],
+    )
+    .await?;
+    let track_presence = match track_presence {
+        Some(track_presence) => track_presence,
+        None => return Ok(None),
+    };
+
+    Ok(Some(Setup {
+        time_zone,
+        bedtime,
+        intensity,
+        track_presence,
+    }))
+}
+
+/// Build an embed summarizing the settings chosen in the setup wizard
+pub fn summary<'a>(e: &'a mut CreateEmbed, setup: &Setup) -> &'a mut CreateEmbed {
+    e.title("You're all set 🌙")
+        .field("Time zone", setup.time_zone.name(), true)
+        .field("Bedtime", setup.bedtime, true)
+        .field("Intensity", setup.intensity, true)
+        .field("Presence tracking", setup.track_presence, true)
+}
@@ -0,0 +1,26 @@
+/// Experience points awarded for going to bed on time one night, before the
+/// streak bonus
+const BASE_XP: u64 = 10;
+
+/// Longest streak of on-time nights that keeps increasing the XP awarded
+const MAX_STREAK_BONUS: u64 = 7;
+
+/// Experience points needed to reach level 2. Each level after that needs
+/// more than the last.
+const LEVEL_XP: u64 = 100;
+
+/// Get the experience points awarded for going to bed on time, given the
+/// current streak of on-time nights, including tonight
+pub fn award(streak: u64) -> u64 {
+    BASE_XP * streak.clamp(1, MAX_STREAK_BONUS)
+}
+
+/// Get the level reached with some amount of lifetime experience points.
+/// Reaching level `n` takes `LEVEL_XP * (n - 1)^2` XP.
+pub fn level(xp: u64) -> u64 {
+    let mut level = 1;
+    while LEVEL_XP * level * level <= xp {
+        level += 1;
+    }
+    level
+}